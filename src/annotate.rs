@@ -0,0 +1,38 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::time::Instant;
+
+/// Operator notes such as "deployed new build here", each the contents of
+/// --annotation-file when the process received SIGUSR2.
+pub struct Annotations {
+    notes: Arc<Mutex<Vec<(Instant, String)>>>,
+}
+
+impl Annotations {
+    /// Starts recording. From here on SIGUSR2 no longer terminates the process.
+    pub fn listen(path: String) -> std::io::Result<Annotations> {
+        let mut signals = signal(SignalKind::user_defined2())?;
+        let notes = Arc::new(Mutex::new(Vec::new()));
+        let recorded = notes.clone();
+        tokio::spawn(async move {
+            while signals.recv().await.is_some() {
+                let at = Instant::now();
+                let note = match std::fs::read_to_string(&path) {
+                    Ok(text) => text.trim().to_owned(),
+                    Err(e) => format!("(cannot read {}: {})", path, e),
+                };
+                println!("Annotation: {}", note);
+                recorded.lock().unwrap().push((at, note));
+            }
+        });
+        Ok(Annotations { notes })
+    }
+
+    /// The notes so far, as offsets from `start`; notes from before it are at zero.
+    pub fn since(&self, start: Instant) -> Vec<(Duration, String)> {
+        self.notes.lock().unwrap().iter()
+            .map(|(at, note)| (at.saturating_duration_since(start), note.clone()))
+            .collect()
+    }
+}
@@ -0,0 +1,56 @@
+use crate::request::{RequestError, Response};
+use std::sync::{Mutex, OnceLock};
+
+/// Capacity consumed by every request of the run, warmup and cooldown
+/// included, and the --max-rcu-budget/--max-wcu-budget it may not cross.
+#[derive(Default)]
+pub struct Budget {
+    max_read_units: Option<f64>,
+    max_write_units: Option<f64>,
+    /// Read and write units consumed so far
+    consumed: Mutex<(f64, f64)>,
+    /// Why the run stopped, once a budget was crossed
+    exhausted: OnceLock<String>,
+}
+
+impl Budget {
+    pub fn new(max_read_units: Option<f64>, max_write_units: Option<f64>) -> Budget {
+        Budget { max_read_units, max_write_units, ..Default::default() }
+    }
+
+    pub fn record(&self, result: &Result<Response, RequestError>) {
+        if let Ok(response) = result {
+            let mut consumed = self.consumed.lock().unwrap();
+            consumed.0 += response.read_units;
+            consumed.1 += response.write_units;
+        }
+    }
+
+    /// Read and write units consumed so far.
+    pub fn consumed(&self) -> (f64, f64) {
+        *self.consumed.lock().unwrap()
+    }
+
+    /// Whether a budget has been crossed, in which case no more requests
+    /// should be sent. Announces it the first time.
+    pub fn is_exhausted(&self) -> bool {
+        if self.exhausted.get().is_some() {
+            return true;
+        }
+        let (read_units, write_units) = self.consumed();
+        let reason = match (self.max_read_units, self.max_write_units) {
+            (Some(max), _) if read_units >= max => format!("{:.1} RCU consumed, budget {}", read_units, max),
+            (_, Some(max)) if write_units >= max => format!("{:.1} WCU consumed, budget {}", write_units, max),
+            _ => return false,
+        };
+        if self.exhausted.set(reason).is_ok() {
+            println!("Capacity budget exhausted ({}), stopping the run", self.exhausted.get().unwrap());
+        }
+        true
+    }
+
+    /// Why the run stopped early, if it did.
+    pub fn exhausted(&self) -> Option<&str> {
+        self.exhausted.get().map(String::as_str)
+    }
+}
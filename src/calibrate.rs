@@ -0,0 +1,61 @@
+use crate::mock::Mock;
+use crate::{quantile_ms, workload, BenchArgs, Cli};
+use aws_sdk_dynamodb::error::{DisplayErrorContext, SdkError};
+use aws_sdk_dynamodb::Client;
+use clap::error::ErrorKind;
+use clap::CommandFactory;
+use std::time::Instant;
+
+/// Calls that open and warm the connection before any is timed...
+const WARMUP_CALLS: usize = 20;
+/// ...and calls timed after them.
+const CALLS: usize = 200;
+
+/// Round-trip latency of the client stack to an endpoint next to it, below
+/// which no measured latency can go.
+pub struct Floor {
+    pub endpoint: String,
+    pub p50: f64,
+    pub p99: f64,
+}
+
+impl Floor {
+    pub fn describe(&self) -> String {
+        format!("p50 {:.3} ms, p99 {:.3} ms ({} calls to {})", self.p50, self.p99, CALLS, self.endpoint)
+    }
+}
+
+/// Times GetItems of an absent key against --calibration-endpoint, or an
+/// in-process mock, through `client`'s configuration: the same HTTP client,
+/// interceptors, retries and signing as the run. Error replies count, since
+/// DynamoDB Local without the table still answers; an unreachable endpoint doesn't.
+pub async fn measure(client: &Client, cli: &Cli, args: &BenchArgs) -> Floor {
+    let (endpoint, _mock) = match &args.calibration_endpoint {
+        Some(endpoint) => (endpoint.clone(), None),
+        None => {
+            let mock = Mock::start().await.unwrap_or_else(|e| Cli::command().error(ErrorKind::Io,
+                format!("cannot start the calibration endpoint: {}", e)).exit());
+            (mock.endpoint_url.clone(), Some(mock))
+        }
+    };
+    let calibration = Client::from_conf(client.config().to_builder().endpoint_url(&endpoint).build());
+    let get = calibration.get_item()
+        .table_name(&cli.table)
+        .set_key(Some(workload::missing_key(cli, 0)));
+
+    let mut durations = Vec::with_capacity(CALLS);
+    for n in 0..WARMUP_CALLS + CALLS {
+        let start = Instant::now();
+        let result = get.clone().send().await;
+        let elapsed = start.elapsed();
+        if let Err(e @ (SdkError::DispatchFailure(_) | SdkError::TimeoutError(_))) = &result {
+            Cli::command().error(ErrorKind::Io, format!("cannot reach calibration endpoint {}: {}",
+                endpoint, DisplayErrorContext(e))).exit();
+        }
+        if n >= WARMUP_CALLS {
+            durations.push(elapsed);
+        }
+    }
+    durations.sort();
+    Floor { endpoint, p50: quantile_ms(&durations, 0.5), p99: quantile_ms(&durations, 0.99) }
+}
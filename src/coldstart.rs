@@ -0,0 +1,172 @@
+use crate::request::KeyType;
+use crate::{load_config, make_client, percentile_ms, quantile_ms, Cli};
+use aws_sdk_dynamodb::error::DisplayErrorContext;
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, ValueEnum};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// Name of the hidden subcommand each invocation process runs.
+const INVOCATION: &str = "cold-start-invocation";
+
+#[derive(Args, Debug, Clone)]
+pub struct ColdStartArgs {
+    /// Invocations, each a fresh process with its own SDK config, client and connections
+    #[arg(long, default_value = "50")]
+    invocations: usize,
+
+    /// Invocations running at once
+    #[arg(long, default_value = "1")]
+    concurrency: usize,
+
+    /// GetItems sent by each invocation; those after the first show the warm latency
+    #[arg(long, default_value = "1")]
+    requests: usize,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct InvocationArgs {
+    /// Key attribute of the table, as `name=TYPE`: the partition key, then the sort key if any
+    #[arg(long, value_parser = crate::parse_assignment, required = true)]
+    key: Vec<(String, String)>,
+
+    /// GetItems to send
+    #[arg(long, default_value = "1")]
+    requests: usize,
+}
+
+/// Runs one invocation: loads the config, builds a client and sends
+/// GetItems of a missing key, then prints its timings as a JSON line.
+pub async fn invoke(cli: &Cli, args: &InvocationArgs) {
+    let key: HashMap<_, _> = args.key.iter().map(|(name, key_type)| {
+        let key_type = KeyType::from_str(key_type, true)
+            .unwrap_or_else(|e| Cli::command().error(ErrorKind::InvalidValue, format!("--key {}: {}", name, e)).exit());
+        (name.clone(), key_type.value(&key_type.missing(0)))
+    }).collect();
+
+    let start = Instant::now();
+    let config = load_config(cli).await;
+    let config_loaded = start.elapsed();
+    let client = make_client(&config);
+    let client_built = start.elapsed() - config_loaded;
+    let mut requests = Vec::new();
+    for _ in 0..args.requests {
+        let request_start = Instant::now();
+        if let Err(e) = client.get_item().table_name(&cli.table).set_key(Some(key.clone())).send().await {
+            Cli::command().error(ErrorKind::Io, format!("GetItem failed: {}", DisplayErrorContext(&e))).exit();
+        }
+        requests.push(request_start.elapsed().as_secs_f64() * 1000.0);
+    }
+    println!("{}", json!({
+        "config_ms": config_loaded.as_secs_f64() * 1000.0,
+        "client_ms": client_built.as_secs_f64() * 1000.0,
+        "requests_ms": requests,
+    }));
+}
+
+/// Timings of one invocation; the process time is measured by the parent.
+#[derive(Default)]
+struct Timings {
+    process: Vec<Duration>,
+    config: Vec<Duration>,
+    client: Vec<Duration>,
+    first: Vec<Duration>,
+    warm: Vec<Duration>,
+}
+
+/// Parses the JSON line an invocation printed into `timings`.
+fn record(timings: &mut Timings, output: &str, process: Duration) -> Option<()> {
+    let line: Value = serde_json::from_str(output.lines().last()?).ok()?;
+    let ms = |value: &Value| value.as_f64().map(|ms| Duration::from_secs_f64(ms / 1000.0));
+    let requests = line["requests_ms"].as_array()?.iter().map(ms).collect::<Option<Vec<_>>>()?;
+    timings.config.push(ms(&line["config_ms"])?);
+    timings.client.push(ms(&line["client_ms"])?);
+    timings.first.push(*requests.first()?);
+    timings.warm.extend(&requests[1..]);
+    timings.process.push(process);
+    Some(())
+}
+
+/// Starts --invocations fresh processes of this binary, --concurrency at a
+/// time, each of which loads the SDK config, builds a client and sends its
+/// GetItems of a missing key over new connections, as a cold Lambda
+/// invocation does, and reports the distribution of each step. Process time
+/// covers startup and exit too, but not the Lambda runtime's own init.
+pub async fn run(cli: &Cli, args: &ColdStartArgs) {
+    if args.invocations == 0 || args.concurrency == 0 || args.requests == 0 {
+        Cli::command().error(ErrorKind::ValueValidation, "--invocations, --concurrency and --requests must be positive").exit();
+    }
+    let exe = std::env::current_exe()
+        .unwrap_or_else(|e| Cli::command().error(ErrorKind::Io, format!("cannot locate this binary: {}", e)).exit());
+    // the global options as given, followed by the invocation with the detected key schema
+    let argv: Vec<String> = std::env::args().skip(1).collect();
+    let global = argv.iter().position(|arg| arg == "cold-start").map_or(&argv[..], |end| &argv[..end]);
+    let mut child_args = global.to_vec();
+    child_args.extend([INVOCATION.to_owned(), "--requests".to_owned(), args.requests.to_string()]);
+    child_args.extend(["--key".to_owned(), format!("{}={:?}", cli.partition_key, cli.partition_type)]);
+    if !cli.no_sort_key {
+        child_args.extend(["--key".to_owned(), format!("{}={:?}", cli.sort_key, cli.sort_type)]);
+    }
+    println!("Starting {} invocations of {}, {} at a time, each sending {} GetItems of a missing key in {}",
+        args.invocations, exe.display(), args.concurrency, args.requests, cli.table);
+
+    let semaphore = Arc::new(Semaphore::new(args.concurrency));
+    let mut tasks = JoinSet::new();
+    for _ in 0..args.invocations {
+        let permit = semaphore.clone().acquire_owned().await.unwrap();
+        let mut command = Command::new(&exe);
+        command.args(&child_args);
+        tasks.spawn(async move {
+            let start = Instant::now();
+            let output = command.output().await;
+            drop(permit);
+            (output, start.elapsed())
+        });
+    }
+    let mut timings = Timings::default();
+    let mut failures = Vec::new();
+    while let Some(result) = tasks.join_next().await {
+        let (output, process) = result.expect("invocation task panicked");
+        match output {
+            Ok(output) if output.status.success() => {
+                if record(&mut timings, &String::from_utf8_lossy(&output.stdout), process).is_none() {
+                    failures.push("unreadable invocation output".to_owned());
+                }
+            }
+            Ok(output) => failures.push(String::from_utf8_lossy(&output.stderr).trim().to_owned()),
+            Err(e) => failures.push(format!("cannot start invocation: {}", e)),
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("\n{} invocations failed, the first with: {}", failures.len(), failures[0]);
+    }
+    if timings.process.is_empty() {
+        return;
+    }
+    for durations in [&mut timings.process, &mut timings.config, &mut timings.client, &mut timings.first, &mut timings.warm] {
+        durations.sort();
+    }
+    println!("\nCold start over {} invocations (milliseconds):", timings.process.len());
+    println!("{:<20} {:>24} {:>24} {:>24} {:>10}", "Step", "p50", "p90", "p99", "Max");
+    let steps = [
+        ("Whole process", &timings.process),
+        ("Config load", &timings.config),
+        ("Client build", &timings.client),
+        ("First request", &timings.first),
+        ("Warm requests", &timings.warm),
+    ];
+    for (step, durations) in steps.into_iter().filter(|(_, durations)| !durations.is_empty()) {
+        println!("{:<20} {:>24} {:>24} {:>24} {:>10.3}", step, percentile_ms(durations, 0.5), percentile_ms(durations, 0.9),
+            percentile_ms(durations, 0.99), quantile_ms(durations, 1.0));
+    }
+    if !timings.warm.is_empty() {
+        println!("The first request of an invocation takes {:+.3} ms more than a warm one at p50",
+            quantile_ms(&timings.first, 0.5) - quantile_ms(&timings.warm, 0.5));
+    }
+}
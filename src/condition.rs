@@ -0,0 +1,192 @@
+use crate::keygen;
+use crate::request::Request;
+use crate::timestamp::{self, TimeFormat};
+use crate::workload::{self, Workload};
+use crate::{template, BenchArgs, Cli};
+use aws_sdk_dynamodb::operation::query::builders::QueryFluentBuilder;
+use aws_sdk_dynamodb::Client;
+use regex::Regex;
+use std::ops::Range;
+use std::time::SystemTime;
+
+/// How a --key-condition placeholder gets its value for each request.
+#[derive(Clone, Debug)]
+enum Generator {
+    /// The request's --partition-value
+    Partition,
+    /// One of the values, picked uniformly
+    Choice(Vec<String>),
+    /// An integer in the range, picked uniformly
+    Int(Range<i64>),
+    /// The time this many milliseconds from when the request is drawn
+    Time(i64),
+    Literal(String),
+}
+
+/// Parses `partition`, `choice:a|b|c`, `int:LO..HI` (HI exclusive),
+/// `time:now-1h` or `literal:VALUE`; anything else is a literal.
+fn parse_generator(s: &str) -> Result<Generator, String> {
+    if s == "partition" {
+        return Ok(Generator::Partition);
+    }
+    let Some((kind, spec)) = s.split_once(':') else {
+        return Ok(Generator::Literal(s.to_owned()));
+    };
+    match kind {
+        "choice" => Ok(Generator::Choice(spec.split('|').map(str::to_owned).collect())),
+        "int" => {
+            let invalid = || format!("invalid generator {:?}, expected int:LO..HI", s);
+            let (lo, hi) = spec.split_once("..").ok_or_else(invalid)?;
+            let (lo, hi) = (lo.parse::<i64>().map_err(|_| invalid())?, hi.parse::<i64>().map_err(|_| invalid())?);
+            if lo < hi { Ok(Generator::Int(lo..hi)) } else { Err(format!("generator {:?} has an empty range", s)) }
+        }
+        "time" => match timestamp::parse_relative(spec)? {
+            Some(offset) => Ok(Generator::Time(offset)),
+            None => Err(format!("invalid generator {:?}, expected a relative time like time:now-1h", s)),
+        },
+        "literal" => Ok(Generator::Literal(spec.to_owned())),
+        _ => Ok(Generator::Literal(s.to_owned())),
+    }
+}
+
+impl Generator {
+    /// The value for a request, given its partition value and a uniform draw in [0, 1).
+    fn value(&self, partition: &str, u: f64, time_format: TimeFormat) -> String {
+        match self {
+            Generator::Partition => partition.to_owned(),
+            Generator::Choice(values) => values[((u * values.len() as f64) as usize).min(values.len() - 1)].clone(),
+            Generator::Int(range) => {
+                let span = (range.end - range.start) as f64;
+                (range.start + ((u * span) as i64).min(range.end - range.start - 1)).to_string()
+            }
+            Generator::Time(offset) => time_format.format(timestamp::shift(SystemTime::now(), *offset)),
+            Generator::Literal(value) => value.clone(),
+        }
+    }
+}
+
+/// Queries shaped by a raw --key-condition expression, e.g.
+/// `#pk = :pk AND begins_with(#sk, :prefix)`, whose placeholders are filled
+/// per request by --condition-value generators. `#pk` and `#sk` name the
+/// table's keys; `:pk` defaults to the request's partition value. Key
+/// conditions can only constrain the two keys, so `:pk` is typed as the
+/// partition key and every other value as the sort key. --template-var
+/// variables are drawn once per request and substituted for `{name}` in
+/// every value, correlating the placeholders that use them.
+pub struct Templated<'a> {
+    cli: &'a Cli,
+    args: &'a BenchArgs,
+    query: QueryFluentBuilder,
+    values: Vec<(String, Generator)>,
+    vars: Vec<(String, Generator)>,
+}
+
+/// Draws a value from each generator, using the uniform draws numbered from `first`.
+fn draw(generators: &[(String, Generator)], first: usize, partition: &str, time_format: TimeFormat) -> Vec<(String, String)> {
+    generators.iter().enumerate()
+        .map(|(n, (name, generator))| (name.clone(), generator.value(partition, keygen::uniform(first + n), time_format)))
+        .collect()
+}
+
+/// Substitutes the drawn variables into a drawn value.
+fn fill(value: &str, vars: &[(String, String)]) -> String {
+    let vars: Vec<(&str, &str)> = vars.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+    template::fill(value, &vars)
+}
+
+impl<'a> Templated<'a> {
+    pub fn new(expression: &str, client: &Client, cli: &'a Cli, args: &'a BenchArgs) -> Result<Templated<'a>, String> {
+        let mut values = Vec::new();
+        for (name, generator) in &args.condition_value {
+            if !name.starts_with(':') {
+                return Err(format!("--condition-value placeholders start with ':', got {:?}", name));
+            }
+            values.push((name.clone(), parse_generator(generator)?));
+        }
+        let placeholders = Regex::new(":[A-Za-z0-9_]+").unwrap();
+        let used: Vec<&str> = placeholders.find_iter(expression).map(|m| m.as_str()).collect();
+        if used.contains(&":pk") && !values.iter().any(|(name, _)| name == ":pk") {
+            values.push((":pk".to_owned(), Generator::Partition));
+        }
+        if let Some(missing) = used.iter().find(|name| !values.iter().any(|(n, _)| n == *name)) {
+            return Err(format!("--key-condition uses {} but no --condition-value gives it", missing));
+        }
+        if let Some((unused, _)) = values.iter().find(|(name, _)| !used.contains(&name.as_str())) {
+            return Err(format!("--condition-value {} isn't used by --key-condition", unused));
+        }
+        let mut vars = Vec::new();
+        for (name, generator) in &args.template_var {
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!("--template-var names are letters, digits and '_', got {:?}", name));
+            }
+            vars.push((name.clone(), parse_generator(generator)?));
+        }
+        let references = Regex::new(r"\{([A-Za-z0-9_]+)\}").unwrap();
+        let referenced: Vec<&str> = args.condition_value.iter()
+            .flat_map(|(_, generator)| references.captures_iter(generator).map(|c| c.get(1).unwrap().as_str()))
+            .collect();
+        if let Some(missing) = referenced.iter().find(|name| !vars.iter().any(|(n, _)| n == *name)) {
+            return Err(format!("--condition-value uses {{{}}} but no --template-var gives it", missing));
+        }
+        if let Some((unused, _)) = vars.iter().find(|(name, _)| !referenced.contains(&name.as_str())) {
+            return Err(format!("--template-var {} isn't used by any --condition-value", unused));
+        }
+        if values.iter().chain(&vars).any(|(_, generator)| matches!(generator, Generator::Partition)) && args.partition_value.is_empty() {
+            return Err("--key-condition needs -P values for its partition placeholders".to_owned());
+        }
+        // values are checked with the variables' first draws substituted
+        let partition = args.partition_value.first().map_or("", String::as_str);
+        let sample_vars = draw(&vars, 0, partition, args.time_format);
+        for (name, generator) in &values {
+            let key_type = if name == ":pk" { cli.partition_type } else { cli.sort_type };
+            let samples = match generator {
+                Generator::Partition => continue,
+                Generator::Choice(choices) => choices.clone(),
+                _ => vec![generator.value("", 0.0, args.time_format)],
+            };
+            for sample in samples {
+                key_type.parse(&fill(&sample, &sample_vars)).map_err(|e| format!("--condition-value {}: {}", name, e))?;
+            }
+        }
+
+        // only the names the expression uses, as DynamoDB rejects unused ones
+        let names = Regex::new("#[A-Za-z0-9_]+").unwrap();
+        let mut query = client.query()
+            .table_name(&cli.table)
+            .set_index_name(args.index_name.clone())
+            .set_limit(args.limit)
+            .set_scan_index_forward(args.descending.then_some(false))
+            .key_condition_expression(expression);
+        for name in names.find_iter(expression).map(|m| m.as_str()) {
+            let attribute = match name {
+                "#pk" => Some(&cli.partition_key),
+                "#sk" if !cli.no_sort_key => Some(&cli.sort_key),
+                _ => args.expression_name.iter().find(|(n, _)| n == name).map(|(_, attribute)| attribute),
+            };
+            let attribute = attribute.ok_or_else(|| format!("--key-condition uses {} but no --expression-name gives it", name))?;
+            query = query.expression_attribute_names(name, attribute);
+        }
+        Ok(Templated { cli, args, query: workload::project(query, &args.projection), values, vars })
+    }
+}
+
+impl Workload for Templated<'_> {
+    fn request(&self, i: usize) -> (Request, bool) {
+        let partitions = &self.args.partition_value;
+        let partition = partitions.get(i % partitions.len().max(1)).map_or("", String::as_str);
+        let mut query = self.query.clone();
+        // a draw per variable and placeholder, so they vary independently
+        // except through the variables they share
+        let first = i * (self.vars.len() + self.values.len());
+        let vars = draw(&self.vars, first, partition, self.args.time_format);
+        for (name, value) in draw(&self.values, first + self.vars.len(), partition, self.args.time_format) {
+            let key_type = if name == ":pk" { self.cli.partition_type } else { self.cli.sort_type };
+            query = query.expression_attribute_values(&name, key_type.value(&fill(&value, &vars)));
+        }
+        (Request::Query(Box::new(query)), false)
+    }
+
+    fn len(&self) -> usize {
+        self.args.partition_value.len().max(1)
+    }
+}
@@ -0,0 +1,194 @@
+use crate::{profile, Cli};
+use clap::error::ErrorKind;
+use clap::{Command, CommandFactory};
+use serde_json::Value;
+
+/// Options given in `tokens`, as (argument id, value) pairs, and the index of
+/// the first positional token (the subcommand, for the top-level command).
+fn scan_options(command: &Command, tokens: &[String]) -> (Vec<(String, Option<String>)>, Option<usize>) {
+    let mut options = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        let (arg, inline) = if let Some(long) = token.strip_prefix("--") {
+            let (name, inline) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_owned())),
+                None => (long, None),
+            };
+            (command.get_arguments().find(|a| a.get_long() == Some(name)), inline)
+        } else if let Some(short) = token.strip_prefix('-').filter(|s| !s.is_empty()) {
+            let mut chars = short.chars();
+            let name = chars.next();
+            let rest = chars.as_str();
+            (command.get_arguments().find(|a| a.get_short() == name), (!rest.is_empty()).then(|| rest.to_owned()))
+        } else {
+            return (options, Some(i));
+        };
+        if let Some(arg) = arg {
+            let value = if inline.is_some() || !arg.get_action().takes_values() {
+                inline
+            } else {
+                i += 1;
+                tokens.get(i).cloned()
+            };
+            options.push((arg.get_id().to_string(), value));
+        }
+        i += 1;
+    }
+    (options, None)
+}
+
+fn fail(message: String) -> ! {
+    Cli::command().error(ErrorKind::InvalidValue, message).exit()
+}
+
+fn load(path: &str) -> serde_json::Map<String, Value> {
+    let contents = std::fs::read_to_string(path).unwrap_or_else(|e| fail(format!("cannot read {}: {}", path, e)));
+    let parsed = if path.ends_with(".toml") {
+        toml::from_str::<Value>(&contents).map_err(|e| e.to_string())
+    } else {
+        serde_yaml::from_str::<Value>(&contents).map_err(|e| e.to_string())
+    };
+    match parsed.unwrap_or_else(|e| fail(format!("cannot parse {}: {}", path, e))) {
+        Value::Object(settings) => settings,
+        _ => fail(format!("{} must contain a mapping of settings", path)),
+    }
+}
+
+/// Turns config `settings` into flags of `command`, skipping those already
+/// given on the command line (`given`), so explicit flags take precedence.
+fn to_flags(command: &Command, settings: &serde_json::Map<String, Value>, given: &[(String, Option<String>)], path: &str) -> Vec<String> {
+    let mut flags = Vec::new();
+    for (key, value) in settings {
+        if value.is_object() {
+            continue;
+        }
+        let long = key.replace('_', "-");
+        let arg = command.get_arguments().find(|a| a.get_long() == Some(&long))
+            .unwrap_or_else(|| fail(format!("unknown setting {:?} in {}", key, path)));
+        if given.iter().any(|(id, _)| id == arg.get_id().as_str()) {
+            continue;
+        }
+        let values = match value {
+            Value::Array(values) => values.clone(),
+            value => vec![value.clone()],
+        };
+        for value in values {
+            match value {
+                Value::Bool(enabled) if !arg.get_action().takes_values() => {
+                    if enabled {
+                        flags.push(format!("--{}", long));
+                    }
+                }
+                Value::String(s) => flags.push(format!("--{}={}", long, s)),
+                Value::Null => {}
+                value => flags.push(format!("--{}={}", long, value)),
+            }
+        }
+    }
+    flags
+}
+
+/// Adds the `defaults` not already in `settings`, whichever of `_` or `-` either spells keys with.
+fn merge_defaults(settings: &mut serde_json::Map<String, Value>, defaults: serde_json::Map<String, Value>) {
+    let normalize = |key: &str| key.replace('_', "-");
+    for (key, value) in defaults {
+        if !settings.keys().any(|k| normalize(k) == normalize(&key)) {
+            settings.insert(key, value);
+        }
+    }
+}
+
+/// Expands `--config <file>` and `--chalk-profile <name>` into the flags they define.
+///
+/// Top-level keys of the file are global options (`table`, `region`, ...);
+/// a mapping named after the subcommand holds that subcommand's options.
+/// Keys are long flag names, with `_` accepted for `-`. Lists repeat a flag.
+/// A profile (see [`profile`]) fills in whatever the file doesn't set.
+pub fn expand_args(args: Vec<String>) -> Vec<String> {
+    let command = Cli::command();
+    let (global, subcommand_index) = scan_options(&command, &args[1..]);
+    let Some(subcommand_index) = subcommand_index.map(|i| i + 1) else {
+        return args;
+    };
+    let Some(subcommand) = command.find_subcommand(&args[subcommand_index]) else {
+        return args;
+    };
+    let (options, _) = scan_options(subcommand, &args[subcommand_index + 1..]);
+    let option = |name: &str| options.iter().find(|(id, _)| id == name).and_then(|(_, value)| value.clone());
+    let (config, profile) = (option("config"), option("chalk_profile"));
+    if config.is_none() && profile.is_none() {
+        return args;
+    }
+    let path = config.clone().unwrap_or_else(|| format!("--chalk-profile {}", profile.as_deref().unwrap()));
+
+    let mut settings = config.as_deref().map(load).unwrap_or_default();
+    let mut section = match settings.remove(subcommand.get_name()) {
+        Some(Value::Object(section)) => section,
+        // the phase list of a `phases` file, expanded phase by phase by `phases`
+        Some(Value::Array(_)) if subcommand.get_name() == "phases" => serde_json::Map::new(),
+        Some(_) => fail(format!("{:?} in {} must be a mapping of settings", subcommand.get_name(), path)),
+        None => serde_json::Map::new(),
+    };
+    let profile = profile.or_else(|| match section.get("chalk_profile").or(section.get("chalk-profile")) {
+        Some(Value::String(name)) => Some(name.clone()),
+        _ => None,
+    });
+    if let Some(name) = profile {
+        // unknown names are left for clap to reject with the list of profiles
+        if let Some(mut defaults) = profile::settings(&name) {
+            if let Some(Value::Object(profile_section)) = defaults.remove(subcommand.get_name()) {
+                merge_defaults(&mut section, profile_section);
+            }
+            merge_defaults(&mut settings, defaults);
+        }
+    }
+    let mut expanded = vec![args[0].clone()];
+    expanded.extend(to_flags(&command, &settings, &global, &path));
+    expanded.extend_from_slice(&args[1..=subcommand_index]);
+    expanded.extend(to_flags(subcommand, &section, &options, &path));
+    expanded.extend_from_slice(&args[subcommand_index + 1..]);
+    expanded
+}
+
+/// The phases listed under `phases` in the config file at `path`, each as its
+/// name, the command line that runs it (the global options of the file and of
+/// `args`, the `phases` command line, then the phase's subcommand with the
+/// settings of its mapping, expanded like any other command line) and its
+/// `before`, `after` and `env` hook settings.
+pub fn phases(args: &[String], path: &str) -> Vec<(String, Vec<String>, serde_json::Map<String, Value>)> {
+    let command = Cli::command();
+    let (global, subcommand_index) = scan_options(&command, &args[1..]);
+    let global_tokens = &args[1..subcommand_index.map_or(args.len(), |i| i + 1)];
+    let mut settings = load(path);
+    let phases = match settings.remove("phases") {
+        Some(Value::Array(phases)) if !phases.is_empty() => phases,
+        _ => fail(format!("{} must list the phases to run under `phases`", path)),
+    };
+    let global_flags = to_flags(&command, &settings, &global, path);
+    phases.into_iter().enumerate().map(|(n, phase)| {
+        let Value::Object(mut phase) = phase else {
+            fail(format!("phase {} in {} must be a mapping", n + 1, path))
+        };
+        let name = match phase.remove("name") {
+            Some(Value::String(name)) => name,
+            None => format!("phase {}", n + 1),
+            Some(_) => fail(format!("the name of phase {} in {} must be a string", n + 1, path)),
+        };
+        let hooks = ["before", "after", "env"].into_iter()
+            .filter_map(|key| phase.remove(key).map(|value| (key.to_owned(), value)))
+            .collect();
+        let mut sections = phase.into_iter();
+        let (Some((subcommand_name, Value::Object(section))), None) = (sections.next(), sections.next()) else {
+            fail(format!("phase {:?} in {} must hold one subcommand mapping, e.g. `bench:`, besides its name and hooks", name, path))
+        };
+        let subcommand = command.find_subcommand(&subcommand_name)
+            .unwrap_or_else(|| fail(format!("unknown subcommand {:?} in phase {:?} of {}", subcommand_name, name, path)));
+        let mut phase_args = vec![args[0].clone()];
+        phase_args.extend(global_flags.iter().cloned());
+        phase_args.extend_from_slice(global_tokens);
+        phase_args.push(subcommand_name.clone());
+        phase_args.extend(to_flags(subcommand, &section, &[], path));
+        (name, expand_args(phase_args), hooks)
+    }).collect()
+}
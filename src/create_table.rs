@@ -0,0 +1,157 @@
+use crate::request::KeyType;
+use crate::Cli;
+use aws_sdk_dynamodb::error::{DisplayErrorContext, ProvideErrorMetadata};
+use aws_sdk_dynamodb::types::{
+    AttributeDefinition, BillingMode, GlobalSecondaryIndex, IndexStatus, KeySchemaElement, KeyType as KeyRole,
+    Projection, ProjectionType, ProvisionedThroughput, TableStatus,
+};
+use aws_sdk_dynamodb::Client;
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, ValueEnum};
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Billing {
+    /// Pay per request (on-demand capacity)
+    OnDemand,
+    /// Fixed --read-capacity and --write-capacity
+    Provisioned,
+}
+
+/// A global secondary index, parsed from `NAME=PK[:TYPE][,SK[:TYPE]]`.
+#[derive(Debug, Clone)]
+pub struct Gsi {
+    name: String,
+    partition: (String, KeyType),
+    sort: Option<(String, KeyType)>,
+}
+
+fn parse_attribute(s: &str) -> Result<(String, KeyType), String> {
+    match s.split_once(':') {
+        Some((name, key_type)) => Ok((name.to_owned(), KeyType::from_str(key_type, true)?)),
+        None => Ok((s.to_owned(), KeyType::S)),
+    }
+}
+
+fn parse_gsi(s: &str) -> Result<Gsi, String> {
+    let (name, keys) = s.split_once('=')
+        .ok_or_else(|| format!("expected NAME=PK[:TYPE][,SK[:TYPE]], got {:?}", s))?;
+    let (partition, sort) = match keys.split_once(',') {
+        Some((partition, sort)) => (parse_attribute(partition)?, Some(parse_attribute(sort)?)),
+        None => (parse_attribute(keys)?, None),
+    };
+    Ok(Gsi { name: name.to_owned(), partition, sort })
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CreateTableArgs {
+    /// Capacity mode of the table and its indexes
+    #[arg(long, value_enum, default_value_t = Billing::OnDemand)]
+    billing_mode: Billing,
+
+    /// Read capacity units of the table and each index, with --billing-mode provisioned
+    #[arg(long, default_value = "5")]
+    read_capacity: i64,
+
+    /// Write capacity units of the table and each index, with --billing-mode provisioned
+    #[arg(long, default_value = "5")]
+    write_capacity: i64,
+
+    /// Global secondary index projecting all attributes, as `NAME=PK[:TYPE][,SK[:TYPE]]`
+    /// with types S (the default), N or B, repeatable
+    #[arg(long, value_parser = parse_gsi)]
+    gsi: Vec<Gsi>,
+
+    /// Give up waiting for the table to become ACTIVE after this many seconds
+    #[arg(long, default_value = "300")]
+    wait_secs: u64,
+}
+
+/// Creates --table with the --partition-key/--sort-key (or --no-sort-key)
+/// schema and waits until it and its indexes are ACTIVE.
+pub async fn run(client: &Client, cli: &Cli, args: &CreateTableArgs) {
+    let mut attributes = vec![(cli.partition_key.clone(), cli.partition_type)];
+    if !cli.no_sort_key {
+        attributes.push((cli.sort_key.clone(), cli.sort_type));
+    }
+    for gsi in &args.gsi {
+        for (name, key_type) in std::iter::once(&gsi.partition).chain(&gsi.sort) {
+            match attributes.iter().find(|(existing, _)| existing == name) {
+                Some((_, existing)) if existing != key_type => Cli::command().error(ErrorKind::ValueValidation,
+                    format!("attribute {} is declared both {:?} and {:?}", name, existing, key_type)).exit(),
+                Some(_) => {}
+                None => attributes.push((name.clone(), *key_type)),
+            }
+        }
+    }
+    let throughput = ProvisionedThroughput::builder()
+        .read_capacity_units(args.read_capacity)
+        .write_capacity_units(args.write_capacity)
+        .build()
+        .unwrap();
+    let provisioned = args.billing_mode == Billing::Provisioned;
+
+    let mut create = client.create_table()
+        .table_name(&cli.table)
+        .billing_mode(if provisioned { BillingMode::Provisioned } else { BillingMode::PayPerRequest })
+        .set_provisioned_throughput(provisioned.then(|| throughput.clone()))
+        .set_key_schema(Some(key_schema(&cli.partition_key, (!cli.no_sort_key).then_some(cli.sort_key.as_str()))));
+    for (name, key_type) in &attributes {
+        create = create.attribute_definitions(AttributeDefinition::builder()
+            .attribute_name(name)
+            .attribute_type(key_type.scalar())
+            .build()
+            .unwrap());
+    }
+    for gsi in &args.gsi {
+        create = create.global_secondary_indexes(GlobalSecondaryIndex::builder()
+            .index_name(&gsi.name)
+            .set_key_schema(Some(key_schema(&gsi.partition.0, gsi.sort.as_ref().map(|(name, _)| name.as_str()))))
+            .projection(Projection::builder().projection_type(ProjectionType::All).build())
+            .set_provisioned_throughput(provisioned.then(|| throughput.clone()))
+            .build()
+            .unwrap());
+    }
+    let sort_key = if cli.no_sort_key {
+        "no sort key".to_owned()
+    } else {
+        format!("{} {:?}", cli.sort_key, cli.sort_type)
+    };
+    println!("Creating {} ({} {:?}, {}, {:?}{})", cli.table, cli.partition_key, cli.partition_type,
+        sort_key, args.billing_mode,
+        args.gsi.iter().map(|gsi| format!(", index {}", gsi.name)).collect::<String>());
+    if let Err(e) = create.send().await {
+        let message = match e.code() {
+            Some("ResourceInUseException") => format!("table {} already exists", cli.table),
+            _ => format!("cannot create {}: {}", cli.table, DisplayErrorContext(&e)),
+        };
+        Cli::command().error(ErrorKind::Io, message).exit();
+    }
+
+    let start = Instant::now();
+    loop {
+        let describe = client.describe_table().table_name(&cli.table).send().await
+            .unwrap_or_else(|e| Cli::command().error(ErrorKind::Io,
+                format!("cannot describe {}: {}", cli.table, DisplayErrorContext(&e))).exit());
+        let table = describe.table();
+        let active = table.and_then(|t| t.table_status()) == Some(&TableStatus::Active)
+            && table.is_none_or(|t| t.global_secondary_indexes().iter()
+                .all(|index| index.index_status() == Some(&IndexStatus::Active)));
+        if active {
+            println!("{} is ACTIVE after {:.1}s", cli.table, start.elapsed().as_secs_f64());
+            return;
+        }
+        if start.elapsed() >= Duration::from_secs(args.wait_secs) {
+            Cli::command().error(ErrorKind::Io, format!("{} is still not ACTIVE after {}s", cli.table, args.wait_secs)).exit();
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+fn key_schema(partition: &str, sort: Option<&str>) -> Vec<KeySchemaElement> {
+    let hash = KeySchemaElement::builder().attribute_name(partition).key_type(KeyRole::Hash).build().unwrap();
+    let range = sort.map(|sort| KeySchemaElement::builder().attribute_name(sort).key_type(KeyRole::Range).build().unwrap());
+    std::iter::once(hash).chain(range).collect()
+}
@@ -0,0 +1,111 @@
+use crate::{bench, quantile_ms, timestamp, BenchArgs, Cli, Summary};
+use aws_sdk_dynamodb::config::interceptors::BeforeTransmitInterceptorContextRef;
+use aws_sdk_dynamodb::config::{ConfigBag, Intercept, ProvideCredentials, RuntimeComponents, SharedCredentialsProvider};
+use aws_sdk_dynamodb::error::BoxError;
+use aws_sdk_dynamodb::Client;
+use clap::error::ErrorKind;
+use clap::CommandFactory;
+use std::cell::Cell;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+tokio::task_local! {
+    /// When the current attempt started signing, and the signing time of the request so far.
+    static SIGNING: (Cell<Option<Instant>>, Cell<Duration>);
+}
+
+/// Signing time of every request [`timed`] since the last [`take`].
+static SIGNING_TIMES: Mutex<Vec<Duration>> = Mutex::new(Vec::new());
+
+/// Runs `fut`, recording the time its attempts spent between the signing
+/// hooks of [`SigningInterceptor`]: resolving credentials and signing.
+pub async fn timed<F: Future>(fut: F) -> F::Output {
+    SIGNING.scope((Cell::new(None), Cell::new(Duration::ZERO)), async {
+        let output = fut.await;
+        SIGNING_TIMES.lock().unwrap().push(SIGNING.with(|(_, total)| total.get()));
+        output
+    }).await
+}
+
+/// Takes the signing times recorded so far, sorted.
+fn take() -> Vec<Duration> {
+    let mut times = std::mem::take(&mut *SIGNING_TIMES.lock().unwrap());
+    times.sort();
+    times
+}
+
+/// Times the auth step of every attempt inside a [`timed`] scope. The
+/// orchestrator resolves the identity and signs the request between these two
+/// hooks, inline in the future driving the request.
+#[derive(Debug)]
+pub struct SigningInterceptor;
+
+impl Intercept for SigningInterceptor {
+    fn name(&self) -> &'static str {
+        "SigningInterceptor"
+    }
+
+    fn read_before_signing(
+        &self,
+        _context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let _ = SIGNING.try_with(|(started, _)| started.set(Some(Instant::now())));
+        Ok(())
+    }
+
+    fn read_after_signing(
+        &self,
+        _context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let _ = SIGNING.try_with(|(started, total)| {
+            if let Some(started) = started.take() {
+                total.set(total.get() + started.elapsed());
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Runs the benchmark twice, first through the default credential provider
+/// chain (with the SDK's identity cache, which refreshes credentials as they
+/// near expiry) and then with the credentials it resolved held as static
+/// ones, and compares latency and time spent resolving credentials and signing.
+/// `provider` is the chain `client` was configured with.
+pub async fn compare(client: Client, provider: Option<SharedCredentialsProvider>, cli: &Cli, args: &BenchArgs) {
+    let provider = provider
+        .unwrap_or_else(|| Cli::command().error(ErrorKind::Io, "no credential provider is configured").exit());
+    let credentials = provider.provide_credentials().await
+        .unwrap_or_else(|e| Cli::command().error(ErrorKind::Io, format!("cannot resolve credentials: {}", e)).exit());
+    let expiry = credentials.expiry();
+    let fixed = Client::from_conf(client.config().to_builder().credentials_provider(credentials).build());
+
+    let mut runs: Vec<(&str, Summary, Vec<Duration>)> = Vec::new();
+    for (label, client) in [("Default provider chain", client), ("Static credentials", fixed)] {
+        println!("\n=== {} ===", label);
+        take();
+        let summary = bench(client, cli, args).await;
+        runs.push((label, summary, take()));
+    }
+
+    println!("\nCredentials comparison (milliseconds):");
+    match expiry {
+        Some(expiry) => println!("Resolved credentials expire at {}", timestamp::both(expiry, cli.timezone)),
+        None => println!("Resolved credentials don't expire"),
+    }
+    println!("{:<24} {:>10} {:>10} {:>10} {:>10} {:>10} {:>12} {:>12} {:>12}",
+        "Credentials", "Requests", "p50", "p90", "p99", "p99.9", "Signing p50", "Signing p99", "Signing max");
+    for (label, summary, signing) in &runs {
+        let [p50, p90, p99, p999] = summary.percentiles;
+        let signing_ms = |quantile| if signing.is_empty() { 0.0 } else { quantile_ms(signing, quantile) };
+        println!("{:<24} {:>10} {:>10.3} {:>10.3} {:>10.3} {:>10.3} {:>12.3} {:>12.3} {:>12.3}", label, summary.requests,
+            p50, p90, p99, p999, signing_ms(0.5), signing_ms(0.99), signing_ms(1.0));
+    }
+    let mean = |signing: &[Duration]| signing.iter().sum::<Duration>().as_secs_f64() * 1000.0 / signing.len().max(1) as f64;
+    println!("Mean signing time per request: {:.4} ms with the provider chain, {:.4} ms with static credentials",
+        mean(&runs[0].2), mean(&runs[1].2));
+}
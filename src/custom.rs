@@ -0,0 +1,423 @@
+use crate::request::{self, item_bytes, Request, RequestError, Response};
+use crate::workload::{self, Workload};
+use crate::{BenchArgs, Cli};
+use crate::request::KeyType;
+use aws_sdk_dynamodb::operation::get_item::builders::GetItemFluentBuilder;
+use aws_sdk_dynamodb::operation::put_item::builders::PutItemFluentBuilder;
+use aws_sdk_dynamodb::operation::query::builders::QueryFluentBuilder;
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::Client;
+use clap::error::ErrorKind;
+use clap::CommandFactory;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinSet;
+
+pub type ResponseFuture = Pin<Box<dyn Future<Output = Result<Response, RequestError>> + Send>>;
+
+/// A request the built-in operations don't cover. It sends itself and
+/// interprets the response into what the report needs (items and bytes).
+pub trait CustomRequest: Send + Sync {
+    /// Name the request is reported under, in place of a DynamoDB API name.
+    fn name(&self) -> &'static str;
+
+    fn send(&self) -> ResponseFuture;
+
+    /// The keys this request addresses, for --error-report.
+    fn keys(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The partition and sort key values it reads, when it reads a single
+    /// partition (see [`Request::primary_key`]).
+    fn primary_key(&self, _partition_key: &str, _sort_key: &str) -> Option<(String, Option<String>)> {
+        None
+    }
+}
+
+type Builder = fn(&Client, &Cli, &BenchArgs) -> Box<dyn Workload>;
+
+/// Workloads runnable with `bench --custom <name>`. To benchmark a new
+/// operation shape, implement [`Workload`] (usually over a [`CustomRequest`])
+/// and add it here.
+const REGISTRY: &[(&str, Builder)] = &[
+    ("aggregate-range", AggregateRange::build),
+    ("negative-cache", NegativeCache::build),
+    ("query-then-get", QueryThenGet::build),
+    ("split-range", SplitRange::build),
+];
+
+pub fn names() -> impl Iterator<Item = &'static str> {
+    REGISTRY.iter().map(|(name, _)| *name)
+}
+
+pub fn build(name: &str, client: &Client, cli: &Cli, args: &BenchArgs) -> Box<dyn Workload> {
+    let (_, build) = REGISTRY.iter().find(|(n, _)| *n == name).expect("--custom is validated against the registry");
+    build(client, cli, args)
+}
+
+/// Cycles through a fixed list of requests, one per partition value.
+struct Cycle(Vec<Request>);
+
+impl Workload for Cycle {
+    fn request(&self, i: usize) -> (Request, bool) {
+        (self.0[i % self.0.len()].clone(), false)
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Reads the whole --sort-start..--sort-end range of a partition, following
+/// every page, the way an aggregation over a bucket range does. One request
+/// is the complete aggregation, however many pages it takes.
+struct AggregateRange {
+    query: QueryFluentBuilder,
+}
+
+impl AggregateRange {
+    fn build(client: &Client, cli: &Cli, args: &BenchArgs) -> Box<dyn Workload> {
+        let requests = workload::make_query(client, cli, args).into_iter()
+            .filter_map(|request| match request {
+                Request::Query(query) => Some(aggregate(*query)),
+                _ => None,
+            })
+            .collect();
+        Box::new(Cycle(requests))
+    }
+}
+
+/// A query that reads its whole range, every page, as one request.
+pub fn aggregate(query: QueryFluentBuilder) -> Request {
+    Request::Custom(Arc::new(AggregateRange { query }))
+}
+
+impl CustomRequest for AggregateRange {
+    fn name(&self) -> &'static str {
+        "AggregateRange"
+    }
+
+    fn send(&self) -> ResponseFuture {
+        let query = self.query.clone();
+        Box::pin(async move {
+            let mut response = Response::default();
+            let mut exclusive_start_key = None;
+            loop {
+                let page_start = Instant::now();
+                let resp = query.clone()
+                    .set_exclusive_start_key(exclusive_start_key)
+                    .return_consumed_capacity(request::return_capacity())
+                    .send()
+                    .await?;
+                response.pages.push(page_start.elapsed());
+                response.add_capacity(resp.consumed_capacity(), false);
+                response.items += resp.count().max(0) as usize;
+                response.bytes += resp.items().iter().map(item_bytes).sum::<usize>();
+                exclusive_start_key = resp.last_evaluated_key;
+                if exclusive_start_key.is_none() {
+                    return Ok(response);
+                }
+            }
+        })
+    }
+
+    fn keys(&self) -> Vec<String> {
+        Request::Query(Box::new(self.query.clone())).keys(&[])
+    }
+
+    fn primary_key(&self, partition_key: &str, sort_key: &str) -> Option<(String, Option<String>)> {
+        Request::Query(Box::new(self.query.clone())).primary_key(partition_key, sort_key)
+    }
+}
+
+/// Queries a partition, then GetItems every item the first page returned:
+/// the two-hop pattern of reading IDs and fetching what they point to. One
+/// request is the query and all its GetItems, which run concurrently the way
+/// an application fans them out.
+struct QueryThenGet {
+    query: QueryFluentBuilder,
+    get: GetItemFluentBuilder,
+    /// GetItem key attributes, each with the queried attribute it is copied from
+    key_map: Vec<(String, String)>,
+}
+
+impl QueryThenGet {
+    fn build(client: &Client, cli: &Cli, args: &BenchArgs) -> Box<dyn Workload> {
+        let get = client.get_item()
+            .table_name(args.chain_table.as_ref().unwrap_or(&cli.table))
+            .return_consumed_capacity(request::return_capacity());
+        let key_map = match args.chain_key.as_slice() {
+            [] if cli.no_sort_key => vec![(cli.partition_key.clone(), cli.partition_key.clone())],
+            [] => vec![(cli.partition_key.clone(), cli.partition_key.clone()), (cli.sort_key.clone(), cli.sort_key.clone())],
+            key_map => key_map.to_vec(),
+        };
+        let requests = workload::make_query(client, cli, args).into_iter()
+            .filter_map(|request| match request {
+                Request::Query(query) => Some(Request::Custom(Arc::new(QueryThenGet {
+                    query: *query,
+                    get: get.clone(),
+                    key_map: key_map.clone(),
+                }))),
+                _ => None,
+            })
+            .collect();
+        Box::new(Cycle(requests))
+    }
+}
+
+impl CustomRequest for QueryThenGet {
+    fn name(&self) -> &'static str {
+        "QueryThenGet"
+    }
+
+    fn send(&self) -> ResponseFuture {
+        let (query, get, key_map) = (self.query.clone(), self.get.clone(), self.key_map.clone());
+        Box::pin(async move {
+            let mut response = Response::default();
+            let resp = query.return_consumed_capacity(request::return_capacity()).send().await?;
+            response.add_capacity(resp.consumed_capacity(), false);
+            let mut gets = JoinSet::new();
+            for item in resp.items() {
+                let mut get = get.clone();
+                for (key, attribute) in &key_map {
+                    let value = item.get(attribute).ok_or_else(|| RequestError {
+                        code: None,
+                        request_id: None,
+                        message: format!("queried item has no {} attribute to chain into {}", attribute, key),
+                        cancellation_reasons: Vec::new(),
+                    })?;
+                    get = get.key(key, value.clone());
+                }
+                gets.spawn(get.send());
+            }
+            while let Some(result) = gets.join_next().await {
+                let resp = result.expect("GetItem task panicked")?;
+                response.items += resp.item().is_some() as usize;
+                response.bytes += resp.item().map_or(0, item_bytes);
+                response.add_capacity(resp.consumed_capacity(), false);
+            }
+            Ok(response)
+        })
+    }
+
+    fn keys(&self) -> Vec<String> {
+        Request::Query(Box::new(self.query.clone())).keys(&[])
+    }
+
+    fn primary_key(&self, partition_key: &str, sort_key: &str) -> Option<(String, Option<String>)> {
+        Request::Query(Box::new(self.query.clone())).primary_key(partition_key, sort_key)
+    }
+}
+
+/// Reads a partition's --sort-start..--sort-end range as --split-ranges
+/// sub-range queries in parallel, merged into one response: a client-side
+/// optimization for wide windows. Requests alternate with the whole range
+/// read as one [`AggregateRange`], so the two compare under the same conditions.
+struct SplitRange {
+    parts: Vec<AggregateRange>,
+}
+
+impl SplitRange {
+    fn build(client: &Client, cli: &Cli, args: &BenchArgs) -> Box<dyn Workload> {
+        let ranges = split(cli, args, args.split_ranges)
+            .unwrap_or_else(|e| Cli::command().error(ErrorKind::ValueValidation, e).exit());
+        let requests = workload::make_query(client, cli, args).into_iter()
+            .filter_map(|request| match request {
+                Request::Query(query) => Some([aggregate((*query).clone()), Request::Custom(Arc::new(SplitRange {
+                    parts: ranges.iter().map(|(start, end)| AggregateRange {
+                        query: (*query).clone()
+                            .expression_attribute_values(":start", cli.sort_type.value(start))
+                            .expression_attribute_values(":end", cli.sort_type.value(end)),
+                    }).collect(),
+                }))]),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        Box::new(Cycle(requests))
+    }
+}
+
+/// Divides the inclusive --sort-start..--sort-end range into `n` contiguous
+/// inclusive sub-ranges. The bounds have to be integers, such as epoch
+/// timestamps; S bounds of the same length keep it, zero-padded.
+fn split(cli: &Cli, args: &BenchArgs, n: usize) -> Result<Vec<(String, String)>, String> {
+    let (Some(start), Some(end)) = (&args.sort_start, &args.sort_end) else {
+        return Err("--custom split-range needs both --sort-start and --sort-end".to_owned());
+    };
+    let (Ok(lo), Ok(hi)) = (start.parse::<i64>(), end.parse::<i64>()) else {
+        return Err(format!("--custom split-range needs integer sort bounds, such as epoch timestamps, got {} and {}", start, end));
+    };
+    if cli.sort_type == KeyType::B || args.sort_op.is_some() {
+        return Err("--custom split-range needs S or N sort keys and a BETWEEN range, without --sort-op".to_owned());
+    }
+    if n == 0 || hi < lo || ((hi - lo) as u64) < n as u64 - 1 {
+        return Err(format!("cannot split {}..{} into {} sub-ranges", start, end, n));
+    }
+    let width = if cli.sort_type == KeyType::S && start.len() == end.len() { start.len() } else { 0 };
+    let bound = |k: usize| lo + ((hi - lo) as i128 * k as i128 / n as i128) as i64;
+    Ok((0..n).map(|k| {
+        let part_end = if k + 1 == n { hi } else { bound(k + 1) - 1 };
+        (format!("{:0width$}", bound(k), width = width), format!("{:0width$}", part_end, width = width))
+    }).collect())
+}
+
+impl CustomRequest for SplitRange {
+    fn name(&self) -> &'static str {
+        "SplitRange"
+    }
+
+    fn send(&self) -> ResponseFuture {
+        let parts: Vec<_> = self.parts.iter().map(AggregateRange::send).collect();
+        Box::pin(async move {
+            let mut response = Response::default();
+            let mut queries = JoinSet::new();
+            for part in parts {
+                queries.spawn(part);
+            }
+            while let Some(result) = queries.join_next().await {
+                let part = result.expect("sub-range query task panicked")?;
+                response.items += part.items;
+                response.bytes += part.bytes;
+                response.add_units(&part);
+            }
+            Ok(response)
+        })
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.parts[0].keys()
+    }
+
+    fn primary_key(&self, partition_key: &str, sort_key: &str) -> Option<(String, Option<String>)> {
+        self.parts[0].primary_key(partition_key, sort_key)
+    }
+}
+
+/// Models a negative-caching layer in front of the table: GetItems of the
+/// -P/-S items, --miss-ratio of them instead reading a key that doesn't exist
+/// yet and, on the miss, writing a negative-cache marker there with a
+/// conditional PutItem, as a read-repair path does. Each marker is read
+/// again --reread-after requests later, so the latency by operation shows
+/// how reads of freshly written items compare with the others.
+struct NegativeCache {
+    hits: Vec<Request>,
+    get: GetItemFluentBuilder,
+    put: PutItemFluentBuilder,
+    partition_key: String,
+    partition_type: KeyType,
+    /// Sort key attribute and value of the markers, unless the table has none
+    sort: Option<(String, AttributeValue)>,
+    /// Tells this run's markers from those of earlier runs, which aren't missing anymore
+    run: u64,
+    miss_ratio: f64,
+    reread_after: usize,
+}
+
+impl NegativeCache {
+    fn build(client: &Client, cli: &Cli, args: &BenchArgs) -> Box<dyn Workload> {
+        let run = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() % 1_000_000;
+        Box::new(NegativeCache {
+            hits: workload::make_get_item(client, cli, args),
+            get: client.get_item().table_name(&cli.table),
+            put: client.put_item()
+                .table_name(&cli.table)
+                .condition_expression("attribute_not_exists(#pk)")
+                .expression_attribute_names("#pk", &cli.partition_key),
+            partition_key: cli.partition_key.clone(),
+            partition_type: cli.partition_type,
+            sort: (!cli.no_sort_key).then(|| (cli.sort_key.clone(), cli.sort_type.value(&cli.sort_type.missing(0)))),
+            run,
+            miss_ratio: args.miss_ratio,
+            reread_after: args.reread_after,
+        })
+    }
+
+    /// The key of the `n`th marker.
+    fn key(&self, n: usize) -> HashMap<String, AttributeValue> {
+        let mut key = HashMap::from([(self.partition_key.clone(), self.partition_type.value(&self.partition_type.marker(self.run, n)))]);
+        key.extend(self.sort.clone());
+        key
+    }
+
+    /// The marker a miss at request `i` writes: one per miss, in order.
+    fn marker(&self, i: usize) -> usize {
+        ((i + 1) as f64 * self.miss_ratio).floor() as usize - 1
+    }
+}
+
+impl Workload for NegativeCache {
+    fn request(&self, i: usize) -> (Request, bool) {
+        if workload::is_picked(i, self.miss_ratio) {
+            let key = self.key(self.marker(i));
+            let mut item = key.clone();
+            item.insert("negative_cache".to_owned(), AttributeValue::Bool(true));
+            let request = MissThenWrite { get: self.get.clone().set_key(Some(key)), put: self.put.clone().set_item(Some(item)) };
+            return (Request::Custom(Arc::new(request)), true);
+        }
+        match i.checked_sub(self.reread_after).filter(|&miss| workload::is_picked(miss, self.miss_ratio)) {
+            Some(miss) => (Request::Custom(Arc::new(Reread(self.get.clone().set_key(Some(self.key(self.marker(miss))))))), false),
+            None => (self.hits[i % self.hits.len()].clone(), false),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.hits.len()
+    }
+}
+
+/// A GetItem of a key that doesn't exist, followed on the miss by the
+/// conditional PutItem of a negative-cache marker, timed as one request.
+struct MissThenWrite {
+    get: GetItemFluentBuilder,
+    put: PutItemFluentBuilder,
+}
+
+impl CustomRequest for MissThenWrite {
+    fn name(&self) -> &'static str {
+        "MissThenWrite"
+    }
+
+    fn send(&self) -> ResponseFuture {
+        let (get, put) = (self.get.clone(), self.put.clone());
+        Box::pin(async move {
+            let mut response = Response::default();
+            let resp = get.return_consumed_capacity(request::return_capacity()).send().await?;
+            response.add_capacity(resp.consumed_capacity(), false);
+            if let Some(item) = resp.item() {
+                // already repaired, so there is nothing to write
+                response.items = 1;
+                response.bytes = item_bytes(item);
+                return Ok(response);
+            }
+            let resp = put.return_consumed_capacity(request::return_capacity()).send().await?;
+            response.add_capacity(resp.consumed_capacity(), true);
+            Ok(response)
+        })
+    }
+
+    fn primary_key(&self, partition_key: &str, sort_key: &str) -> Option<(String, Option<String>)> {
+        Request::GetItem(Box::new(self.get.clone())).primary_key(partition_key, sort_key)
+    }
+}
+
+/// A GetItem of a negative-cache marker written earlier in the run.
+struct Reread(GetItemFluentBuilder);
+
+impl CustomRequest for Reread {
+    fn name(&self) -> &'static str {
+        "RereadAfterWrite"
+    }
+
+    fn send(&self) -> ResponseFuture {
+        Box::pin(Request::GetItem(Box::new(self.0.clone())).send())
+    }
+
+    fn primary_key(&self, partition_key: &str, sort_key: &str) -> Option<(String, Option<String>)> {
+        Request::GetItem(Box::new(self.0.clone())).primary_key(partition_key, sort_key)
+    }
+}
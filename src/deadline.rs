@@ -0,0 +1,63 @@
+use crate::request::{Request, RequestError, Response};
+use crate::histogram::Latencies;
+use crate::print_halves;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Cancel-and-retry for --retry-deadline-ms: an attempt still unanswered at
+/// the deadline is abandoned (its future dropped, which closes its connection)
+/// and a fresh one sent at once, the last of --retry-attempts being waited
+/// out. Only even-numbered requests follow the policy; odd-numbered ones wait
+/// for their first attempt, as the control group.
+pub struct Deadline {
+    deadline: Duration,
+    attempts: u32,
+    governed: AtomicUsize,
+    abandoned: AtomicUsize,
+    retried: AtomicUsize,
+    exhausted: AtomicUsize,
+}
+
+impl Deadline {
+    pub fn new(deadline: Duration, attempts: u32) -> Deadline {
+        Deadline {
+            deadline,
+            attempts,
+            governed: AtomicUsize::new(0),
+            abandoned: AtomicUsize::new(0),
+            retried: AtomicUsize::new(0),
+            exhausted: AtomicUsize::new(0),
+        }
+    }
+
+    /// Sends the `index`th request, under the deadline if it is even-numbered.
+    pub async fn send(&self, request: Request, index: usize) -> Result<Response, RequestError> {
+        if index % 2 == 1 {
+            return request.send().await;
+        }
+        self.governed.fetch_add(1, Ordering::Relaxed);
+        for attempt in 1..self.attempts {
+            if let Ok(result) = tokio::time::timeout(self.deadline, request.clone().send()).await {
+                return result;
+            }
+            self.abandoned.fetch_add(1, Ordering::Relaxed);
+            if attempt == 1 {
+                self.retried.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.exhausted.fetch_add(1, Ordering::Relaxed);
+        request.send().await
+    }
+
+    /// Compares the requests retried at the deadline with those left to wait.
+    pub fn report(&self, retrying: &Latencies, waiting: &Latencies) {
+        println!("\nCancel and retry after {:.3} ms, up to {} attempts (milliseconds):",
+            self.deadline.as_secs_f64() * 1000.0, self.attempts);
+        let (requests, abandoned) = (self.governed.load(Ordering::Relaxed), self.abandoned.load(Ordering::Relaxed));
+        println!("Attempts abandoned: {} for {} requests ({:.1}% extra requests), {} requests retried, {} waited out their last attempt",
+            abandoned, requests, 100.0 * abandoned as f64 / requests.max(1) as f64,
+            self.retried.load(Ordering::Relaxed), self.exhausted.load(Ordering::Relaxed));
+        print_halves(("Cancel and retry", retrying), ("Waiting", waiting), &[("p50", 0.5), ("p99", 0.99), ("p99.9", 0.999)],
+            "with cancel and retry");
+    }
+}
@@ -0,0 +1,185 @@
+use crate::request::KeyType;
+use crate::{timestamp, Cli, Commands};
+use aws_sdk_dynamodb::error::DisplayErrorContext;
+use aws_sdk_dynamodb::types::{KeySchemaElement, KeyType as KeyRole, ProvisionedThroughputDescription};
+use aws_sdk_dynamodb::Client;
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory};
+use serde_json::{json, Value};
+use std::time::SystemTime;
+
+#[derive(Args, Debug, Clone)]
+pub struct DescribeArgs {
+    /// Print the snapshot as JSON, as embedded in benchmark outputs
+    #[arg(long)]
+    json: bool,
+}
+
+fn key_schema(elements: &[KeySchemaElement]) -> Value {
+    elements.iter().map(|element| json!({
+        "name": element.attribute_name(),
+        "role": element.key_type().as_str(),
+    })).collect()
+}
+
+fn throughput(throughput: Option<&ProvisionedThroughputDescription>) -> Value {
+    match throughput.map(|t| (t.read_capacity_units().unwrap_or(0), t.write_capacity_units().unwrap_or(0))) {
+        Some((read, write)) if read > 0 || write > 0 => json!({ "read_capacity_units": read, "write_capacity_units": write }),
+        // on-demand tables and their indexes report zero
+        _ => Value::Null,
+    }
+}
+
+/// A snapshot of the table's schema, capacity and size, from DescribeTable.
+/// Item count and size are DynamoDB's estimates, refreshed about every six hours.
+pub async fn snapshot(client: &Client, table: &str) -> Result<Value, String> {
+    let resp = client.describe_table().table_name(table).send().await
+        .map_err(|e| DisplayErrorContext(&e).to_string())?;
+    let table = resp.table().ok_or("DescribeTable returned no table")?;
+    let types: serde_json::Map<String, Value> = table.attribute_definitions().iter()
+        .map(|definition| (definition.attribute_name().to_owned(), json!(definition.attribute_type().as_str())))
+        .collect();
+    Ok(json!({
+        "described_at": timestamp::utc(SystemTime::now()),
+        "table_name": table.table_name(),
+        "status": table.table_status().map(|status| status.as_str()),
+        "attribute_types": types,
+        "key_schema": key_schema(table.key_schema()),
+        // tables created before on-demand existed report no billing mode summary
+        "billing_mode": table.billing_mode_summary().and_then(|summary| summary.billing_mode())
+            .map_or("PROVISIONED", |mode| mode.as_str()),
+        "provisioned_throughput": throughput(table.provisioned_throughput()),
+        "global_secondary_indexes": table.global_secondary_indexes().iter().map(|index| json!({
+            "name": index.index_name(),
+            "key_schema": key_schema(index.key_schema()),
+            "status": index.index_status().map(|status| status.as_str()),
+            "provisioned_throughput": throughput(index.provisioned_throughput()),
+            "item_count": index.item_count(),
+        })).collect::<Vec<_>>(),
+        "local_secondary_indexes": table.local_secondary_indexes().iter().map(|index| json!({
+            "name": index.index_name(),
+            "key_schema": key_schema(index.key_schema()),
+            "item_count": index.item_count(),
+        })).collect::<Vec<_>>(),
+        "item_count": table.item_count(),
+        "size_bytes": table.table_size_bytes(),
+    }))
+}
+
+fn keys(schema: &Value) -> String {
+    schema.as_array().into_iter().flatten()
+        .map(|key| format!("{} ({})", key["name"].as_str().unwrap_or_default(), key["role"].as_str().unwrap_or_default()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn capacity(throughput: &Value) -> String {
+    match throughput {
+        Value::Null => "on demand".to_owned(),
+        t => format!("{} RCU, {} WCU", t["read_capacity_units"], t["write_capacity_units"]),
+    }
+}
+
+/// Prints a snapshot taken by [`snapshot`].
+pub fn print(snapshot: &Value) {
+    println!("Table {} ({}), described at {}", snapshot["table_name"].as_str().unwrap_or_default(),
+        snapshot["status"].as_str().unwrap_or("unknown status"), snapshot["described_at"].as_str().unwrap_or_default());
+    println!("Key schema: {}", keys(&snapshot["key_schema"]));
+    println!("Billing mode: {}, {}", snapshot["billing_mode"].as_str().unwrap_or_default(),
+        capacity(&snapshot["provisioned_throughput"]));
+    println!("Items: {} ({:.3} MB), estimated", snapshot["item_count"],
+        snapshot["size_bytes"].as_f64().unwrap_or_default() / 1_000_000.0);
+    for index in snapshot["global_secondary_indexes"].as_array().into_iter().flatten() {
+        println!("Global secondary index {}: {}; {}, {} items", index["name"].as_str().unwrap_or_default(),
+            keys(&index["key_schema"]), capacity(&index["provisioned_throughput"]), index["item_count"]);
+    }
+    for index in snapshot["local_secondary_indexes"].as_array().into_iter().flatten() {
+        println!("Local secondary index {}: {}; {} items", index["name"].as_str().unwrap_or_default(),
+            keys(&index["key_schema"]), index["item_count"]);
+    }
+}
+
+/// Fills in --partition-key/--sort-key and their types from the table's key
+/// schema, or sets --no-sort-key for a table without a sort key. `explicit`
+/// tells which of them were given (on the command line, or by a config file
+/// or profile); those must agree with the schema. A benchmark with
+/// --index-name takes the index's key schema instead. When the table can't be
+/// described, the given values and defaults stand.
+pub async fn detect_keys(client: &Client, cli: &mut Cli, explicit: impl Fn(&str) -> bool) {
+    let resp = match client.describe_table().table_name(&cli.table).send().await {
+        Ok(resp) => resp,
+        Err(e) => {
+            if !(explicit("partition_key") && (explicit("sort_key") || explicit("no_sort_key"))) {
+                let sort_key = if cli.no_sort_key { "no sort key" } else { cli.sort_key.as_str() };
+                println!("Cannot describe {} to detect its key schema, assuming {} and {}: {}",
+                    cli.table, cli.partition_key, sort_key, DisplayErrorContext(&e));
+            }
+            return;
+        }
+    };
+    let Some(table) = resp.table() else { return };
+    let index = match &cli.command {
+        Commands::Bench(args) => args.index_name.clone(),
+        _ => None,
+    };
+    let (key_schema, described) = match &index {
+        Some(index) => {
+            let global = table.global_secondary_indexes().iter()
+                .find(|description| description.index_name() == Some(index.as_str()))
+                .map(|description| description.key_schema());
+            let local = table.local_secondary_indexes().iter()
+                .find(|description| description.index_name() == Some(index.as_str()))
+                .map(|description| description.key_schema());
+            let key_schema = global.or(local).unwrap_or_else(|| Cli::command().error(ErrorKind::InvalidValue, format!(
+                "{} has no secondary index named {}", cli.table, index)).exit());
+            (key_schema, format!("index {} of {}", index, cli.table))
+        }
+        None => (table.key_schema(), cli.table.clone()),
+    };
+    let has_sort_key = key_schema.iter().any(|element| element.key_type() == &KeyRole::Range);
+    if has_sort_key && explicit("no_sort_key") {
+        Cli::command().error(ErrorKind::ArgumentConflict, format!(
+            "--no-sort-key conflicts with the sort key of {}", described)).exit();
+    }
+    if !has_sort_key {
+        if explicit("sort_key") {
+            Cli::command().error(ErrorKind::ArgumentConflict, format!(
+                "--sort-key {} conflicts with {}, which has no sort key", cli.sort_key, described)).exit();
+        }
+        cli.no_sort_key = true;
+    }
+    for element in key_schema {
+        let (name, key_type, flag) = match element.key_type() {
+            KeyRole::Hash => (&mut cli.partition_key, &mut cli.partition_type, "partition"),
+            KeyRole::Range => (&mut cli.sort_key, &mut cli.sort_type, "sort"),
+            _ => continue,
+        };
+        let actual = element.attribute_name();
+        if explicit(&format!("{}_key", flag)) && name.as_str() != actual {
+            Cli::command().error(ErrorKind::ArgumentConflict, format!(
+                "--{}-key {} conflicts with the {} key of {}, {}", flag, name, flag, described, actual)).exit();
+        }
+        actual.clone_into(name);
+        let actual_type = table.attribute_definitions().iter()
+            .find(|definition| definition.attribute_name() == actual)
+            .and_then(|definition| KeyType::of(definition.attribute_type()));
+        if let Some(actual_type) = actual_type {
+            if explicit(&format!("{}_type", flag)) && *key_type != actual_type {
+                Cli::command().error(ErrorKind::ArgumentConflict, format!(
+                    "--{}-type {:?} conflicts with the {} key {} of {}, which is {:?}",
+                    flag, key_type, flag, actual, described, actual_type)).exit();
+            }
+            *key_type = actual_type;
+        }
+    }
+}
+
+pub async fn run(client: &Client, cli: &Cli, args: &DescribeArgs) {
+    let snapshot = snapshot(client, &cli.table).await
+        .unwrap_or_else(|e| Cli::command().error(ErrorKind::Io, format!("cannot describe {}: {}", cli.table, e)).exit());
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&snapshot).unwrap());
+    } else {
+        print(&snapshot);
+    }
+}
@@ -0,0 +1,25 @@
+use aws_sdk_dynamodb::error::DisplayErrorContext;
+use aws_sdk_dynamodb::Client;
+
+/// Tags of `table` matching any of the `key=value` rules, e.g. `env=production`.
+/// Values compare case-insensitively, since `Production` protects a table just as well.
+pub async fn protected_by(client: &Client, table: &str, rules: &[(String, String)]) -> Result<Vec<String>, String> {
+    let describe = client.describe_table().table_name(table).send().await
+        .map_err(|e| DisplayErrorContext(&e).to_string())?;
+    let arn = describe.table().and_then(|t| t.table_arn()).ok_or("DescribeTable returned no table ARN")?;
+    let mut matches = Vec::new();
+    let mut next_token = None;
+    loop {
+        let resp = client.list_tags_of_resource().resource_arn(arn).set_next_token(next_token).send().await
+            .map_err(|e| DisplayErrorContext(&e).to_string())?;
+        for tag in resp.tags() {
+            if rules.iter().any(|(key, value)| tag.key() == key && tag.value().eq_ignore_ascii_case(value)) {
+                matches.push(format!("{}={}", tag.key(), tag.value()));
+            }
+        }
+        next_token = resp.next_token;
+        if next_token.is_none() {
+            return Ok(matches);
+        }
+    }
+}
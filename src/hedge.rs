@@ -0,0 +1,114 @@
+use crate::request::{Request, RequestError, Response};
+use crate::histogram::Latencies;
+use crate::{print_halves, quantile_ms};
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The threshold is the --hedge-after percentile of this many recent unhedged latencies...
+const WINDOW: usize = 1000;
+/// ...recomputed every this many.
+const RECOMPUTE_EVERY: usize = 100;
+
+/// Hedged requests for --hedge-after: a duplicate is sent when the original
+/// is still unanswered after a threshold, and whichever answers first counts.
+/// Only even-numbered requests are hedged; odd-numbered ones run as usual and
+/// serve both as the control group and as the source of the threshold, so
+/// neither is skewed by cancelled originals. Requests sent before there is a
+/// threshold are in neither group, as even-numbered ones weren't hedged.
+pub struct Hedger {
+    quantile: f64,
+    recent: Mutex<VecDeque<Duration>>,
+    threshold: Mutex<Option<Duration>>,
+    /// Indices of the requests sent before the first threshold
+    early: Mutex<HashSet<usize>>,
+    observed: AtomicUsize,
+    hedged: AtomicUsize,
+    duplicates: AtomicUsize,
+    duplicate_wins: AtomicUsize,
+}
+
+impl Hedger {
+    pub fn new(quantile: f64) -> Hedger {
+        Hedger {
+            quantile,
+            recent: Mutex::new(VecDeque::with_capacity(WINDOW)),
+            threshold: Mutex::new(None),
+            early: Mutex::new(HashSet::new()),
+            observed: AtomicUsize::new(0),
+            hedged: AtomicUsize::new(0),
+            duplicates: AtomicUsize::new(0),
+            duplicate_wins: AtomicUsize::new(0),
+        }
+    }
+
+    fn observe(&self, latency: Duration) {
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == WINDOW {
+            recent.pop_front();
+        }
+        recent.push_back(latency);
+        let observed = self.observed.fetch_add(1, Ordering::Relaxed) + 1;
+        if observed.is_multiple_of(RECOMPUTE_EVERY) {
+            let mut sorted: Vec<_> = recent.iter().copied().collect();
+            sorted.sort();
+            let threshold = Duration::from_secs_f64(quantile_ms(&sorted, self.quantile) / 1000.0);
+            *self.threshold.lock().unwrap() = Some(threshold);
+        }
+    }
+
+    /// Sends the `index`th request, hedging it if it is even-numbered and a threshold is known yet.
+    pub async fn send(&self, request: Request, index: usize) -> Result<Response, RequestError> {
+        let threshold = *self.threshold.lock().unwrap();
+        if threshold.is_none() {
+            self.early.lock().unwrap().insert(index);
+        }
+        if index % 2 == 1 {
+            let start = Instant::now();
+            let result = request.send().await;
+            self.observe(start.elapsed());
+            return result;
+        }
+        let Some(threshold) = threshold else {
+            return request.send().await;
+        };
+        self.hedged.fetch_add(1, Ordering::Relaxed);
+        let original = request.clone().send();
+        tokio::pin!(original);
+        tokio::select! {
+            result = &mut original => return result,
+            _ = tokio::time::sleep(threshold) => {}
+        }
+        self.duplicates.fetch_add(1, Ordering::Relaxed);
+        let duplicate = request.send();
+        tokio::pin!(duplicate);
+        tokio::select! {
+            result = &mut original => result,
+            result = &mut duplicate => {
+                self.duplicate_wins.fetch_add(1, Ordering::Relaxed);
+                result
+            }
+        }
+    }
+
+    /// Whether the `index`th request was sent before there was a threshold,
+    /// and so belongs to neither side of the comparison.
+    pub fn is_early(&self, index: usize) -> bool {
+        self.early.lock().unwrap().contains(&index)
+    }
+
+    /// Compares the hedged (even-numbered) requests with the unhedged ones.
+    pub fn report(&self, hedged: &Latencies, unhedged: &Latencies) {
+        println!("\nHedging after p{} of unhedged latency (milliseconds):", self.quantile * 100.0);
+        match *self.threshold.lock().unwrap() {
+            Some(threshold) => println!("Threshold at the end of the run: {:.3}", threshold.as_secs_f64() * 1000.0),
+            None => println!("Fewer than {} unhedged requests, so nothing was hedged", RECOMPUTE_EVERY),
+        }
+        let (requests, duplicates) = (self.hedged.load(Ordering::Relaxed), self.duplicates.load(Ordering::Relaxed));
+        println!("Duplicates sent: {} for {} hedged requests ({:.1}% extra requests), {} answered first",
+            duplicates, requests, 100.0 * duplicates as f64 / requests.max(1) as f64, self.duplicate_wins.load(Ordering::Relaxed));
+        println!("Requests sent before the first threshold, left out of both halves: {}", self.early.lock().unwrap().len());
+        print_halves(("Hedged", hedged), ("Unhedged", unhedged), &[("p99", 0.99), ("p99.9", 0.999)], "with hedging");
+    }
+}
@@ -0,0 +1,168 @@
+use crate::is_low_confidence;
+use clap::Args;
+use hdrhistogram::Histogram;
+use std::time::Duration;
+
+#[derive(Args, Debug, Clone, Copy)]
+pub struct HistogramArgs {
+    /// Significant figures latency is recorded with, 1 to 5; more costs memory, not time per request
+    #[arg(long, default_value = "3", value_parser = clap::value_parser!(u8).range(1..=5))]
+    histogram_sigfig: u8,
+
+    /// Highest latency the histograms tell apart, in milliseconds; slower requests are
+    /// counted at this value, and the report says how many were
+    #[arg(long, default_value = "60000", value_parser = clap::value_parser!(u64).range(1..))]
+    histogram_max_ms: u64,
+}
+
+impl HistogramArgs {
+    /// An empty histogram of this precision.
+    pub fn latencies(&self) -> Latencies {
+        let histogram = Histogram::new_with_bounds(1, self.histogram_max_ms * 1000, self.histogram_sigfig)
+            .expect("--histogram-max-ms and --histogram-sigfig are validated");
+        Latencies { histogram, clamped: 0 }
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{} significant figures up to {} ms", self.histogram_sigfig, self.histogram_max_ms)
+    }
+}
+
+/// Latencies of requests, counted into an HdrHistogram in microseconds
+/// instead of kept one by one, so memory doesn't grow with the run.
+#[derive(Clone)]
+pub struct Latencies {
+    histogram: Histogram<u64>,
+    /// Latencies above the highest trackable value, counted at it
+    clamped: usize,
+}
+
+impl Latencies {
+    pub fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros() as u64;
+        if micros > self.histogram.high() {
+            self.clamped += 1;
+        }
+        self.histogram.saturating_record(micros.max(1));
+    }
+
+    pub fn add(&mut self, other: &Latencies) {
+        self.histogram.add(&other.histogram).expect("histograms of a run share their bounds");
+        self.clamped += other.clamped;
+    }
+
+    pub fn len(&self) -> usize {
+        self.histogram.len() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.histogram.is_empty()
+    }
+
+    pub fn clamped(&self) -> usize {
+        self.clamped
+    }
+
+    /// Latency at `quantile` in milliseconds, to the histogram's precision.
+    pub fn quantile_ms(&self, quantile: f64) -> f64 {
+        self.histogram.value_at_quantile(quantile) as f64 / 1000.0
+    }
+
+    /// [`Latencies::quantile_ms`] formatted, marked when too few samples lie beyond it.
+    pub fn percentile_ms(&self, quantile: f64) -> String {
+        let value = self.quantile_ms(quantile);
+        if is_low_confidence(self.len(), quantile) {
+            format!("{:.3} (low confidence)", value)
+        } else {
+            format!("{:.3}", value)
+        }
+    }
+
+    pub fn mean_ms(&self) -> f64 {
+        self.histogram.mean() / 1000.0
+    }
+
+    pub fn stddev_ms(&self) -> f64 {
+        self.histogram.stdev() / 1000.0
+    }
+
+    /// Distinct recorded latencies in milliseconds, in increasing order, with their counts.
+    pub fn counts(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+        self.histogram.iter_recorded()
+            .map(|value| (self.histogram.median_equivalent(value.value_iterated_to()) as f64 / 1000.0, value.count_at_value()))
+    }
+
+    /// Prints the same line as `print_split_latency` does for a list of latencies.
+    pub fn print(&self, label: &str) {
+        if self.is_empty() {
+            return;
+        }
+        println!("{}: {} requests, p50 {}, p90 {}, p99 {}, max {:.3}", label, self.len(),
+            self.percentile_ms(0.5), self.percentile_ms(0.9), self.percentile_ms(0.99), self.quantile_ms(1.0));
+    }
+}
+
+#[cfg(test)]
+impl Latencies {
+    /// Latencies of `ms` milliseconds each, counted up to `max_ms` at the default precision.
+    pub fn from_ms(max_ms: u64, ms: impl IntoIterator<Item = u64>) -> Latencies {
+        let mut latencies = HistogramArgs { histogram_sigfig: 3, histogram_max_ms: max_ms }.latencies();
+        for ms in ms {
+            latencies.record(Duration::from_millis(ms));
+        }
+        latencies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let latencies = Latencies::from_ms(1000, []);
+        assert!(latencies.is_empty());
+        assert_eq!(latencies.len(), 0);
+        assert_eq!(latencies.quantile_ms(0.5), 0.0);
+        assert_eq!(latencies.counts().count(), 0);
+    }
+
+    #[test]
+    fn single_value_is_every_quantile() {
+        let latencies = Latencies::from_ms(1000, [12]);
+        for quantile in [0.0, 0.5, 0.999, 1.0] {
+            assert!((latencies.quantile_ms(quantile) - 12.0).abs() < 0.02, "quantile {}", quantile);
+        }
+        assert!(latencies.percentile_ms(0.99).ends_with("(low confidence)"));
+    }
+
+    #[test]
+    fn min_and_max_quantiles() {
+        let latencies = Latencies::from_ms(1000, 1..=1000);
+        assert!((latencies.quantile_ms(0.0) - 1.0).abs() < 0.01);
+        assert!((latencies.quantile_ms(1.0) - 1000.0).abs() < 1.0);
+        assert!((latencies.quantile_ms(0.5) - 500.0).abs() < 0.5);
+        assert!(!latencies.percentile_ms(0.99).ends_with("(low confidence)"));
+    }
+
+    #[test]
+    fn zero_latency_counts_as_one_microsecond() {
+        let latencies = Latencies::from_ms(1000, [0]);
+        assert_eq!(latencies.len(), 1);
+        assert_eq!(latencies.quantile_ms(1.0), 0.001);
+    }
+
+    #[test]
+    fn clamps_above_the_highest_value() {
+        let latencies = Latencies::from_ms(10, [5, 5000]);
+        assert_eq!((latencies.len(), latencies.clamped()), (2, 1));
+        assert!(latencies.quantile_ms(1.0) <= 10.01);
+    }
+
+    #[test]
+    fn add_merges_counts_and_clamps() {
+        let mut a = Latencies::from_ms(10, [1]);
+        a.add(&Latencies::from_ms(10, [2, 1000]));
+        assert_eq!((a.len(), a.clamped()), (3, 1));
+    }
+}
@@ -0,0 +1,154 @@
+use crate::Cli;
+use clap::error::ErrorKind;
+use clap::CommandFactory;
+use serde_json::{Map, Value};
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+/// How long an HTTP hook may take before it is given up on.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// One step run around a phase.
+#[derive(Debug, Clone)]
+enum Hook {
+    /// A shell command, run with `sh -c`
+    Command(String),
+    /// An HTTP request
+    Http {
+        method: reqwest::Method,
+        url: String,
+        headers: Vec<(String, String)>,
+        body: Option<String>,
+    },
+}
+
+impl Hook {
+    fn describe(&self) -> String {
+        match self {
+            Hook::Command(command) => format!("`{}`", command),
+            Hook::Http { method, url, .. } => format!("{} {}", method, url),
+        }
+    }
+}
+
+/// The hooks of one phase, from its `before`, `after` and `env` settings.
+#[derive(Debug, Clone, Default)]
+pub struct Hooks {
+    before: Vec<Hook>,
+    after: Vec<Hook>,
+    /// Environment variables set for the hook commands
+    env: Vec<(String, String)>,
+}
+
+fn fail(message: String) -> ! {
+    Cli::command().error(ErrorKind::InvalidValue, message).exit()
+}
+
+/// A scalar setting as a string, as it would be written on a command line.
+fn scalar(value: &Value, what: &str) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(_) | Value::Bool(_) => value.to_string(),
+        _ => fail(format!("{} must be a string, number or boolean", what)),
+    }
+}
+
+fn parse_hook(value: &Value, what: &str) -> Hook {
+    let hook = match value {
+        Value::String(command) => return Hook::Command(command.clone()),
+        Value::Object(hook) => hook,
+        _ => fail(format!("{} must be a shell command or a mapping with `http`", what)),
+    };
+    if let Some(key) = hook.keys().find(|key| !["http", "method", "headers", "body"].contains(&key.as_str())) {
+        fail(format!("{} has an unknown setting `{}`", what, key));
+    }
+    let url = match hook.get("http") {
+        Some(Value::String(url)) => url.clone(),
+        _ => fail(format!("{} must give its URL as `http`", what)),
+    };
+    let body = hook.get("body").map(|body| match body {
+        Value::String(body) => body.clone(),
+        // a structured body is sent as JSON
+        body => body.to_string(),
+    });
+    let method = match hook.get("method") {
+        Some(method) => scalar(method, &format!("the method of {}", what)).to_uppercase().parse()
+            .unwrap_or_else(|_| fail(format!("{} has an invalid method", what))),
+        None if body.is_some() => reqwest::Method::POST,
+        None => reqwest::Method::GET,
+    };
+    let headers = match hook.get("headers") {
+        None => Vec::new(),
+        Some(Value::Object(headers)) => headers.iter()
+            .map(|(name, value)| (name.clone(), scalar(value, &format!("header {} of {}", name, what))))
+            .collect(),
+        Some(_) => fail(format!("the headers of {} must be a mapping", what)),
+    };
+    Hook::Http { method, url, headers, body }
+}
+
+fn parse_list(value: Option<&Value>, phase: &str, when: &str) -> Vec<Hook> {
+    let what = |n: usize| format!("{} hook {} of phase {:?}", when, n + 1, phase);
+    match value {
+        None => Vec::new(),
+        Some(Value::Array(hooks)) => hooks.iter().enumerate().map(|(n, hook)| parse_hook(hook, &what(n))).collect(),
+        Some(hook) => vec![parse_hook(hook, &what(0))],
+    }
+}
+
+/// Parses the hook settings of `phase`, as returned by `config::phases`.
+pub fn parse(phase: &str, settings: &Map<String, Value>) -> Hooks {
+    let env = match settings.get("env") {
+        None => Vec::new(),
+        Some(Value::Object(env)) => env.iter()
+            .map(|(name, value)| (name.clone(), scalar(value, &format!("env {} of phase {:?}", name, phase))))
+            .collect(),
+        Some(_) => fail(format!("the env of phase {:?} must be a mapping", phase)),
+    };
+    Hooks {
+        before: parse_list(settings.get("before"), phase, "before"),
+        after: parse_list(settings.get("after"), phase, "after"),
+        env,
+    }
+}
+
+async fn run_hook(hook: &Hook, env: &[(String, String)]) -> Result<(), String> {
+    match hook {
+        Hook::Command(command) => {
+            let status = Command::new("sh").arg("-c").arg(command).envs(env.iter().cloned()).status().await
+                .map_err(|e| format!("cannot start: {}", e))?;
+            if !status.success() {
+                return Err(format!("exited with {}", status));
+            }
+        }
+        Hook::Http { method, url, headers, body } => {
+            let mut request = reqwest::Client::new().request(method.clone(), url).timeout(HTTP_TIMEOUT);
+            for (name, value) in headers {
+                request = request.header(name, value);
+            }
+            if let Some(body) = body {
+                request = request.body(body.clone());
+            }
+            request.send().await.and_then(|response| response.error_for_status()).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+impl Hooks {
+    /// Runs the `before` hooks, or the `after` ones, in order, returning the
+    /// time they took. A hook that fails stops the run, since the phases
+    /// after it would measure a setup other than the intended one.
+    pub async fn run(&self, phase: &str, after: bool) -> Duration {
+        let (when, hooks) = if after { ("after", &self.after) } else { ("before", &self.before) };
+        let start = Instant::now();
+        for hook in hooks {
+            let hook_start = Instant::now();
+            if let Err(e) = run_hook(hook, &self.env).await {
+                Cli::command().error(ErrorKind::Io, format!("{} hook {} of phase {:?} failed: {}", when, hook.describe(), phase, e)).exit();
+            }
+            println!("Ran {} hook {} in {:.3}s", when, hook.describe(), hook_start.elapsed().as_secs_f64());
+        }
+        start.elapsed()
+    }
+}
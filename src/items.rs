@@ -0,0 +1,159 @@
+use crate::request::{attribute_bytes, item_bytes, RequestError};
+use crate::{timestamp, Cli};
+use aws_sdk_dynamodb::Client;
+use clap::Args;
+use std::time::{Instant, SystemTime};
+
+/// Width in characters of the longest histogram bar.
+const BAR_WIDTH: usize = 40;
+
+#[derive(Args, Debug, Clone)]
+pub struct ItemsArgs {
+    /// Number of segments the table is split into, each scanned by its own worker
+    #[arg(short = 'g', long, default_value = "8")]
+    total_segments: i32,
+
+    /// Scan pages read per segment; each page is up to 1 MB
+    #[arg(long, default_value = "50", conflicts_with = "full_scan")]
+    sample_pages: usize,
+
+    /// Scan the whole table instead of sampling it
+    #[arg(long)]
+    full_scan: bool,
+}
+
+/// Size and shape of one item.
+struct Shape {
+    bytes: usize,
+    attributes: usize,
+    /// Bytes and attributes besides the primary key, what a benchmark write generates
+    payload_bytes: usize,
+    payload_attributes: usize,
+}
+
+#[derive(Default)]
+struct Sample {
+    pages: usize,
+    shapes: Vec<Shape>,
+    /// Whether the segment was read to its end
+    complete: bool,
+    error: Option<RequestError>,
+}
+
+async fn sample_segment(client: Client, table: String, key_names: Vec<String>, segment: i32, args: ItemsArgs) -> Sample {
+    let mut sample = Sample::default();
+    let mut exclusive_start_key = None;
+    loop {
+        let resp = client.scan()
+            .table_name(&table)
+            .segment(segment)
+            .total_segments(args.total_segments)
+            .set_exclusive_start_key(exclusive_start_key)
+            .send()
+            .await;
+        let resp = match resp {
+            Ok(resp) => resp,
+            Err(e) => {
+                sample.error = Some(e.into());
+                return sample;
+            }
+        };
+        sample.pages += 1;
+        for item in resp.items() {
+            let payload: Vec<_> = item.iter().filter(|(name, _)| !key_names.contains(name)).collect();
+            sample.shapes.push(Shape {
+                bytes: item_bytes(item),
+                attributes: item.len(),
+                payload_bytes: payload.iter().map(|(name, value)| name.len() + attribute_bytes(value)).sum(),
+                payload_attributes: payload.len(),
+            });
+        }
+        exclusive_start_key = resp.last_evaluated_key;
+        if exclusive_start_key.is_none() {
+            sample.complete = true;
+            return sample;
+        }
+        if !args.full_scan && sample.pages >= args.sample_pages {
+            return sample;
+        }
+    }
+}
+
+/// The `quantile` of sorted `values`.
+fn quantile(values: &[usize], quantile: f64) -> usize {
+    values[((values.len() - 1) as f64 * quantile).round() as usize]
+}
+
+/// Prints sorted `values` bucketed by powers of two, with their percentiles.
+fn print_histogram(title: &str, unit: &str, values: &[usize]) {
+    println!("\n{} ({}): min {}, p50 {}, p90 {}, p99 {}, max {}", title, unit, values[0], quantile(values, 0.5),
+        quantile(values, 0.9), quantile(values, 0.99), values[values.len() - 1]);
+    // bucket b holds the values of bit length b: 0, 1, 2-3, 4-7, ...
+    let bucket = |value: usize| (usize::BITS - value.leading_zeros()) as usize;
+    let mut counts = vec![0; bucket(values[values.len() - 1]) + 1];
+    for &value in values {
+        counts[bucket(value)] += 1;
+    }
+    let first = bucket(values[0]);
+    let most = *counts.iter().max().unwrap();
+    println!("{:>23} {:>10} {:>8}", "Range", "Items", "Share");
+    for (b, &count) in counts.iter().enumerate().skip(first) {
+        let (low, high) = if b == 0 { (0, 0) } else { (1 << (b - 1), (1 << b) - 1) };
+        let range = if low == high { low.to_string() } else { format!("{} - {}", low, high) };
+        println!("{:>23} {:>10} {:>7.2}%  {}", range, count, 100.0 * count as f64 / values.len() as f64,
+            "#".repeat((count * BAR_WIDTH).div_ceil(most)));
+    }
+}
+
+/// Samples --table with a parallel Scan and prints histograms of item sizes
+/// and attribute counts, and the --item-size and --attribute-count that
+/// match the median item, so benchmark writes can look like the real ones.
+/// Sizes are DynamoDB's: attribute names plus values.
+pub async fn run(client: &Client, cli: &Cli, args: &ItemsArgs) {
+    let extent = if args.full_scan { "all".to_owned() } else { format!("up to {}", args.sample_pages) };
+    println!("Sampling {} with {} segments, {} pages each", cli.table, args.total_segments, extent);
+    let started_at = SystemTime::now();
+    println!("Scan started at {}", timestamp::both(started_at, cli.timezone));
+    let start = Instant::now();
+    let mut key_names = vec![cli.partition_key.clone()];
+    if !cli.no_sort_key {
+        key_names.push(cli.sort_key.clone());
+    }
+    let workers: Vec<_> = (0..args.total_segments).map(|segment| {
+        tokio::spawn(sample_segment(client.clone(), cli.table.clone(), key_names.clone(), segment, args.clone()))
+    }).collect();
+    let mut shapes = Vec::new();
+    let (mut pages, mut complete) = (0, 0);
+    for (segment, worker) in workers.into_iter().enumerate() {
+        let sample = worker.await.unwrap();
+        if let Some(e) = &sample.error {
+            println!("Segment {} stopped on error: {}", segment, e);
+        }
+        pages += sample.pages;
+        complete += sample.complete as usize;
+        shapes.extend(sample.shapes);
+    }
+
+    println!("\nRead {} pages in {:.3}s: {} items; {} of {} segments read to the end",
+        pages, start.elapsed().as_secs_f64(), shapes.len(), complete, args.total_segments);
+    if shapes.is_empty() {
+        return;
+    }
+    let sorted = |field: fn(&Shape) -> usize| {
+        let mut values: Vec<_> = shapes.iter().map(field).collect();
+        values.sort();
+        values
+    };
+    print_histogram("Item size", "bytes", &sorted(|shape| shape.bytes));
+    print_histogram("Attributes per item", "including the key", &sorted(|shape| shape.attributes));
+
+    let (payload_bytes, payload_attributes) = (sorted(|shape| shape.payload_bytes), sorted(|shape| shape.payload_attributes));
+    println!("\nBesides the key, the median item has {} bytes in {} attributes (p90 {} bytes, p99 {} bytes)",
+        quantile(&payload_bytes, 0.5), quantile(&payload_attributes, 0.5), quantile(&payload_bytes, 0.9),
+        quantile(&payload_bytes, 0.99));
+    println!("Matching write payload: bench --item-size {} --attribute-count {}",
+        quantile(&payload_bytes, 0.5).max(1), quantile(&payload_attributes, 0.5).max(1));
+    if !args.full_scan {
+        println!("Counts cover the sampled pages only; pass --full-scan for exact ones");
+    }
+}
@@ -0,0 +1,412 @@
+use crate::request::{key_string, KeyType, Request};
+use crate::rng;
+use crate::workload::{self, RelativeBounds, Workload};
+use crate::{quantile_ms, timestamp, BenchArgs, Cli, Operation};
+use ::time::UtcOffset;
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::Client;
+use clap::ValueEnum;
+use std::cell::{Cell, OnceCell};
+use std::fmt::Write;
+use std::ops::Range;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How generated partition keys are spread over the --key-range.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyDistribution {
+    /// Every key equally likely
+    Uniform,
+    /// Zipfian over the range, the first keys hottest (see --zipf-theta)
+    Zipfian,
+    /// Each key in turn, wrapping around
+    Sequential,
+    /// Zipfian towards the end of the range, like reads of recently written keys
+    Latest,
+}
+
+/// Parses `START..END` (end exclusive).
+pub fn parse_range(s: &str) -> Result<Range<u64>, String> {
+    let invalid = || format!("invalid key range {:?}, expected START..END, e.g. 0..1000000", s);
+    let (start, end) = s.split_once("..").ok_or_else(invalid)?;
+    let (start, end) = (start.parse::<u64>().map_err(|_| invalid())?, end.parse::<u64>().map_err(|_| invalid())?);
+    if start < end {
+        Ok(start..end)
+    } else {
+        Err(format!("key range {:?} is empty", s))
+    }
+}
+
+/// Random bits for the `i`th choice, from the --rng backend; by default
+/// SplitMix64 of `i`, so random choices are reproducible run to run.
+pub fn mix(i: usize) -> u64 {
+    rng::draw(i)
+}
+
+/// Maps `i` to a well-mixed value in [0, 1), see [`mix`].
+pub fn uniform(i: usize) -> f64 {
+    mix(i) as f64 / (u64::MAX as f64 + 1.0)
+}
+
+/// Zipfian ranks over `n` items with skew `theta`, using the rejection-free
+/// method of Gray et al. that YCSB uses.
+struct Zipf {
+    n: f64,
+    theta: f64,
+    alpha: f64,
+    zeta_n: f64,
+    eta: f64,
+}
+
+impl Zipf {
+    fn new(n: u64, theta: f64) -> Zipf {
+        let zeta = |n: u64| (1..=n).map(|k| 1.0 / (k as f64).powf(theta)).sum::<f64>();
+        let (zeta_2, zeta_n) = (zeta(2.min(n)), zeta(n));
+        let n = n as f64;
+        Zipf {
+            n,
+            theta,
+            alpha: 1.0 / (1.0 - theta),
+            zeta_n,
+            eta: (1.0 - (2.0 / n).powf(1.0 - theta)) / (1.0 - zeta_2 / zeta_n),
+        }
+    }
+
+    /// The rank (0 hottest) for a uniform draw `u`.
+    fn rank(&self, u: f64) -> u64 {
+        let uz = u * self.zeta_n;
+        if uz < 1.0 {
+            return 0;
+        }
+        if uz < 1.0 + 0.5f64.powf(self.theta) {
+            return 1.min(self.n as u64 - 1);
+        }
+        ((self.n * (self.eta * u - self.eta + 1.0).powf(self.alpha)) as u64).min(self.n as u64 - 1)
+    }
+}
+
+/// Partition values drawn from `--key-prefix` followed by a number in --key-range.
+pub struct KeyGenerator {
+    prefix: String,
+    range: Range<u64>,
+    distribution: KeyDistribution,
+    zipf: Option<Zipf>,
+}
+
+impl KeyGenerator {
+    pub fn new(prefix: &str, range: Range<u64>, distribution: KeyDistribution, theta: f64) -> KeyGenerator {
+        let zipf = matches!(distribution, KeyDistribution::Zipfian | KeyDistribution::Latest)
+            .then(|| Zipf::new(range.end - range.start, theta));
+        KeyGenerator { prefix: prefix.to_owned(), range, distribution, zipf }
+    }
+
+    /// The partition value of the `i`th request, over the first `n` keys from
+    /// the start of the range, `n` at least its size. Zipfian ranks stay over
+    /// the range's size: the hottest keys stay the first, or the latest, ones.
+    fn key_in(&self, i: usize, n: u64) -> String {
+        self.value_of(self.range.start + self.key_offset(i, n))
+    }
+
+    fn key_offset(&self, i: usize, n: u64) -> u64 {
+        match (self.distribution, &self.zipf) {
+            (KeyDistribution::Uniform, _) => ((uniform(i) * n as f64) as u64).min(n - 1),
+            (KeyDistribution::Sequential, _) => i as u64 % n,
+            (KeyDistribution::Zipfian, Some(zipf)) => zipf.rank(uniform(i)),
+            (KeyDistribution::Latest, Some(zipf)) => n - 1 - zipf.rank(uniform(i)),
+            (_, None) => unreachable!("zipfian distributions are built with their Zipf"),
+        }
+    }
+
+    /// The partition value of the `i`th request of shard `shard` of `shards`,
+    /// drawn from that shard's keys with the shape of the distribution.
+    fn key_in_shard(&self, i: usize, shard: usize, shards: usize) -> String {
+        let n = self.range.end - self.range.start;
+        let keys = shard_of(&self.range, shard, shards);
+        let size = keys.end - keys.start;
+        let offset = match self.distribution {
+            KeyDistribution::Uniform => ((uniform(i) * size as f64) as u64).min(size - 1),
+            KeyDistribution::Sequential => (i / shards) as u64 % size,
+            // ranks over the whole range, scaled down to the shard's
+            _ => (self.key_offset(i, n) as u128 * size as u128 / n as u128) as u64,
+        };
+        self.value_of(keys.start + offset)
+    }
+
+    /// The partition value of key number `n`, allocated once at its final size.
+    fn value_of(&self, n: u64) -> String {
+        let mut value = String::with_capacity(self.prefix.len() + 20);
+        value.push_str(&self.prefix);
+        write!(value, "{}", n).expect("writing to a String doesn't fail");
+        value
+    }
+}
+
+/// Shard `shard` of `range` split into `shards` contiguous shards, as even as
+/// they come; expects no more shards than keys.
+pub fn shard_of(range: &Range<u64>, shard: usize, shards: usize) -> Range<u64> {
+    let n = (range.end - range.start) as u128;
+    let bound = |shard: usize| range.start + (n * shard as u128 / shards as u128) as u64;
+    bound(shard)..bound(shard + 1)
+}
+
+/// --key-growth: the keyspace past --key-range grows exponentially from the
+/// first request on, as organic data growth does, by appending the next key
+/// whenever it is behind its target size.
+pub struct Growth {
+    /// Growth per second, e.g. 0.01 for 1%
+    rate: f64,
+    started: OnceCell<Instant>,
+    appended: Cell<u64>,
+}
+
+impl Growth {
+    pub fn new(rate: f64) -> Growth {
+        Growth { rate, started: OnceCell::new(), appended: Cell::new(0) }
+    }
+
+    /// Keys appended past the range so far.
+    pub fn appended(&self) -> u64 {
+        self.appended.get()
+    }
+
+    /// The offset from the start of the range of the key to append now, if
+    /// the keyspace of `initial` keys plus those appended is behind its target.
+    fn append(&self, initial: u64) -> Option<u64> {
+        let elapsed = self.started.get_or_init(Instant::now).elapsed();
+        let target = initial as f64 * (1.0 + self.rate).powf(elapsed.as_secs_f64());
+        let size = initial + self.appended.get();
+        if (size as f64) < target.floor() {
+            self.appended.set(self.appended.get() + 1);
+            Some(size)
+        } else {
+            None
+        }
+    }
+}
+
+/// Stands in for the partition value in the statements built as templates:
+/// no real statement contains it, so replacing it can't touch anything else.
+const STATEMENT_MARKER: &str = "\u{0}partition\u{0}";
+
+/// Runs --operation against generated partition values instead of the -P list.
+/// Each operation's requests are built once, as templates, and each request
+/// is a copy of one with its own partition value swapped in, rather than
+/// built anew; see [`Request::with_partition`] for what the copy costs.
+pub struct Generated<'a> {
+    partition_key: &'a str,
+    partition_type: KeyType,
+    keys: KeyGenerator,
+    growth: Option<&'a Growth>,
+    relative_bounds: Option<RelativeBounds>,
+    /// The requests for the template's partition value, one per sort value or batch
+    templates: Vec<Request>,
+    /// The partition value the templates hold
+    template_value: AttributeValue,
+    /// The PutItem appending a partition under --key-growth, for the first key
+    append: Option<(Request, AttributeValue)>,
+}
+
+impl<'a> Generated<'a> {
+    pub fn new(keys: KeyGenerator, growth: Option<&'a Growth>, client: &Client, cli: &'a Cli, args: &'a BenchArgs) -> Generated<'a> {
+        let first = keys.value_of(keys.range.start);
+        let first_value = cli.partition_type.into_value(first.clone());
+        let template_value = match args.operation {
+            // B keys are rejected for statements up front
+            Operation::ExecuteStatement | Operation::BatchExecuteStatement if cli.partition_type == KeyType::N =>
+                AttributeValue::N(STATEMENT_MARKER.to_owned()),
+            Operation::ExecuteStatement | Operation::BatchExecuteStatement => AttributeValue::S(STATEMENT_MARKER.to_owned()),
+            _ => first_value.clone(),
+        };
+        let templates = workload::make_requests(client, cli, &BenchArgs {
+            partition_value: vec![key_string(&template_value)],
+            ..args.clone()
+        });
+        // the new partition gets one item, at the first sort value
+        let append = growth.map(|_| {
+            let put = workload::make_put_item(client, cli, &BenchArgs { partition_value: vec![first], ..args.clone() }).swap_remove(0);
+            (put, first_value)
+        });
+        Generated {
+            partition_key: &cli.partition_key,
+            partition_type: cli.partition_type,
+            keys,
+            growth,
+            relative_bounds: RelativeBounds::new(cli, args),
+            templates,
+            template_value,
+            append,
+        }
+    }
+
+    /// `template` for the partition `value`.
+    fn request_for(&self, template: &Request, template_value: &AttributeValue, value: String) -> Request {
+        template.with_partition(self.partition_key, template_value, self.partition_type.into_value(value))
+            .expect("--operation builds no custom requests")
+    }
+
+    fn template(&self, i: usize) -> &Request {
+        &self.templates[i % self.templates.len()]
+    }
+}
+
+impl Workload for Generated<'_> {
+    fn request_on_shard(&self, i: usize, shard: usize, shards: usize) -> (Request, bool) {
+        let request = self.request_for(self.template(i), &self.template_value, self.keys.key_in_shard(i, shard, shards));
+        match &self.relative_bounds {
+            Some(bounds) => (bounds.apply(request), false),
+            None => (request, false),
+        }
+    }
+
+    fn request(&self, i: usize) -> (Request, bool) {
+        let initial = self.keys.range.end - self.keys.range.start;
+        if let (Some(offset), Some((put, first))) = (self.growth.and_then(|growth| growth.append(initial)), &self.append) {
+            return (self.request_for(put, first, self.keys.value_of(self.keys.range.start + offset)), false);
+        }
+        let size = initial + self.growth.map_or(0, Growth::appended);
+        let request = self.request_for(self.template(i), &self.template_value, self.keys.key_in(i, size));
+        match &self.relative_bounds {
+            Some(bounds) => (bounds.apply(request), false),
+            None => (request, false),
+        }
+    }
+
+    fn len(&self) -> usize {
+        (self.keys.range.end - self.keys.range.start) as usize
+    }
+}
+
+/// Prints, for consecutive windows of a --key-growth run, the keyspace size
+/// at their end and the latency of reads and appends, then how read latency
+/// trends with the size: the least-squares slope of the windows' read p50
+/// over the log2 of their size. `samples` are (start offset, latency, ok,
+/// append) and `initial` the keys there were when the run started.
+pub fn print_growth(samples: &mut [(Duration, Duration, bool, bool)], initial: u64, window: Duration,
+    started_at: SystemTime, offset: UtcOffset) {
+    samples.sort_by_key(|(start, ..)| *start);
+    println!("\nLatency by keyspace size ({}s windows, milliseconds):", window.as_secs_f64());
+    println!("{:<36} {:>12} {:>8} {:>10} {:>10} {:>8} {:>10}", "Window", "Keys", "Reads", "Read p50", "Read p99", "Appends", "Append p50");
+    let mut keys = initial;
+    let mut trend = Vec::new();
+    let index = |start: Duration| (start.as_secs_f64() / window.as_secs_f64()) as u32;
+    for window_samples in samples.chunk_by(|a, b| index(a.0) == index(b.0)) {
+        let (mut reads, mut appends) = (Vec::new(), Vec::new());
+        for (_, latency, _, append) in window_samples.iter().filter(|(_, _, ok, _)| *ok) {
+            if *append { appends.push(*latency) } else { reads.push(*latency) }
+            keys += *append as u64;
+        }
+        reads.sort();
+        appends.sort();
+        let read_p50 = (!reads.is_empty()).then(|| quantile_ms(&reads, 0.5));
+        let cell = |durations: &[Duration], quantile: f64| if durations.is_empty() {
+            "-".to_owned()
+        } else {
+            format!("{:.3}", quantile_ms(durations, quantile))
+        };
+        let start = window * index(window_samples[0].0);
+        println!("{:<36} {:>12} {:>8} {:>10} {:>10} {:>8} {:>10}", timestamp::both(started_at + start, offset), keys,
+            reads.len(), cell(&reads, 0.5), cell(&reads, 0.99), appends.len(), cell(&appends, 0.5));
+        if let Some(p50) = read_p50 {
+            trend.push(((keys as f64).log2(), p50));
+        }
+    }
+    if keys == initial || trend.len() < 2 {
+        println!("Too few windows or no growth to relate latency to keyspace size");
+        return;
+    }
+    let n = trend.len() as f64;
+    let (mean_x, mean_y) = (trend.iter().map(|(x, _)| x).sum::<f64>() / n, trend.iter().map(|(_, y)| y).sum::<f64>() / n);
+    let covariance: f64 = trend.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance: f64 = trend.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    println!("The keyspace grew {:.2}x, from {} to {} keys; read p50 changes by {:+.3} ms per doubling",
+        keys as f64 / initial as f64, initial, keys, if variance > 0.0 { covariance / variance } else { 0.0 });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DISTRIBUTIONS: [KeyDistribution; 4] =
+        [KeyDistribution::Uniform, KeyDistribution::Zipfian, KeyDistribution::Sequential, KeyDistribution::Latest];
+
+    #[test]
+    fn parse_range_rejects_empty_and_malformed() {
+        assert_eq!(parse_range("0..10"), Ok(0..10));
+        assert_eq!(parse_range("7..8"), Ok(7..8));
+        for malformed in ["5..5", "6..5", "5", "..5", "a..b", "-1..5", "0...5"] {
+            assert!(parse_range(malformed).is_err(), "{:?}", malformed);
+        }
+    }
+
+    #[test]
+    fn zipf_ranks_stay_in_range() {
+        for n in [1, 2, 3, 10, 1000] {
+            for theta in [0.01, 0.5, 0.99, 0.999_999] {
+                let zipf = Zipf::new(n, theta);
+                for u in [0.0, 1e-9, 0.25, 0.5, 0.75, 0.999_999_999] {
+                    assert!(zipf.rank(u) < n, "n {} theta {} u {}", n, theta, u);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn zipf_favours_low_ranks() {
+        let zipf = Zipf::new(1000, 0.99);
+        let hottest = (0..10_000).filter(|&i| zipf.rank(splitmix_uniform(i)) == 0).count();
+        // the first of 1000 keys takes about 13% of draws at theta 0.99
+        assert!(hottest > 1000, "{}", hottest);
+        assert_eq!(zipf.rank(0.0), 0);
+        assert!(zipf.rank(0.999_999) > 900);
+    }
+
+    fn splitmix_uniform(i: usize) -> f64 {
+        rng::splitmix(i as u64) as f64 / (u64::MAX as f64 + 1.0)
+    }
+
+    #[test]
+    fn single_key_range() {
+        for distribution in DISTRIBUTIONS {
+            let keys = KeyGenerator::new("user#", 7..8, distribution, 0.99);
+            for i in 0..100 {
+                assert_eq!(keys.key_in(i, 1), "user#7");
+                assert_eq!(keys.key_in_shard(i, 0, 1), "user#7");
+            }
+        }
+    }
+
+    #[test]
+    fn sequential_wraps_around() {
+        let keys = KeyGenerator::new("", 10..13, KeyDistribution::Sequential, 0.99);
+        let drawn: Vec<_> = (0..6).map(|i| keys.key_in(i, 3)).collect();
+        assert_eq!(drawn, ["10", "11", "12", "10", "11", "12"]);
+    }
+
+    #[test]
+    fn shards_cover_the_range_in_order() {
+        for (range, shards) in [(0..10, 1), (0..10, 3), (5..6, 1), (0..7, 7), (100..1_000_003, 64)] {
+            let mut next = range.start;
+            for shard in 0..shards {
+                let keys = shard_of(&range, shard, shards);
+                assert_eq!(keys.start, next);
+                assert!(keys.end > keys.start, "{:?} shard {} of {} is empty", range, shard, shards);
+                next = keys.end;
+            }
+            assert_eq!(next, range.end);
+        }
+    }
+
+    #[test]
+    fn shard_keys_stay_in_their_shard() {
+        let range = 0..100;
+        for distribution in DISTRIBUTIONS {
+            let keys = KeyGenerator::new("", range.clone(), distribution, 0.99);
+            for shard in 0..7 {
+                let owned = shard_of(&range, shard, 7);
+                for i in 0..200 {
+                    let key: u64 = keys.key_in_shard(i, shard, 7).parse().unwrap();
+                    assert!(owned.contains(&key), "{:?} key {} outside shard {:?}", distribution, key, owned);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{BenchArgs, Cli, Stage};
+use clap::ValueEnum;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// Version of the exported config, bumped when its shape changes.
+const VERSION: u32 = 1;
+
+/// What one stage of a completed run did.
+pub struct Phase<'a> {
+    pub stage: &'a Stage,
+    pub elapsed: Duration,
+    pub requests: usize,
+    pub errors: usize,
+}
+
+fn name(value: impl ValueEnum) -> String {
+    value.to_possible_value().expect("no variants are skipped").get_name().to_owned()
+}
+
+/// The effective workload of a run in the config format of the Chalk load
+/// testing service (--export-load-test): the keys and sort windows that were
+/// read, and the rates each stage ran at, so a benchmark here can seed a
+/// larger-scale test there. Relative sort key times (`now-1h`) are kept as
+/// written, to be resolved as that test runs.
+pub fn config(cli: &Cli, args: &BenchArgs, phases: &[Phase], table: Option<&Value>) -> Value {
+    let key = |name: &str, key_type| json!({ "name": name, "type": format!("{:?}", key_type) });
+    json!({
+        "format": "chalk-load-test",
+        "version": VERSION,
+        "target": {
+            "service": "dynamodb",
+            "region": cli.region,
+            "table": cli.table,
+            "partition_key": key(&cli.partition_key, cli.partition_type),
+            "sort_key": (!cli.no_sort_key).then(|| key(&cli.sort_key, cli.sort_type)),
+            "description": table,
+        },
+        "workload": workload(args),
+        "phases": phases.iter().map(|phase| json!({
+            "duration_secs": phase.elapsed.as_secs_f64(),
+            // virtual users send as fast as they're answered
+            "target_qps": args.virtual_users.is_none().then_some(phase.stage.qps),
+            "achieved_qps": phase.requests as f64 / phase.elapsed.as_secs_f64(),
+            "concurrency": phase.stage.parallelism,
+            "requests": phase.requests,
+            "errors": phase.errors,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+/// What each request of the run was: the operation, preset, custom workload or synthesized traffic.
+pub fn request_name(args: &BenchArgs) -> String {
+    match (args.workload, &args.custom, &args.synthesize_from) {
+        (_, _, Some(_)) => "synthesized".to_owned(),
+        (_, Some(custom), _) => custom.clone(),
+        (Some(preset), _, _) => name(preset),
+        (None, None, None) => name(args.operation),
+    }
+}
+
+fn workload(args: &BenchArgs) -> Value {
+    json!({
+        "request": request_name(args),
+        "keys": {
+            "partition_values": args.partition_value,
+            "partition_weights": args.partition_weight,
+            "generated": args.key_range.as_ref().map(|range| json!({
+                "prefix": args.key_prefix,
+                "start": range.start,
+                "end": range.end,
+                "distribution": name(args.key_distribution),
+                "zipf_theta": args.zipf_theta,
+            })),
+            "sort_values": args.sort_value,
+        },
+        "rng": {
+            "backend": name(args.rng),
+            "seed": args.rng_seed,
+        },
+        "sort_window": {
+            "start": args.sort_start,
+            "end": args.sort_end,
+            "op": args.sort_op.map(name),
+            "width": args.sort_window,
+            "time_format": name(args.time_format),
+        },
+        "synthesized": args.synthesize_from.as_ref().map(|environment| json!({
+            "environment": environment,
+            "aggregation_weights": args.aggregation_weight.iter()
+                .map(|(key, weight)| (key.clone(), json!(weight)))
+                .collect::<serde_json::Map<_, _>>(),
+            "buckets": args.synth_buckets,
+        })),
+        "batch_size": args.operation.is_batch().then_some(args.batch_size),
+        "virtual_users": args.virtual_users.map(|users| json!({ "users": users, "think_time_ms": args.think_time_ms })),
+    })
+}
@@ -1,11 +1,65 @@
-use aws_sdk_dynamodb::operation::query::builders::QueryFluentBuilder;
-use aws_sdk_dynamodb::{types::AttributeValue, Client};
-use clap::{Subcommand, Parser, Args};
-use regex::Regex;
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::{Duration, Instant};
+mod annotate;
+mod budget;
+mod calibrate;
+mod coldstart;
+mod condition;
+mod config;
+mod create_table;
+mod credentials;
+mod custom;
+mod deadline;
+mod describe;
+mod guard;
+mod hedge;
+mod histogram;
+mod hooks;
+mod items;
+mod keygen;
+mod loadtest;
+mod mapping;
+mod metadata;
+mod metrics;
+mod mock;
+mod overhead;
+mod pair;
+mod partitions;
+mod phases;
+mod prewarm;
+mod profile;
+mod redact;
+mod request;
+mod rng;
+mod rundiff;
+mod scan;
+mod schema;
+mod script;
+mod seed;
+mod selftest;
+mod slo;
+mod splits;
+mod stats;
+mod sweep;
+mod synthesis;
+mod template;
+mod throttle;
+mod timestamp;
+mod tuner;
+mod workload;
+
+use aws_sdk_dynamodb::Client;
+use clap::{CommandFactory, FromArgMatches, Subcommand, Parser, Args, ValueEnum};
+use clap::parser::ValueSource;
+use clap::error::ErrorKind;
+use workload::{is_picked, Mix, Workload};
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use ::time::UtcOffset;
 use tokio::sync::Semaphore;
+use tokio::task::{JoinError, JoinSet};
 use tokio::time;
 
 #[derive(Parser)]
@@ -19,103 +73,1150 @@ struct Cli {
     #[arg(short, long)]
     table: String,
 
+    /// Disable SDK request compression (rejected: the SDK doesn't compress DynamoDB requests)
+    #[arg(long)]
+    disable_request_compression: bool,
+
+    /// Minimum request body size in bytes before the SDK compresses it (rejected: the SDK
+    /// doesn't compress DynamoDB requests)
+    #[arg(long)]
+    request_min_compression_size: Option<u32>,
+
+    /// Replace partition/sort key values with hashes in all output
+    #[arg(long)]
+    redact_keys: bool,
+
+    /// UTC offset that timestamps are also shown in, e.g. `-07:00`
+    #[arg(long, value_parser = timestamp::parse_offset, default_value = "UTC")]
+    timezone: UtcOffset,
+
+    /// HTTP client used by the SDK
+    #[arg(long, value_enum, default_value_t = HttpClient::Hyper)]
+    http_client: HttpClient,
+
     /// AWS region
     #[arg(short, long)]
     region: String,
 
-    /// Partition key name
+    /// Partition key name (detected from DescribeTable when not given)
     #[arg(short = 'p', long, default_value = "__id__")]
     partition_key: String,
 
-    /// Sort key name
+    /// Sort key name (detected from DescribeTable when not given)
     #[arg(short = 's', long, default_value = "__ns__")]
     sort_key: String,
 
+    /// Partition key attribute type (detected from DescribeTable when not given)
+    #[arg(long, value_enum, ignore_case = true, default_value_t = request::KeyType::S)]
+    partition_type: request::KeyType,
+
+    /// Sort key attribute type (detected from DescribeTable when not given)
+    #[arg(long, value_enum, ignore_case = true, default_value_t = request::KeyType::S)]
+    sort_type: request::KeyType,
+
+    /// The table has no sort key: items are addressed by partition key alone
+    /// (detected from DescribeTable when not given)
+    #[arg(long, conflicts_with_all = ["sort_key", "sort_type"])]
+    no_sort_key: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+/// HTTP clients the SDK can be built with. There is no AWS CRT choice: the
+/// Rust SDK's smithy runtime ships no CRT connector to plug in.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum HttpClient {
+    /// hyper 0.14 with rustls (the SDK default)
+    Hyper,
+}
+
+impl HttpClient {
+    fn name(self) -> &'static str {
+        match self {
+            HttpClient::Hyper => "hyper",
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
-    Bench(BenchArgs),
-    ShowMapping {
-        /// Chalk environment name
-        #[arg(short, long)]
-        environment: String
+    Bench(Box<BenchArgs>),
+    /// Parallel Scan of the whole table
+    Scan(scan::ScanArgs),
+    /// Sample the table with Scan and rank partition key values by item count and size
+    AnalyzePartitions(partitions::AnalyzeArgs),
+    /// Sample the table with Scan and print histograms of item sizes and attribute counts
+    AnalyzeItems(items::ItemsArgs),
+    /// Print the FQN mappings of a Chalk environment
+    ShowMapping(mapping::MappingArgs),
+    /// Compare the FQN mappings of two Chalk environments
+    MappingDiff(mapping::MappingDiffArgs),
+    /// Print the JSON Schema of a machine-readable output (no table or region needed)
+    Schema(schema::SchemaArgs),
+    /// Compare the latency of two runs from their raw logs or HdrHistogram distributions (no table or region needed)
+    DiffRuns(rundiff::DiffRunsArgs),
+    /// Print the table's key schema, capacity, indexes and size
+    Describe(describe::DescribeArgs),
+    /// Create the table with the --partition-key/--sort-key schema and wait until it is ACTIVE
+    CreateTable(create_table::CreateTableArgs),
+    /// Write synthetic items with the table's key schema, to benchmark against
+    Seed(seed::SeedArgs),
+    /// Delete the items written by `seed` (or `prewarm`)
+    Cleanup(seed::CleanupArgs),
+    /// Ramp load on a new table up to a target rate until it stops throttling
+    Prewarm(prewarm::PrewarmArgs),
+    /// Time client construction and endpoint resolution for shared and per-request clients
+    ClientOverhead(overhead::OverheadArgs),
+    /// Time fresh processes that each build a client and send their first requests, like cold Lambda invocations
+    ColdStart(coldstart::ColdStartArgs),
+    /// One cold-start invocation, run by `cold-start` in a process of its own
+    #[command(hide = true)]
+    ColdStartInvocation(coldstart::InvocationArgs),
+    /// Benchmark the load generator itself against an in-process mock DynamoDB
+    Selftest(Box<selftest::SelftestArgs>),
+    /// Run the phases listed in a config file (seed, read, mixed, cleanup, ...) one after another
+    Phases(phases::PhasesArgs),
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Operation {
+    /// Range Query on the sort key
+    Query,
+    /// GetItem on exact (partition, sort) pairs
+    GetItem,
+    /// PutItem of --item-size byte payloads on (partition, sort) pairs
+    PutItem,
+    /// UpdateItem with --update-expression on (partition, sort) pairs
+    UpdateItem,
+    /// BatchGetItem of --batch-size (partition, sort) pairs per call
+    BatchGetItem,
+    /// BatchWriteItem of --batch-size items per call
+    BatchWriteItem,
+    /// TransactWriteItems putting --batch-size items per call
+    TransactWriteItems,
+    /// TransactGetItems reading --batch-size (partition, sort) pairs per call
+    TransactGetItems,
+    /// PartiQL ExecuteStatement built from --statement
+    ExecuteStatement,
+    /// PartiQL BatchExecuteStatement of --batch-size statements per call
+    BatchExecuteStatement,
+    /// Requests that fail fast with the --error-kind error
+    Error,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum FailureKind {
+    /// GetItem against a table that doesn't exist (ResourceNotFoundException)
+    MissingTable,
+    /// PutItem whose condition can never hold (ConditionalCheckFailedException)
+    ConditionalCheck,
+    /// Query with an undefined expression value (ValidationException)
+    Validation,
+}
+
+impl FailureKind {
+    fn expected_code(self) -> &'static str {
+        match self {
+            FailureKind::MissingTable => "ResourceNotFoundException",
+            FailureKind::ConditionalCheck => "ConditionalCheckFailedException",
+            FailureKind::Validation => "ValidationException",
+        }
+    }
+}
+
+impl Operation {
+    fn max_batch_size(self) -> usize {
+        match self {
+            Operation::BatchWriteItem | Operation::BatchExecuteStatement => 25,
+            _ => 100,
+        }
+    }
+
+    fn is_batch(self) -> bool {
+        matches!(self, Operation::BatchGetItem | Operation::BatchWriteItem)
+    }
+
+    fn is_transaction(self) -> bool {
+        matches!(self, Operation::TransactWriteItems | Operation::TransactGetItems)
+    }
+
+    fn needs_sort_values(self) -> bool {
+        !matches!(self, Operation::Query | Operation::ExecuteStatement | Operation::BatchExecuteStatement | Operation::Error)
     }
 }
 
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Clone)]
 struct BenchArgs {
+    /// YAML or TOML file with benchmark settings (see README); flags given on
+    /// the command line override it
+    #[arg(long)]
+    config: Option<String>,
+
+    /// Built-in settings for a standard Chalk table layout (see src/profile.rs); --config
+    /// and flags given on the command line override it
+    #[arg(long, value_parser = clap::builder::PossibleValuesParser::new(profile::names()))]
+    chalk_profile: Option<String>,
+
+    /// Chalk environment whose FQN mappings resolve partition values to aggregation
+    /// keys for the latency breakdown by namespace
+    #[arg(long, requires = "chalk_profile")]
+    chalk_environment: Option<String>,
+
+    #[command(flatten)]
+    mapping_cache: mapping::CacheArgs,
+
+    #[command(flatten)]
+    metrics: metrics::MetricsArgs,
+
+    #[command(flatten)]
+    pair: pair::PairArgs,
+
+    #[command(flatten)]
+    split_watch: splits::SplitWatchArgs,
+
+    #[command(flatten)]
+    histogram: histogram::HistogramArgs,
+
+    /// Operation to benchmark
+    #[arg(short = 'o', long, value_enum, default_value_t = Operation::Query)]
+    operation: Operation,
+
+    /// Built-in operation mix to run instead of a single --operation
+    #[arg(long, value_enum, conflicts_with = "operation")]
+    workload: Option<workload::Preset>,
+
+    /// Custom workload registered in src/custom.rs to run instead of a single --operation
+    #[arg(long, value_parser = clap::builder::PossibleValuesParser::new(custom::names()),
+        conflicts_with_all = ["operation", "workload"])]
+    custom: Option<String>,
+
+    /// Sub-ranges `--custom split-range` divides the --sort-start..--sort-end range into
+    #[arg(long, default_value = "4", requires = "custom")]
+    split_ranges: usize,
+
+    /// For `--custom query-then-get`: GetItem key attribute and the queried attribute it is
+    /// copied from, as `key=attribute`, repeatable (default: the table's partition and sort key)
+    #[arg(long, value_parser = parse_assignment)]
+    chain_key: Vec<(String, String)>,
+
+    /// For `--custom query-then-get`: table the GetItems read from (default: --table)
+    #[arg(long)]
+    chain_table: Option<String>,
+
+    /// For `--custom negative-cache`: requests after a miss at which its freshly written
+    /// marker is read again
+    #[arg(long, default_value = "100", requires = "custom", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    reread_after: usize,
+
+    /// Rhai script computing partition values, sort values or ranges and payloads per request
+    #[arg(long, conflicts_with_all = ["workload", "custom"])]
+    script: Option<String>,
+
     /// Partition key value
     #[arg(short = 'P', long)]
     partition_value: Vec<String>,
 
-    /// Sort key start value (for range query)
+    /// Synthesize read traffic for this whole Chalk environment from its FQN mappings: each
+    /// request reads the latest --synth-buckets buckets of one aggregation key for one -P entity
+    #[arg(long, conflicts_with_all = ["operation", "workload", "custom", "script", "key_range", "sort_start", "sort_end", "sort_window", "sort_op"])]
+    synthesize_from: Option<String>,
+
+    /// Relative weight of an aggregation key with --synthesize-from, as KEY=WEIGHT; when given,
+    /// only the listed keys are read (default: every key equally)
+    #[arg(long, value_parser = parse_weight, requires = "synthesize_from")]
+    aggregation_weight: Vec<(String, f64)>,
+
+    /// Buckets each --synthesize-from request reads
+    #[arg(long, default_value = "24")]
+    synth_buckets: u64,
+
+    /// Share of traffic for each -P value, in the same order, e.g. `-P hot -P cold
+    /// --partition-weight 0.9 --partition-weight 0.1` (default: round-robin)
+    #[arg(long, requires = "partition_value")]
+    partition_weight: Vec<f64>,
+
+    /// Generate partition values as --key-prefix followed by a number in this `START..END`
+    /// range (end exclusive), instead of listing them with -P
+    #[arg(long, value_parser = keygen::parse_range,
+        conflicts_with_all = ["partition_value", "workload", "custom", "script"])]
+    key_range: Option<std::ops::Range<u64>>,
+
+    /// Prefix of generated partition values
+    #[arg(long, default_value = "", requires = "key_range")]
+    key_prefix: String,
+
+    /// How generated partition values are spread over --key-range
+    #[arg(long, value_enum, default_value_t = keygen::KeyDistribution::Uniform, requires = "key_range")]
+    key_distribution: keygen::KeyDistribution,
+
+    /// Skew of the zipfian and latest --key-distribution, in (0, 1)
+    #[arg(long, default_value = "0.99", requires = "key_range")]
+    zipf_theta: f64,
+
+    /// Random number generator behind generated keys, sort windows and item template
+    /// placeholders; operation mixes and miss picks follow fixed sequences instead
+    #[arg(long, value_enum, default_value = "splitmix")]
+    rng: rng::Backend,
+
+    /// Seed of the xoshiro --rng
+    #[arg(long, default_value = "0")]
+    rng_seed: u64,
+
+    /// Grow the keyspace past --key-range by this share per second, e.g. `1%`, appending
+    /// new partitions with PutItems, and report latency against the keyspace size
+    #[arg(long, value_parser = parse_fraction, requires = "key_range")]
+    key_growth: Option<f64>,
+
+    /// Exact sort key value (for get-item/put-item, paired with every partition value)
+    #[arg(short = 'V', long)]
+    sort_value: Vec<String>,
+
+    /// Sort key start value (for range query), or a time relative to each query such as `now-1h`
     #[arg(short = 'S', long)]
     sort_start: Option<String>,
 
-    /// Sort key end value (for range query)
+    /// Sort key end value (for range query), or a time relative to each query such as `now`
     #[arg(short = 'E', long)]
     sort_end: Option<String>,
 
+    /// Sort key condition instead of the --sort-start..--sort-end range: lt compares
+    /// with --sort-end, the others with --sort-start
+    #[arg(long, value_enum)]
+    sort_op: Option<workload::SortOp>,
+
+    /// Global or local secondary index to query instead of the table; the key flags then
+    /// name the index's keys, and are detected from its key schema when not given
+    #[arg(long, conflicts_with_all = ["workload", "custom", "script", "synthesize_from"])]
+    index_name: Option<String>,
+
+    /// Query `Limit`: read at most this many items per query, e.g. the first K of a range
+    /// (the page size of --custom aggregations, which follow every page)
+    #[arg(long, conflicts_with = "script", value_parser = clap::value_parser!(i32).range(1..))]
+    limit: Option<i32>,
+
+    /// Attributes each query returns, e.g. `attr1,attr2`, instead of whole items
+    #[arg(long, conflicts_with_all = ["custom", "script"], value_delimiter = ',')]
+    projection: Vec<String>,
+
+    /// Read each query's range in descending sort key order (`ScanIndexForward=false`),
+    /// e.g. the most recent time-series bucket first
+    #[arg(long, conflicts_with = "script")]
+    descending: bool,
+
+    /// Follow LastEvaluatedKey until each query's result is exhausted, timing the whole
+    /// request and reporting page counts and per-page latency
+    #[arg(long, conflicts_with_all = ["custom", "script", "synthesize_from"])]
+    paginate: bool,
+
+    /// Query with `Select=COUNT`, returning only how many items match instead of the items
+    #[arg(long, conflicts_with_all = ["custom", "script", "projection"])]
+    select_count: bool,
+
+    /// Query even-numbered requests with `Select=COUNT` and odd-numbered ones for the items,
+    /// and compare the two halves
+    #[arg(long, conflicts_with_all = ["custom", "script", "projection", "select_count", "compare_consistency",
+        "hedge_after", "retry_deadline_ms", "variance_check"])]
+    compare_select_count: bool,
+
+    /// Raw key condition expression for queries, e.g. `#pk = :pk AND begins_with(#sk, :prefix)`,
+    /// instead of one built from --sort-start/--sort-end. `#pk` and `#sk` name the keys
+    #[arg(long, conflicts_with_all = ["workload", "custom", "script", "key_range", "synthesize_from",
+        "sort_start", "sort_end", "sort_op", "sort_window", "sort_value"])]
+    key_condition: Option<String>,
+
+    /// Value generator for a --key-condition placeholder, as `:name=GENERATOR`: `partition`,
+    /// `choice:a|b|c`, `int:LO..HI`, `time:now-1h` or a literal value
+    #[arg(long, value_parser = parse_assignment, requires = "key_condition")]
+    condition_value: Vec<(String, String)>,
+
+    /// Variable drawn once per request and substituted for `{name}` in every --condition-value,
+    /// as `name=GENERATOR`, so one value (e.g. an entity id) can appear in both keys
+    #[arg(long, value_parser = parse_assignment, requires = "key_condition")]
+    template_var: Vec<(String, String)>,
+
+    /// How relative --sort-start/--sort-end times are written as sort key values
+    #[arg(long, value_enum, default_value_t = timestamp::TimeFormat::EpochMillis)]
+    time_format: timestamp::TimeFormat,
+
+    /// Query a window this wide at a different position within numeric --sort-start..--sort-end
+    /// for each query, instead of the whole range every time
+    #[arg(long, requires_all = ["sort_start", "sort_end"], conflicts_with_all = ["custom", "script", "key_range", "sort_op"])]
+    sort_window: Option<u64>,
+
     /// Number of query operations to perform
     #[arg(short, long, default_value = "100")]
     num_queries: usize,
 
+    /// Run the fewest queries that make this percentile (e.g. `p99.9`) trustworthy at
+    /// --confidence, instead of --num-queries
+    #[arg(long, value_parser = parse_percentile, conflicts_with_all = ["num_queries", "stage"])]
+    min_samples_for: Option<f64>,
+
+    /// Confidence level for --min-samples-for
+    #[arg(long, default_value = "0.95", requires = "min_samples_for")]
+    confidence: f64,
+
     /// QPS (queries per second) limit
     #[arg(long, default_value = "10")]
     qps: u32,
 
+    /// Run these stages in sequence instead of a flat --num-queries/--qps run, each as
+    /// `LIMIT@QPS[/PARALLELISM]` where LIMIT is a query count or a duration such as `60s` or `2m`
+    #[arg(long, value_parser = parse_stage)]
+    stage: Vec<Stage>,
+
     /// Parallelism level (number of concurrent queries)
     #[arg(short = 'k', long, default_value = "1")]
     parallelism: usize,
+
+    /// Grow the concurrency cap from --parallelism while throughput improves and p99 stays
+    /// under this many milliseconds, then report the level chosen
+    #[arg(long, conflicts_with_all = ["stage", "virtual_users"])]
+    auto_parallelism: Option<f64>,
+
+    /// Run closed-loop instead of pacing at --qps: this many virtual users each send a request,
+    /// wait for its response and --think-time-ms, then send their next one
+    #[arg(long, conflicts_with_all = ["qps", "stage", "parallelism"])]
+    virtual_users: Option<usize>,
+
+    /// Pause between a virtual user's response and its next request
+    #[arg(long, default_value = "0", requires = "virtual_users")]
+    think_time_ms: u64,
     
     /// Number of warmup queries to run before the benchmark (to eliminate cold-start effects)
     #[arg(short = 'w', long, default_value = "10")]
     warmup_queries: usize,
+
+    /// QPS limit during warmup (defaults to --qps)
+    #[arg(long)]
+    warmup_qps: Option<u32>,
+
+    /// Keep probing for this many seconds after the run and report whether
+    /// latency returns to the warmup baseline
+    #[arg(long)]
+    cooldown_secs: Option<f64>,
+
+    /// QPS of the cooldown probes
+    #[arg(long, default_value = "1")]
+    cooldown_qps: u32,
+
+    /// Cooldown p50 counts as back to baseline when within this factor of the warmup p50
+    #[arg(long, default_value = "1.2")]
+    cooldown_tolerance: f64,
+
+    /// Pause sending when more than this share of requests in a second are throttled, e.g. `5%`
+    #[arg(long, value_parser = parse_fraction)]
+    max_throttle_rate: Option<f64>,
+
+    /// Refuse to run writing workloads against tables carrying this tag, as `key=value`, repeatable
+    #[arg(long, value_parser = parse_assignment, default_value = "env=production")]
+    protect_tag: Vec<(String, String)>,
+
+    /// Run writing workloads even against tables matching --protect-tag
+    #[arg(long)]
+    allow_protected_table: bool,
+
+    /// Stop the run once requests have consumed this many read capacity units, warmup and cooldown included
+    #[arg(long)]
+    max_rcu_budget: Option<f64>,
+
+    /// Stop the run once requests have consumed this many write capacity units, warmup and cooldown included
+    #[arg(long)]
+    max_wcu_budget: Option<f64>,
+
+    /// Capacity detail requests ask for: `total` per request, or `indexes` to also split
+    /// it over the table and each index the request touched
+    #[arg(long, value_enum, default_value = "total")]
+    consumed_capacity: request::CapacityDetail,
+
+    /// How long to pause for when --max-throttle-rate is exceeded
+    #[arg(long, default_value = "5", requires = "max_throttle_rate")]
+    throttle_pause_secs: f64,
+
+    /// Size in bytes of the JSON payload attribute written by put-item/batch-write-item
+    #[arg(long, default_value = "1024")]
+    item_size: usize,
+
+    /// Split the --item-size payload over this many attributes (`attr0`, `attr1`, ...)
+    #[arg(long, default_value = "1", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    attribute_count: usize,
+
+    /// Run the write workload once per item size, e.g. `1KB,4KB,16KB,64KB,256KB`, and compare them
+    #[arg(long, conflicts_with_all = ["item_template", "workload", "custom", "script", "key_condition", "synthesize_from"],
+        value_parser = parse_size, value_delimiter = ',')]
+    sweep_item_size: Vec<usize>,
+
+    /// Run the write workload once per attribute count, e.g. `1,10,50`, and compare them
+    #[arg(long, conflicts_with_all = ["item_template", "workload", "custom", "script", "key_condition", "synthesize_from"],
+        value_delimiter = ',', value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    sweep_attribute_count: Vec<usize>,
+
+    /// Run the query once per Limit, e.g. `10,100,1000,none` (`none` for no Limit), and compare them
+    #[arg(long, conflicts_with_all = ["workload", "custom", "script", "limit", "sweep_item_size", "sweep_attribute_count"],
+        value_delimiter = ',', value_parser = parse_limit)]
+    sweep_limit: Vec<Option<i32>>,
+
+    /// JSON object whose attributes are written with every item, instead of a --item-size payload.
+    /// Strings may hold `{{uuid}}`, `{{randint LO HI}}` and `{{lorem N}}`, rendered for each item
+    #[arg(long)]
+    item_template: Option<String>,
+
+    /// Keys per call for batch-get-item (at most 100), batch-write-item (at most 25)
+    /// and transact-write-items/transact-get-items (at most 100)
+    #[arg(short = 'b', long, default_value = "25")]
+    batch_size: usize,
+
+    /// Update expression for update-item, e.g. `SET #v = :v` or `ADD #c :inc`
+    #[arg(long, default_value = "ADD #c :inc")]
+    update_expression: String,
+
+    /// Expression attribute name for update-item, as `#name=attribute`
+    #[arg(long, value_parser = parse_assignment, default_value = "#c=counter")]
+    expression_name: Vec<(String, String)>,
+
+    /// Expression attribute value for update-item, as `:name=<JSON value>`
+    #[arg(long, value_parser = parse_assignment, default_value = ":inc=1")]
+    expression_value: Vec<(String, String)>,
+
+    /// PartiQL statement template for execute-statement/batch-execute-statement. `{table}`,
+    /// `{partition_key}` and `{sort_key}` are replaced by names; `{partition_value}`,
+    /// `{sort_value}`, `{sort_start}` and `{sort_end}` by string literals.
+    /// Defaults to the SELECT equivalent of the query (or get-item, with --sort-value)
+    #[arg(long)]
+    statement: Option<String>,
+
+    /// Kind of failure to provoke for --operation error
+    #[arg(long, value_enum, default_value_t = FailureKind::MissingTable)]
+    error_kind: FailureKind,
+
+    /// Restrict requests to this share of the keyspace, e.g. `10%` or `0.1`
+    #[arg(long, value_parser = parse_fraction, default_value = "100%")]
+    working_set: f64,
+
+    /// Fraction of query/get-item requests that target keys known to be absent
+    #[arg(long, default_value = "0")]
+    miss_ratio: f64,
+
+    /// Print request count and latency per worker slot (or virtual user), to spot starved workers
+    #[arg(long)]
+    worker_stats: bool,
+
+    /// Split --key-range into one contiguous shard per worker slot (or virtual user), each
+    /// drawing its keys from its own shard only, so per-worker stats are per-shard stats
+    #[arg(long, requires = "key_range", conflicts_with_all = ["key_growth", "auto_parallelism"])]
+    worker_key_affinity: bool,
+
+    /// Annotate the timeline with the contents of this file whenever the process receives
+    /// SIGUSR2, e.g. "deployed new build here"
+    #[arg(long)]
+    annotation_file: Option<String>,
+
+    /// Before the run, time round trips to a local endpoint through the same client stack and
+    /// report them as the measurement floor, with the run's percentiles net of it
+    #[arg(long)]
+    calibrate_floor: bool,
+
+    /// Hedge even-numbered requests: send a duplicate when one is still unanswered after this
+    /// percentile (e.g. `p95`) of the odd-numbered, unhedged requests' latency, and compare the two halves
+    #[arg(long, value_parser = parse_percentile, conflicts_with_all = ["raw_log", "capture"])]
+    hedge_after: Option<f64>,
+
+    /// Read with strong consistency (ConsistentRead) instead of the default eventual consistency
+    #[arg(long)]
+    consistent_read: bool,
+
+    /// Read even-numbered requests with strong and odd-numbered ones with eventual consistency,
+    /// and compare the two halves
+    #[arg(long, conflicts_with_all = ["consistent_read", "hedge_after", "retry_deadline_ms", "variance_check"])]
+    compare_consistency: bool,
+
+    /// Cancel even-numbered requests still unanswered after this many milliseconds and send a
+    /// fresh attempt at once, and compare them with the odd-numbered requests, which wait
+    #[arg(long, conflicts_with_all = ["raw_log", "capture", "hedge_after"])]
+    retry_deadline_ms: Option<f64>,
+
+    /// Attempts per request under --retry-deadline-ms; the last one is waited out
+    #[arg(long, default_value = "3", requires = "retry_deadline_ms")]
+    retry_attempts: u32,
+
+    /// Latency SLO to express the results against as an error-budget burn rate, e.g.
+    /// `99%<20ms/30d`, repeatable
+    #[arg(long, value_parser = slo::parse)]
+    slo: Vec<slo::Slo>,
+
+    /// Report the percentiles of even- and odd-numbered requests separately and their
+    /// difference, an estimate of run-to-run noise
+    #[arg(long)]
+    variance_check: bool,
+
+    /// Run the benchmark with the default credential provider chain, then with the credentials
+    /// it resolved as static ones, and compare latency and time spent resolving credentials and signing
+    #[arg(long, conflicts_with_all = ["sweep_item_size", "sweep_attribute_count", "sweep_limit"])]
+    compare_credentials: bool,
+
+    /// Endpoint for --calibrate-floor, e.g. DynamoDB Local's `http://localhost:8000`,
+    /// instead of an in-process mock
+    #[arg(long, requires = "calibrate_floor")]
+    calibration_endpoint: Option<String>,
+
+    /// Print per-interval statistics over windows of this many seconds
+    #[arg(long)]
+    report_interval: Option<f64>,
+
+    /// Write an NDJSON line with response metadata for sampled requests to this file
+    #[arg(long)]
+    raw_log: Option<String>,
+
+    /// Share of requests written to --raw-log, e.g. `1%`
+    #[arg(long, value_parser = parse_fraction, default_value = "100%")]
+    raw_log_sample: f64,
+
+    /// Write an NDJSON line for every measured request (operation, parameters, start time,
+    /// latency, result size, request id) to this file, for offline analysis and replay
+    #[arg(long)]
+    capture: Option<String>,
+
+    /// Write every failed request's context (timestamp, request id, operation,
+    /// hashed keys, error chain) to this JSON file
+    #[arg(long)]
+    error_report: Option<String>,
+
+    /// Write the run's effective workload (keys, sort windows, achieved rates) to this file
+    /// as a Chalk load-test config, to seed larger-scale tests
+    #[arg(long)]
+    export_load_test: Option<String>,
+
+    /// Keys in each batch-get-item call that deliberately don't exist
+    #[arg(long, default_value = "0")]
+    missing_keys: usize,
+}
+
+fn parse_assignment(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        .ok_or_else(|| format!("expected NAME=VALUE, got {:?}", s))
+}
+
+fn parse_weight(s: &str) -> Result<(String, f64), String> {
+    let (key, weight) = parse_assignment(s)?;
+    match weight.parse::<f64>() {
+        Ok(weight) if weight > 0.0 => Ok((key, weight)),
+        _ => Err(format!("weight of {:?} must be a positive number, got {:?}", key, weight)),
+    }
+}
+
+/// Parses a size in bytes, plain or with a `KB` or `MB` suffix (powers of 1024).
+fn parse_size(s: &str) -> Result<usize, String> {
+    let upper = s.to_ascii_uppercase();
+    let (number, unit) = match (upper.strip_suffix("KB"), upper.strip_suffix("MB")) {
+        (Some(number), _) => (number, 1024),
+        (_, Some(number)) => (number, 1024 * 1024),
+        _ => (upper.strip_suffix('B').unwrap_or(&upper), 1),
+    };
+    match number.trim().parse::<usize>().ok().filter(|&n| n > 0).and_then(|n| n.checked_mul(unit)) {
+        Some(size) => Ok(size),
+        None => Err(format!("invalid size {:?}, expected e.g. 4KB or 4096", s)),
+    }
+}
+
+/// Parses a --sweep-limit value: a positive Query Limit, or `none` for no Limit.
+fn parse_limit(s: &str) -> Result<Option<i32>, String> {
+    if s.eq_ignore_ascii_case("none") {
+        return Ok(None);
+    }
+    match s.trim().parse::<i32>() {
+        Ok(n) if n > 0 => Ok(Some(n)),
+        _ => Err(format!("invalid limit {:?}, expected a positive number or `none`", s)),
+    }
+}
+
+/// Parses a percentile such as `p99.9` or `99.9` into a quantile (0.999).
+fn parse_percentile(s: &str) -> Result<f64, String> {
+    let percent = s.strip_prefix('p').unwrap_or(s).parse::<f64>()
+        .map_err(|e| format!("invalid percentile {:?}: {}", s, e))?;
+    if percent > 0.0 && percent < 100.0 {
+        Ok(percent / 100.0)
+    } else {
+        Err(format!("{:?} is not in (0, 100)", s))
+    }
+}
+
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    let fraction = match s.strip_suffix('%') {
+        Some(percent) => percent.parse::<f64>().map(|p| p / 100.0),
+        None => s.parse::<f64>(),
+    }.map_err(|e| format!("invalid fraction {:?}: {}", s, e))?;
+    if fraction > 0.0 && fraction <= 1.0 {
+        Ok(fraction)
+    } else {
+        Err(format!("{:?} is not in (0%, 100%]", s))
+    }
 }
 
-fn make_query(client: &Client, cli: &Cli, args: &BenchArgs) -> Vec<QueryFluentBuilder> {
-    let mut query_without_pkey = client
-        .query()
-        .table_name(&cli.table)
-        .expression_attribute_names("#pk", &cli.partition_key);
+/// `secs` seconds, if that is a positive, non-zero duration a `Duration` can hold.
+fn positive_secs(secs: f64) -> Option<Duration> {
+    Some(secs).filter(|secs| secs.is_finite() && *secs > 0.0)
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .filter(|duration| !duration.is_zero())
+}
+
+/// One stage of a multi-stage run.
+#[derive(Debug, Clone, Copy)]
+struct Stage {
+    limit: StageLimit,
+    qps: u32,
+    parallelism: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum StageLimit {
+    Queries(usize),
+    Duration(Duration),
+}
+
+impl Stage {
+    fn planned_queries(&self) -> usize {
+        match self.limit {
+            StageLimit::Queries(n) => n,
+            StageLimit::Duration(d) => (d.as_secs_f64() * self.qps as f64).ceil() as usize,
+        }
+    }
 
-    if let Some(start) = &args.sort_start {
-        query_without_pkey = query_without_pkey
-            .expression_attribute_values(":start", AttributeValue::S(start.clone()))
-            .expression_attribute_names("#sk", &cli.sort_key)
+    /// Whether the stage sends another request, given how many it has sent and for how long.
+    fn continues(&self, sent: usize, elapsed: Duration) -> bool {
+        match self.limit {
+            StageLimit::Queries(n) => sent < n,
+            StageLimit::Duration(d) => elapsed < d,
+        }
     }
-    if let Some(end) = &args.sort_end {
-        query_without_pkey = query_without_pkey
-            .expression_attribute_values(":end", AttributeValue::S(end.clone()))
-            .expression_attribute_names("#sk", &cli.sort_key)
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.limit {
+            StageLimit::Queries(n) => write!(f, "{} queries", n)?,
+            StageLimit::Duration(d) => write!(f, "{}s", d.as_secs_f64())?,
+        }
+        write!(f, " at {} QPS with parallelism of {}", self.qps, self.parallelism)
     }
+}
 
-    let sort_key_condition = match (args.sort_start.is_some(), args.sort_end.is_some()) {
-        (true, true) =>  " AND #sk BETWEEN :start AND :end",
-        (true, false) => " AND #sk >= :start",
-        (false, true) => " AND #sk <= :end",
-        (false, false) => ""
+/// Parses `LIMIT@QPS[/PARALLELISM]`; a stage without parallelism gets 0,
+/// which [`BenchArgs::stages`] replaces by --parallelism.
+fn parse_stage(s: &str) -> Result<Stage, String> {
+    let invalid = |reason: &str| format!("invalid stage {:?}: {} (expected LIMIT@QPS[/PARALLELISM], e.g. 60s@100/8)", s, reason);
+    let (limit, rate) = s.split_once('@').ok_or_else(|| invalid("missing @QPS"))?;
+    let (qps, parallelism) = match rate.split_once('/') {
+        Some((qps, parallelism)) => (qps, parallelism.parse().ok().filter(|&k| k > 0)
+            .ok_or_else(|| invalid("parallelism must be a positive integer"))?),
+        None => (rate, 0),
     };
+    let qps = qps.parse().ok().filter(|&qps| qps > 0).ok_or_else(|| invalid("QPS must be a positive integer"))?;
+    let seconds = |n: &str, scale: f64| n.parse::<f64>().ok().filter(|n| *n > 0.0)
+        .and_then(|n| Duration::try_from_secs_f64(n * scale).ok())
+        .map(StageLimit::Duration);
+    let limit = if let Some(ms) = limit.strip_suffix("ms") {
+        seconds(ms, 0.001)
+    } else if let Some(secs) = limit.strip_suffix('s') {
+        seconds(secs, 1.0)
+    } else if let Some(mins) = limit.strip_suffix('m') {
+        seconds(mins, 60.0)
+    } else {
+        limit.parse().ok().filter(|&n| n > 0).map(StageLimit::Queries)
+    }.ok_or_else(|| invalid("LIMIT must be a query count or a duration like 60s"))?;
+    Ok(Stage { limit, qps, parallelism })
+}
 
-    query_without_pkey = query_without_pkey.key_condition_expression(format!("#pk = :pk{}", sort_key_condition));
+impl BenchArgs {
+    /// Whether the workload writes to the table.
+    fn writes(&self) -> bool {
+        if let Some(name) = &self.custom {
+            return name == "negative-cache";
+        }
+        if self.key_growth.is_some() {
+            return true;
+        }
+        if let Some(preset) = self.workload {
+            return preset.writes();
+        }
+        match self.operation {
+            Operation::PutItem | Operation::UpdateItem | Operation::BatchWriteItem | Operation::TransactWriteItems => true,
+            Operation::ExecuteStatement | Operation::BatchExecuteStatement => self.statement.as_deref()
+                .is_some_and(|statement| !statement.trim_start().to_ascii_uppercase().starts_with("SELECT")),
+            _ => false,
+        }
+    }
+
+    /// --num-queries, or the count --min-samples-for asks for.
+    fn num_queries(&self) -> usize {
+        match self.min_samples_for {
+            Some(quantile) => min_samples_for(quantile, self.confidence),
+            None => self.num_queries,
+        }
+    }
+
+    /// The --stage list, or the single stage described by --num-queries/--qps.
+    fn stages(&self) -> Vec<Stage> {
+        if self.stage.is_empty() {
+            let parallelism = self.virtual_users.unwrap_or(self.parallelism);
+            return vec![Stage { limit: StageLimit::Queries(self.num_queries()), qps: self.qps, parallelism }];
+        }
+        self.stage.iter()
+            .map(|stage| Stage { parallelism: if stage.parallelism == 0 { self.parallelism } else { stage.parallelism }, ..*stage })
+            .collect()
+    }
+}
+
+/// Result of one measured request.
+struct Sample {
+    index: usize,
+    stage: usize,
+    /// Worker slot (or virtual user) that sent the request
+    worker: usize,
+    /// When the request started, relative to the start of the run
+    offset: Duration,
+    /// When it was scheduled to start: its tick of the request rate, or when
+    /// its virtual user became ready; earlier than `offset` when sending fell behind
+    intended: Duration,
+    latency: Duration,
+    result: Result<request::Response, request::RequestError>,
+    miss: bool,
+    operation: &'static str,
+    /// Keys the request addressed, when an error report was requested
+    keys: Option<Vec<String>>,
+    /// Key values of the request, to redact from its error under --redact-keys
+    key_values: Vec<String>,
+    /// Feature namespace the request read, in --chalk-profile runs
+    namespace: Option<String>,
+    /// Request parameters, when requests are captured
+    params: Option<serde_json::Value>,
+    /// Per-attempt response metadata, for captured requests and those sampled into the raw log
+    attempts: Option<Vec<metadata::AttemptMetadata>>,
+}
+
+/// Prints request count, errors and latency for consecutive windows of the run.
+fn print_intervals(timeline: &[(Duration, Duration, bool)], annotations: &[(Duration, String)], window: Duration,
+    started_at: SystemTime, offset: UtcOffset) {
+    let mut windows: Vec<(usize, Vec<Duration>)> = Vec::new();
+    for (start, latency, ok) in timeline {
+        let i = (start.as_secs_f64() / window.as_secs_f64()) as usize;
+        if windows.len() <= i {
+            windows.resize_with(i + 1, Default::default);
+        }
+        windows[i].0 += !ok as usize;
+        windows[i].1.push(*latency);
+    }
+    let notes_in = |i: usize| annotations.iter()
+        .filter(move |(at, _)| (at.as_secs_f64() / window.as_secs_f64()) as usize == i);
+    println!("\nInterval statistics ({}s windows, milliseconds):", window.as_secs_f64());
+    for (i, (errors, mut latencies)) in windows.into_iter().enumerate() {
+        let window_start = timestamp::both(started_at + window * i as u32, offset);
+        if latencies.is_empty() {
+            println!("{}: no requests", window_start);
+        } else {
+            latencies.sort();
+            println!("{}: {} requests, {} errors, p50 {:.3}, p99 {:.3}, max {:.3}", window_start, latencies.len(), errors,
+                quantile_ms(&latencies, 0.5), quantile_ms(&latencies, 0.99), quantile_ms(&latencies, 1.0));
+        }
+        for (at, note) in notes_in(i) {
+            println!("  ^ {}: {}", timestamp::both(started_at + *at, offset), note);
+        }
+    }
+}
+
+/// Compares post-run probe latency against the warmup baseline, per second
+/// of cooldown, and reports when (if ever) the p50 settled back within tolerance.
+fn print_cooldown(baseline: &mut [Duration], probes: &[(Duration, Duration, bool)], tolerance: f64, ended_at: SystemTime, offset: UtcOffset) {
+    println!("\nCooldown (milliseconds):");
+    let mut latencies: Vec<_> = probes.iter().filter(|(_, _, ok)| *ok).map(|(_, latency, _)| *latency).collect();
+    let errors = probes.len() - latencies.len();
+    if latencies.is_empty() {
+        println!("No successful cooldown probes ({} errors)", errors);
+        return;
+    }
+    latencies.sort();
+    println!("{} probes, {} errors, p50 {:.3}, p99 {:.3}, max {:.3}", probes.len(), errors,
+        quantile_ms(&latencies, 0.5), quantile_ms(&latencies, 0.99), quantile_ms(&latencies, 1.0));
+    if baseline.is_empty() {
+        println!("No successful warmup requests to compare against; run with --warmup-queries");
+        return;
+    }
+    baseline.sort();
+    let threshold = quantile_ms(baseline, 0.5) * tolerance;
+    println!("Baseline (warmup) p50 {:.3}, threshold {:.3}", quantile_ms(baseline, 0.5), threshold);
+
+    let windows = probes.last().map_or(0, |(offset, _, _)| offset.as_secs() as usize + 1);
+    let mut p50s = Vec::with_capacity(windows);
+    for i in 0..windows {
+        let mut window: Vec<_> = probes.iter()
+            .filter(|(offset, _, ok)| *ok && offset.as_secs() as usize == i)
+            .map(|(_, latency, _)| *latency)
+            .collect();
+        window.sort();
+        p50s.push((!window.is_empty()).then(|| quantile_ms(&window, 0.5)));
+    }
+    // settled once every later window with successful probes stays under the threshold
+    let settled = (0..windows).find(|&i| p50s[i..].iter().flatten().all(|p50| *p50 <= threshold));
+    match settled {
+        Some(i) => println!("Latency returned to baseline {}s after the run (at {})", i,
+            timestamp::both(ended_at + Duration::from_secs(i as u64), offset)),
+        None => println!("Latency did not return to baseline within the cooldown"),
+    }
+}
+
+fn print_split_latency(label: &str, durations: &mut [Duration]) {
+    if durations.is_empty() {
+        return;
+    }
+    durations.sort();
+    println!("{}: {} requests, p50 {}, p90 {}, p99 {}, max {:.3}", label, durations.len(),
+        percentile_ms(durations, 0.5), percentile_ms(durations, 0.9), percentile_ms(durations, 0.99), quantile_ms(durations, 1.0));
+}
 
-    args.partition_value.iter().map(|val| {
-        query_without_pkey.clone().expression_attribute_values(":pk", AttributeValue::S(val.clone()))
-    }).collect()
+/// Prints how many pages requests that follow every page took, the latency
+/// of single pages, and how far the first page alone understates the whole
+/// request.
+fn print_pages(page_counts: &[usize], pages: &mut [Duration], first_pages: &mut [Duration], requests: &mut [Duration]) {
+    let multi_page = page_counts.iter().filter(|&&count| count > 1).count();
+    println!("\nPagination: {:.2} pages per request on average, at most {}; {} of {} requests took more than one page",
+        page_counts.iter().sum::<usize>() as f64 / page_counts.len() as f64, page_counts.iter().max().unwrap(),
+        multi_page, page_counts.len());
+    println!("Latency (milliseconds):");
+    print_split_latency("  Per page", pages);
+    print_split_latency("  First page", first_pages);
+    print_split_latency("  Whole request", requests);
+}
+
+/// Prints the distribution of the capacity units measured requests consumed,
+/// and with --consumed-capacity indexes, how they split over the table and
+/// its indexes.
+fn print_capacity(units: &[(f64, f64)], index_units: &HashMap<String, (f64, f64)>) {
+    println!("\nConsumed capacity per request ({} measured requests):", units.len());
+    for (label, mut values) in [("RCU", units.iter().map(|u| u.0).collect::<Vec<_>>()), ("WCU", units.iter().map(|u| u.1).collect())] {
+        let total: f64 = values.iter().sum();
+        if total == 0.0 {
+            continue;
+        }
+        values.sort_by(f64::total_cmp);
+        let at = |q: f64| values[nearest_rank(values.len(), q)];
+        println!("{}: mean {:.2}, p50 {:.2}, p99 {:.2}, max {:.2}, {:.1} in total",
+            label, total / values.len() as f64, at(0.5), at(0.99), at(1.0), total);
+    }
+    if index_units.is_empty() {
+        return;
+    }
+    let (reads, writes) = index_units.values().fold((0.0, 0.0), |(r, w), (reads, writes)| (r + reads, w + writes));
+    let share = |units: f64, total: f64| if total > 0.0 { format!("{:.1}%", 100.0 * units / total) } else { "-".to_owned() };
+    let mut indexes: Vec<_> = index_units.iter().collect();
+    indexes.sort_by(|a, b| (b.1.0 + b.1.1).total_cmp(&(a.1.0 + a.1.1)));
+    println!("{:<32} {:>10} {:>8} {:>10} {:>8}", "Table or index", "RCU", "Share", "WCU", "Share");
+    for (name, (index_reads, index_writes)) in indexes {
+        println!("{:<32} {:>10.1} {:>8} {:>10.1} {:>8}", name, index_reads, share(*index_reads, reads), index_writes, share(*index_writes, writes));
+    }
+}
+
+/// Result size buckets for [`print_result_sizes`], by their smallest item count.
+const RESULT_SIZES: [(usize, &str); 4] = [(0, "0 items"), (1, "1-10 items"), (11, "11-100 items"), (101, "100+ items")];
+
+/// Prints latency by how many items responses returned, to tell the fast path
+/// of empty ranges apart from genuinely large reads.
+fn print_result_sizes(durations_by_items: &HashMap<usize, histogram::Latencies>, args: &histogram::HistogramArgs) {
+    let mut buckets = vec![args.latencies(); RESULT_SIZES.len()];
+    for (items, durations) in durations_by_items {
+        let bucket = RESULT_SIZES.iter().rposition(|(min, _)| items >= min).unwrap();
+        buckets[bucket].add(durations);
+    }
+    println!("\nLatency by result size (milliseconds):");
+    for ((_, label), durations) in RESULT_SIZES.iter().zip(buckets) {
+        durations.print(label);
+    }
+}
+
+/// Prints the latency of two halves of a run, then each of `quantiles` of
+/// `first` against `second`: the change `first`'s treatment makes, which
+/// `change` describes (e.g. "with hedging"). Callers print their own heading
+/// and any rows of their own around it.
+fn print_halves(first: (&str, &histogram::Latencies), second: (&str, &histogram::Latencies), quantiles: &[(&str, f64)], change: &str) {
+    let ((first_label, first), (second_label, second)) = (first, second);
+    first.print(first_label);
+    second.print(second_label);
+    if first.is_empty() || second.is_empty() {
+        return;
+    }
+    for (label, quantile) in quantiles {
+        let (a, b) = (first.quantile_ms(*quantile), second.quantile_ms(*quantile));
+        println!("{}: {} {} vs {} {}, {:+.3} ({:+.1}%) {}", label, first_label, first.percentile_ms(*quantile),
+            second_label, second.percentile_ms(*quantile), a - b, 100.0 * (a - b) / b.max(f64::EPSILON), change);
+    }
+}
+
+/// Percentiles the halves of a run are compared at.
+const HALVES_QUANTILES: [(&str, f64); 4] = [("p50", 0.5), ("p90", 0.9), ("p99", 0.99), ("p99.9", 0.999)];
+
+/// Prints the percentiles of the even- and odd-numbered requests and how far
+/// apart they are. The halves interleave, so they saw the same conditions and
+/// differ only by sampling noise, which is also about what separates two runs.
+fn print_variance([even, odd]: &[histogram::Latencies; 2]) {
+    println!("\nVariance check (even vs odd requests, milliseconds):");
+    if even.is_empty() || odd.is_empty() {
+        println!("Too few requests to split");
+        return;
+    }
+    print_halves(("Even", even), ("Odd", odd), &HALVES_QUANTILES, "between the halves");
+    println!("Differences between runs no larger than these are within the noise of the measurement");
+}
+
+/// Prints the percentiles of the strongly consistent (even-numbered) and
+/// eventually consistent (odd-numbered) requests of --compare-consistency.
+fn print_consistency([strong, eventual]: &[histogram::Latencies; 2]) {
+    println!("\nStrongly vs eventually consistent reads (milliseconds):");
+    print_halves(("Strongly consistent", strong), ("Eventually consistent", eventual), &HALVES_QUANTILES, "for strong consistency");
+}
+
+/// Prints the percentiles of the counting (even-numbered) and item-returning
+/// (odd-numbered) queries of --compare-select-count.
+fn print_select_count([count, items]: &[histogram::Latencies; 2]) {
+    println!("\nSelect=COUNT vs returning items (milliseconds):");
+    print_halves(("Select=COUNT", count), ("Returning items", items), &HALVES_QUANTILES, "for counting");
+}
+
+/// Prints latency per feature namespace, the namespaces with the worst p99 first.
+fn print_namespaces(durations_by_namespace: HashMap<String, Vec<Duration>>) {
+    println!("\nLatency by namespace (milliseconds):");
+    let mut by_namespace: Vec<_> = durations_by_namespace.into_iter().collect();
+    for (_, durations) in &mut by_namespace {
+        durations.sort();
+    }
+    by_namespace.sort_by(|(_, a), (_, b)| quantile_ms(b, 0.99).total_cmp(&quantile_ms(a, 0.99)));
+    for (namespace, mut durations) in by_namespace {
+        print_split_latency(&namespace, &mut durations);
+    }
+}
+
+/// Prints each worker's share of the requests and its latency. With a fair
+/// scheduler every worker sends about as many requests.
+fn print_workers(durations_by_worker: &[histogram::Latencies], key_shards: Option<(&std::ops::Range<u64>, usize)>) {
+    println!("\nLatency by worker (milliseconds):");
+    for (worker, durations) in durations_by_worker.iter().enumerate() {
+        let label = match key_shards {
+            Some((range, shards)) => {
+                let shard = keygen::shard_of(range, worker, shards);
+                format!("Worker {} (keys {}..{})", worker, shard.start, shard.end)
+            }
+            None => format!("Worker {}", worker),
+        };
+        if durations.is_empty() {
+            println!("{}: no requests", label);
+        }
+        durations.print(&label);
+    }
+    let counts: Vec<_> = durations_by_worker.iter().map(histogram::Latencies::len).collect();
+    let (min, max) = (counts.iter().min().copied().unwrap_or(0), counts.iter().max().copied().unwrap_or(0));
+    let mean = counts.iter().sum::<usize>() as f64 / counts.len().max(1) as f64;
+    println!("Requests per worker: min {}, mean {:.1}, max {}{}", min, mean, max,
+        if min > 0 { format!(" (max/min {:.2}x)", max as f64 / min as f64) } else { String::new() });
+}
+
+/// How late a request must start, after the time it was scheduled for, to count as held back.
+const LATE_START: Duration = Duration::from_millis(1);
+
+/// Prints service time (from when each request actually started) beside
+/// response time (from when it was scheduled to), which is what a caller
+/// issuing requests at the target rate would have seen: while the tool
+/// waits for a free worker or is stalled, requests start late, and service
+/// time alone leaves that wait out, coordinated omission.
+fn print_response_time(service: &histogram::Latencies, response: &histogram::Latencies, late_starts: usize) {
+    println!("\nService time vs response time (milliseconds, response time from each request's scheduled start):");
+    println!("{:<8} {:>24} {:>24}", "", "Service", "Response");
+    for (label, quantile) in [("p50", 0.5), ("p90", 0.9), ("p99", 0.99), ("p99.9", 0.999), ("max", 1.0)] {
+        println!("{:<8} {:>24} {:>24}", label, service.percentile_ms(quantile), response.percentile_ms(quantile));
+    }
+    println!("Requests started {} ms or more late: {} ({:.2}%)", LATE_START.as_millis(), late_starts,
+        100.0 * late_starts as f64 / service.len().max(1) as f64);
+}
+
+/// A percentile is only worth quoting when at least this many samples lie beyond it.
+const MIN_TAIL_SAMPLES: f64 = 10.0;
+
+fn is_low_confidence(samples: usize, quantile: f64) -> bool {
+    (samples as f64 * (1.0 - quantile)) < MIN_TAIL_SAMPLES
+}
+
+/// Fewest samples for which, with probability `confidence`, at least
+/// [`MIN_TAIL_SAMPLES`] land above the true `quantile`, i.e. the smallest n
+/// with P(Binomial(n, 1 - quantile) >= MIN_TAIL_SAMPLES) >= confidence.
+fn min_samples_for(quantile: f64, confidence: f64) -> usize {
+    let tail = 1.0 - quantile;
+    let k = MIN_TAIL_SAMPLES as usize;
+    let enough = |n: usize| {
+        // P(X < k), summing binomial terms from P(X = 0) = (1 - p)^n
+        let mut term = (n as f64 * (1.0 - tail).ln()).exp();
+        let mut below = 0.0;
+        for i in 0..k.min(n + 1) {
+            below += term;
+            term *= (n - i) as f64 / (i + 1) as f64 * tail / (1.0 - tail);
+        }
+        1.0 - below >= confidence
+    };
+    let mut high = (MIN_TAIL_SAMPLES / tail).ceil() as usize;
+    while !enough(high) {
+        high *= 2;
+    }
+    let mut low = high / 2;
+    while low + 1 < high {
+        let mid = (low + high) / 2;
+        if enough(mid) { high = mid } else { low = mid }
+    }
+    high
+}
+
+/// Formats a percentile in milliseconds, marking it when too few samples back it.
+fn percentile_ms(sorted_durations: &[Duration], quantile: f64) -> String {
+    let value = quantile_ms(sorted_durations, quantile);
+    if is_low_confidence(sorted_durations.len(), quantile) {
+        format!("{:.3} (low confidence)", value)
+    } else {
+        format!("{:.3}", value)
+    }
 }
 
 fn quantile_ms(sorted_durations: &[Duration], quantile: f64) -> f64 {
-    sorted_durations[((sorted_durations.len() as f64 * quantile).ceil() as usize).max(1) - 1].as_micros() as f64 / 1000.0
+    sorted_durations[nearest_rank(sorted_durations.len(), quantile)].as_micros() as f64 / 1000.0
 }
 
-#[tokio::main]
-async fn main() -> () {
-    let cli = Cli::parse();
+/// Index of the `quantile` of `len` sorted values, by the nearest-rank method.
+fn nearest_rank(len: usize, quantile: f64) -> usize {
+    ((len as f64 * quantile).ceil() as usize).max(1) - 1
+}
 
-    // Initialize AWS SDK
+/// Waits for every request task of a phase. A task that panicked lost its
+/// sample, so the panic is re-raised here instead of skewing the results.
+async fn join_all(tasks: &mut JoinSet<()>) {
+    while let Some(result) = tasks.join_next().await {
+        reap(result);
+    }
+}
+
+/// Request tasks still running, after collecting the ones that finished.
+fn in_flight(tasks: &mut JoinSet<()>) -> usize {
+    while let Some(result) = tasks.try_join_next() {
+        reap(result);
+    }
+    tasks.len()
+}
+
+fn reap(result: Result<(), JoinError>) {
+    if let Err(e) = result {
+        if e.is_panic() {
+            std::panic::resume_unwind(e.into_panic());
+        }
+        panic!("request task failed: {}", e);
+    }
+}
+
+/// Loads the SDK config from the environment with the settings given on the command line.
+async fn load_config(cli: &Cli) -> aws_config::SdkConfig {
     let mut config = aws_config::from_env()
         .region(aws_sdk_dynamodb::config::Region::new(cli.region.clone()));
 
@@ -123,143 +1224,1244 @@ async fn main() -> () {
         config = config.endpoint_url(endpoint_url)
     }
 
-    let config = config.load().await;
+    config.load().await
+}
 
-    let client = Client::new(&config);
+/// A client for `config` carrying the interceptors the measurements rely on.
+fn make_client(config: &aws_config::SdkConfig) -> Client {
+    Client::from_conf(
+        aws_sdk_dynamodb::config::Builder::from(config)
+            .interceptor(metadata::MetadataInterceptor)
+            .interceptor(credentials::SigningInterceptor)
+            .interceptor(overhead::EndpointInterceptor)
+            .build(),
+    )
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match schema::standalone(&args) {
+        Some(schema::StandaloneCommand::Schema(args)) => return schema::run(&args),
+        Some(schema::StandaloneCommand::DiffRuns(args)) => return rundiff::run(&args),
+        None => {}
+    }
+    let matches = Cli::command().get_matches_from(config::expand_args(args));
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    match &cli.command {
+        Commands::Schema(args) => return schema::run(args),
+        Commands::DiffRuns(args) => return rundiff::run(args),
+        _ => {}
+    }
+
+    // DynamoDB operations don't carry smithy's @requestCompression trait, so the
+    // SDK would accept these settings and never apply them
+    if cli.disable_request_compression || cli.request_min_compression_size.is_some() {
+        Cli::command().error(
+            ErrorKind::ArgumentConflict,
+            "--disable-request-compression and --request-min-compression-size have no effect: the SDK doesn't compress DynamoDB requests",
+        ).exit();
+    }
+
+    // an invocation times its own config loading, and gets the key schema from its parent
+    if let Commands::ColdStartInvocation(args) = &cli.command {
+        return coldstart::invoke(&cli, args).await;
+    }
+
+    // Initialize AWS SDK
+    let config = load_config(&cli).await;
+    let client = make_client(&config);
+
+    // each phase detects the keys when it starts, as an earlier one may create the table
+    if let Commands::Phases(args) = &cli.command {
+        return phases::run(&client, &config, args).await;
+    }
+
+    // the table doesn't exist yet, or isn't the one requests go to
+    if !matches!(cli.command, Commands::CreateTable(_) | Commands::Selftest(_)) {
+        let explicit = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        describe::detect_keys(&client, &mut cli, explicit).await;
+    }
+
+    run_command(&client, &config, &cli).await;
+}
+
+/// Runs the subcommand of `cli` with `client`, made from `config`, returning
+/// the summary of a single benchmark run.
+async fn run_command(client: &Client, config: &aws_config::SdkConfig, cli: &Cli) -> Option<Summary> {
+    match &cli.command {
+        Commands::Bench(args) if args.compare_credentials => {
+            credentials::compare(client.clone(), config.credentials_provider(), cli, args).await
+        }
+        Commands::Bench(args) if args.sweep_item_size.is_empty() && args.sweep_attribute_count.is_empty() && args.sweep_limit.is_empty() => {
+            return Some(bench(client.clone(), cli, args).await);
+        }
+        Commands::Bench(args) => sweep::run(client.clone(), cli, args).await,
+        Commands::Scan(args) => scan::run(client, cli, args).await,
+        Commands::AnalyzePartitions(args) => partitions::run(client, cli, args).await,
+        Commands::AnalyzeItems(args) => items::run(client, cli, args).await,
+        Commands::ShowMapping(args) => mapping::run(client, cli, args).await,
+        Commands::MappingDiff(args) => mapping::diff(client, cli, args).await,
+        Commands::Describe(args) => describe::run(client, cli, args).await,
+        Commands::CreateTable(args) => create_table::run(client, cli, args).await,
+        Commands::Seed(args) => seed::run(client, cli, args).await,
+        Commands::Cleanup(args) => seed::cleanup(client, cli, args).await,
+        Commands::Prewarm(args) => prewarm::run(client, cli, args).await,
+        Commands::ColdStart(args) => coldstart::run(cli, args).await,
+        Commands::ClientOverhead(args) => overhead::run(client, cli, args).await,
+        Commands::Schema(_) | Commands::DiffRuns(_) | Commands::ColdStartInvocation(_) | Commands::Phases(_) => unreachable!("handled before connecting"),
+        Commands::Selftest(args) => selftest::run(cli, args).await,
+    }
+    None
+}
+
+/// Headline results of one benchmark run.
+struct Summary {
+    requests: usize,
+    errors: usize,
+    /// Items returned (or written) by the successful requests
+    items: usize,
+    /// Capacity units consumed, warmup and cooldown included
+    read_units: f64,
+    write_units: f64,
+    /// p50, p90, p99 and p99.9 latency in milliseconds
+    percentiles: [f64; 4],
+    throughput: f64,
+}
+
+/// Runs the benchmark `args` describe and prints its report.
+async fn bench(client: Client, cli: &Cli, args: &BenchArgs) -> Summary {
+    request::set_capacity_detail(args.consumed_capacity);
+    rng::select(args.rng, args.rng_seed);
+    if cli.no_sort_key {
+        if !args.sort_value.is_empty() || args.sort_start.is_some() || args.sort_end.is_some() || args.sort_op.is_some() {
+            Cli::command().error(ErrorKind::ArgumentConflict, format!(
+                "{} has no sort key, so --sort-value, --sort-start, --sort-end and --sort-op don't apply", cli.table)).exit();
+        }
+        if args.synthesize_from.is_some() || args.custom.as_deref().is_some_and(|name| !matches!(name, "query-then-get" | "negative-cache")) {
+            Cli::command().error(ErrorKind::ArgumentConflict, format!(
+                "{} has no sort key, which --synthesize-from and --custom range workloads need", cli.table)).exit();
+        }
+    }
+    if args.workload.is_some() && args.sort_value.is_empty() && !cli.no_sort_key {
+        Cli::command().error(ErrorKind::MissingRequiredArgument, "--workload requires at least one --sort-value").exit();
+    }
+    if args.operation.needs_sort_values() && args.sort_value.is_empty() && !cli.no_sort_key {
+        Cli::command().error(
+            ErrorKind::MissingRequiredArgument,
+            format!("--operation {:?} requires at least one --sort-value", args.operation),
+        ).exit();
+    }
+    if args.batch_size == 0 || args.batch_size > args.operation.max_batch_size() {
+        Cli::command().error(
+            ErrorKind::ValueValidation,
+            format!("--batch-size must be between 1 and {} for {:?}", args.operation.max_batch_size(), args.operation),
+        ).exit();
+    }
+    if args.missing_keys >= args.batch_size {
+        Cli::command().error(ErrorKind::ValueValidation, "--missing-keys must be smaller than --batch-size").exit();
+    }
 
-    let args = match &cli.command {
-        Commands::Bench(args) => args,
-        Commands::ShowMapping { environment } => {
-            return show_mapping(&client, &cli, &environment).await
+    if !(0.0..=1.0).contains(&args.miss_ratio) {
+        Cli::command().error(ErrorKind::ValueValidation, "--miss-ratio must be between 0 and 1").exit();
+    }
+    if args.qps == 0 || args.warmup_qps == Some(0) || args.cooldown_qps == 0 {
+        Cli::command().error(ErrorKind::ValueValidation, "--qps, --warmup-qps and --cooldown-qps must be positive").exit();
+    }
+    if !(args.confidence > 0.0 && args.confidence < 1.0) {
+        Cli::command().error(ErrorKind::ValueValidation, "--confidence must be between 0 and 1").exit();
+    }
+    if args.report_interval.is_some_and(|window| positive_secs(window).is_none()) {
+        Cli::command().error(ErrorKind::ValueValidation, "--report-interval must be a positive number of seconds").exit();
+    }
+    if args.max_rcu_budget.is_some_and(|budget| budget <= 0.0) || args.max_wcu_budget.is_some_and(|budget| budget <= 0.0) {
+        Cli::command().error(ErrorKind::ValueValidation, "--max-rcu-budget and --max-wcu-budget must be positive").exit();
+    }
+    if args.auto_parallelism.is_some_and(|p99| positive_secs(p99 / 1000.0).is_none()) {
+        Cli::command().error(ErrorKind::ValueValidation, "--auto-parallelism must be a positive p99 in milliseconds").exit();
+    }
+    if args.virtual_users == Some(0) {
+        Cli::command().error(ErrorKind::ValueValidation, "--virtual-users must be positive").exit();
+    }
+    if args.retry_deadline_ms.is_some_and(|ms| positive_secs(ms / 1000.0).is_none()) || args.retry_attempts < 2 {
+        Cli::command().error(ErrorKind::ValueValidation, "--retry-deadline-ms must be positive and --retry-attempts at least 2").exit();
+    }
+    if positive_secs(args.throttle_pause_secs).is_none() {
+        Cli::command().error(ErrorKind::ValueValidation, "--throttle-pause-secs must be a positive number of seconds").exit();
+    }
+    if args.miss_ratio > 0.0 && args.workload.is_none() && !matches!(args.operation, Operation::Query | Operation::GetItem) {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--miss-ratio only applies to query and get-item").exit();
+    }
+
+    if cli.redact_keys && args.capture.is_some() {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--capture records key values in full and can't be combined with --redact-keys").exit();
+    }
+    if cli.redact_keys && args.export_load_test.is_some() {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--export-load-test records key values in full and can't be combined with --redact-keys").exit();
+    }
+    if (!args.chain_key.is_empty() || args.chain_table.is_some()) && args.custom.as_deref() != Some("query-then-get") {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--chain-key and --chain-table only apply to --custom query-then-get").exit();
+    }
+    let negative_cache = args.custom.as_deref() == Some("negative-cache");
+    if (args.custom.is_some() || args.script.is_some() || args.synthesize_from.is_some() || args.key_condition.is_some())
+        && ((args.miss_ratio > 0.0 && !negative_cache) || args.working_set < 1.0)
+    {
+        Cli::command().error(ErrorKind::ArgumentConflict,
+            "--miss-ratio and --working-set don't apply to --custom (but for negative-cache's --miss-ratio), --script, --synthesize-from or --key-condition workloads").exit();
+    }
+    if negative_cache && (args.miss_ratio == 0.0 || (args.sort_value.is_empty() && !cli.no_sort_key)) {
+        Cli::command().error(ErrorKind::MissingRequiredArgument,
+            "--custom negative-cache needs a --miss-ratio above 0 and at least one --sort-value").exit();
+    }
+    let reads_consistently = args.workload.is_some()
+        || matches!(args.operation, Operation::Query | Operation::GetItem | Operation::BatchGetItem | Operation::ExecuteStatement);
+    if (args.consistent_read || args.compare_consistency) && (args.custom.is_some() || !reads_consistently) {
+        Cli::command().error(ErrorKind::ArgumentConflict,
+            "--consistent-read and --compare-consistency apply to query, get-item, batch-get-item, execute-statement and --workload reads").exit();
+    }
+    if args.index_name.is_some() && args.operation != Operation::Query {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--index-name only applies to query").exit();
+    }
+    if args.limit.is_some() && args.workload.is_none() && args.operation != Operation::Query {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--limit only applies to query").exit();
+    }
+    if !args.projection.is_empty() && args.workload.is_none() && args.operation != Operation::Query {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--projection only applies to query").exit();
+    }
+    if args.descending && args.workload.is_none() && args.operation != Operation::Query {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--descending only applies to query").exit();
+    }
+    args.split_watch.validate(args.writes());
+    if args.paginate && args.workload.is_none() && args.operation != Operation::Query {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--paginate only applies to query").exit();
+    }
+    if args.select_count && args.workload.is_none() && args.operation != Operation::Query {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--select-count only applies to query").exit();
+    }
+    if args.compare_select_count && (args.workload.is_some() || args.operation != Operation::Query) {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--compare-select-count only applies to query").exit();
+    }
+    if args.key_condition.is_some() && args.operation != Operation::Query {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--key-condition only applies to query").exit();
+    }
+    if args.synthesize_from.is_some() && (args.partition_value.is_empty() || args.synth_buckets == 0) {
+        Cli::command().error(ErrorKind::ValueValidation, "--synthesize-from needs entity ids (-P) and at least one --synth-buckets").exit();
+    }
+    let generated = args.key_range.as_ref().map(|range| format!("{}{}", args.key_prefix, range.start));
+    let sort_values = args.sort_value.iter().chain(&args.sort_start).chain(&args.sort_end)
+        .filter(|value| !matches!(timestamp::parse_relative(value), Ok(Some(_))));
+    let key_values = args.partition_value.iter().chain(&generated).map(|value| (cli.partition_type, value))
+        .chain(sort_values.map(|value| (cli.sort_type, value)));
+    for (key_type, value) in key_values {
+        if let Err(e) = key_type.parse(value) {
+            Cli::command().error(ErrorKind::ValueValidation, e).exit();
+        }
+    }
+    if (cli.partition_type == request::KeyType::B || cli.sort_type == request::KeyType::B)
+        && matches!(args.operation, Operation::ExecuteStatement | Operation::BatchExecuteStatement)
+    {
+        Cli::command().error(ErrorKind::ArgumentConflict, "PartiQL statements can't be generated for B keys").exit();
+    }
+    if args.synthesize_from.is_some() && cli.partition_type != request::KeyType::S {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--synthesize-from needs an S partition key, as mapped partitions are prefixed").exit();
+    }
+    let times = args.synthesize_from.is_some()
+        || [&args.sort_start, &args.sort_end].into_iter().flatten().any(|bound| matches!(timestamp::parse_relative(bound), Ok(Some(_))));
+    if times && cli.sort_type != request::KeyType::S && (args.time_format == timestamp::TimeFormat::Rfc3339 || cli.sort_type == request::KeyType::B) {
+        Cli::command().error(ErrorKind::ArgumentConflict, "sort key times of N keys need an epoch --time-format, and B keys can't hold them").exit();
+    }
+    if args.sort_window.is_some() && cli.sort_type == request::KeyType::B {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--sort-window needs S or N sort keys").exit();
+    }
+    if let Some(op) = args.sort_op {
+        let operand = match op {
+            workload::SortOp::Lt => args.sort_end.is_some() && args.sort_start.is_none(),
+            _ => args.sort_start.is_some() && args.sort_end.is_none(),
+        };
+        if !operand {
+            Cli::command().error(ErrorKind::ArgumentConflict,
+                "--sort-op lt takes --sort-end only; begins-with, gt and eq take --sort-start only").exit();
+        }
+    }
+    let relative = [&args.sort_start, &args.sort_end].into_iter().flatten()
+        .map(|bound| timestamp::parse_relative(bound).unwrap_or_else(|e| Cli::command().error(ErrorKind::ValueValidation, e).exit()))
+        .any(|offset| offset.is_some());
+    if relative && (args.custom.is_some() || args.script.is_some() || (args.workload.is_none() && args.operation != Operation::Query)) {
+        Cli::command().error(ErrorKind::ArgumentConflict, "relative --sort-start/--sort-end times only apply to query requests").exit();
+    }
+    if let Some(width) = args.sort_window {
+        let bounds = args.sort_start.as_deref().and_then(|start| start.parse::<u64>().ok())
+            .zip(args.sort_end.as_deref().and_then(|end| end.parse::<u64>().ok()));
+        match bounds {
+            Some((start, end)) if start + width <= end => {}
+            Some(_) => Cli::command().error(ErrorKind::ValueValidation, "--sort-window is wider than --sort-start..--sort-end").exit(),
+            None => Cli::command().error(ErrorKind::ValueValidation, "--sort-window needs numeric --sort-start and --sort-end").exit(),
+        }
+    }
+    if !args.partition_weight.is_empty() {
+        if args.partition_weight.len() != args.partition_value.len() {
+            Cli::command().error(ErrorKind::WrongNumberOfValues, "give one --partition-weight per --partition-value").exit();
+        }
+        if args.partition_weight.iter().any(|weight| weight.is_nan() || *weight <= 0.0) {
+            Cli::command().error(ErrorKind::ValueValidation, "--partition-weight values must be positive").exit();
+        }
+        if args.working_set < 1.0 || args.custom.is_some() || args.script.is_some() {
+            Cli::command().error(ErrorKind::ArgumentConflict, "--partition-weight doesn't apply with --working-set, --custom or --script").exit();
+        }
+    }
+    if args.key_range.is_some() && (args.miss_ratio > 0.0 || args.working_set < 1.0) {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--miss-ratio and --working-set don't apply to generated keys").exit();
+    }
+    if let Some(range) = args.key_range.as_ref().filter(|_| args.worker_key_affinity) {
+        let workers = args.stages().iter().map(|stage| stage.parallelism).max().unwrap_or(1);
+        if range.end - range.start < workers as u64 {
+            Cli::command().error(ErrorKind::ValueValidation,
+                format!("--worker-key-affinity needs at least one key per worker, but --key-range has fewer than {}", workers)).exit();
+        }
+    }
+    if args.key_growth.is_some() && args.sort_value.is_empty() && !cli.no_sort_key {
+        Cli::command().error(ErrorKind::MissingRequiredArgument, "--key-growth requires a --sort-value to write appended items at").exit();
+    }
+    if args.key_growth.is_some() && !matches!(args.operation, Operation::Query | Operation::GetItem | Operation::BatchGetItem) {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--key-growth applies to query, get-item and batch-get-item reads").exit();
+    }
+    if !(args.zipf_theta > 0.0 && args.zipf_theta < 1.0) {
+        Cli::command().error(ErrorKind::ValueValidation, "--zipf-theta must be between 0 and 1").exit();
+    }
+    if args.rng_seed != 0 && args.rng != rng::Backend::Xoshiro {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--rng-seed only applies to --rng xoshiro").exit();
+    }
+
+    if args.writes() && !args.allow_protected_table {
+        match guard::protected_by(&client, &cli.table, &args.protect_tag).await {
+            Ok(tags) if tags.is_empty() => {}
+            Ok(tags) => Cli::command().error(ErrorKind::ArgumentConflict, format!(
+                "{} is tagged {}; refusing to run a writing workload against it without --allow-protected-table",
+                cli.table, tags.join(", "))).exit(),
+            Err(e) => Cli::command().error(ErrorKind::Io, format!(
+                "cannot check the tags of {} before writing to it ({}); pass --allow-protected-table to skip the check",
+                cli.table, e)).exit(),
+        }
+    }
+
+    let redact = redact::Redactor::new(cli.redact_keys);
+    let growth = args.key_growth.map(keygen::Growth::new);
+    let workload: Box<dyn Workload> = match (&args.custom, &args.script) {
+        _ if args.synthesize_from.is_some() => {
+            let environment = args.synthesize_from.as_deref().unwrap();
+            let mappings = mapping::load(&client, cli, environment, &args.mapping_cache).await;
+            let synthesized = synthesis::Synthesized::new(mappings, &client, cli, args)
+                .unwrap_or_else(|e| Cli::command().error(ErrorKind::ValueValidation, e).exit());
+            println!("Synthesizing {} traffic from {} FQN mappings, {} buckets per request, over {} entities",
+                environment, synthesized.mappings(), args.synth_buckets, args.partition_value.len());
+            Box::new(synthesized)
+        }
+        _ if args.key_condition.is_some() => {
+            let expression = args.key_condition.as_deref().unwrap();
+            let templated = condition::Templated::new(expression, &client, cli, args)
+                .unwrap_or_else(|e| Cli::command().error(ErrorKind::ValueValidation, e).exit());
+            println!("Key condition: {}", expression);
+            Box::new(templated)
+        }
+        (Some(name), _) => custom::build(name, &client, cli, args),
+        (None, Some(path)) => {
+            let scripted = script::Scripted::load(path, &client, cli, args);
+            println!("Script {} computes {} per request", path, scripted.hooks().join(", "));
+            Box::new(scripted)
+        }
+        (None, None) => match &args.key_range {
+            Some(range) => {
+                println!("Partition values: {}{{{}..{}}}, {:?}", redact.value(&args.key_prefix), range.start, range.end, args.key_distribution);
+                let keys = keygen::KeyGenerator::new(&args.key_prefix, range.clone(), args.key_distribution, args.zipf_theta);
+                if let Some(rate) = args.key_growth {
+                    println!("Keyspace growth: {}% per second", rate * 100.0);
+                }
+                if args.worker_key_affinity {
+                    let workers = args.stages().iter().map(|stage| stage.parallelism).max().unwrap_or(1);
+                    println!("Key affinity: {} shards of about {} keys, one per worker", workers, (range.end - range.start) / workers as u64);
+                }
+                Box::new(keygen::Generated::new(keys, growth.as_ref(), &client, cli, args))
+            }
+            None => Box::new(Mix::build(&client, cli, args)),
         },
     };
 
-    let queries = make_query(&client, &cli, &args);
+    let benchmark = match (args.workload, &args.custom) {
+        _ if args.synthesize_from.is_some() => "Synthesized".to_owned(),
+        (Some(preset), _) => format!("{:?}", preset),
+        (None, Some(name)) => name.clone(),
+        (None, None) => format!("{:?}", args.operation),
+    };
+    match args.stage.as_slice() {
+        [] if args.virtual_users.is_some() => println!("Starting {} benchmark with {} queries from {} virtual users with {}ms think time",
+            benchmark, args.num_queries(), args.stages()[0].parallelism, args.think_time_ms),
+        [] => println!("Starting {} benchmark with {} queries at {} QPS with parallelism of {}", 
+            benchmark, args.num_queries(), args.qps, args.parallelism),
+        [_] => println!("Starting {} benchmark with {}", benchmark, args.stages()[0]),
+        stages => println!("Starting {} benchmark with {} stages", benchmark, stages.len()),
+    }
+    if let Some(path) = &args.config {
+        println!("Settings from {}", path);
+    }
+    if let Some(profile) = &args.chalk_profile {
+        println!("Chalk profile: {}", profile);
+    }
+    if let Some(quantile) = args.min_samples_for {
+        println!("{} queries put at least {} samples above p{} with {}% confidence", args.num_queries(),
+            MIN_TAIL_SAMPLES, quantile * 100.0, args.confidence * 100.0);
+    }
+    println!("Table: {}, Partition Keys: {} = {}", 
+        cli.table, cli.partition_key, redact.debug(&args.partition_value));
+    if !args.partition_weight.is_empty() {
+        println!("Partition weights: {:?}", args.partition_weight);
+    }
+    let sort_key = if cli.no_sort_key { "none" } else { cli.sort_key.as_str() };
+    match (args.workload, args.operation) {
+        (Some(preset), _) => {
+            println!("Sort Key: {}, Values: {}, Range: {} to {}", sort_key, 
+                redact.debug(&args.sort_value), redact.debug(&args.sort_start), redact.debug(&args.sort_end));
+            println!("Workload mix: {}", preset.describe());
+        }
+        (None, Operation::Query) => match args.sort_op {
+            Some(op) => println!("Sort Key: {}, Condition: {:?} {}", sort_key, op,
+                redact.debug(args.sort_start.as_ref().or(args.sort_end.as_ref()).unwrap())),
+            None => println!("Sort Key: {}, Range: {} to {}",
+                sort_key, redact.debug(&args.sort_start), redact.debug(&args.sort_end)),
+        },
+        (None, Operation::GetItem) => println!("Sort Key: {}, Values: {}", 
+            sort_key, redact.debug(&args.sort_value)),
+        (None, Operation::BatchGetItem) => println!("Sort Key: {}, Values: {}, Batch size: {} ({} missing)", 
+            sort_key, redact.debug(&args.sort_value), args.batch_size, args.missing_keys),
+        (None, Operation::BatchWriteItem | Operation::TransactWriteItems | Operation::TransactGetItems) => println!("Sort Key: {}, Values: {}, Batch size: {}", 
+            sort_key, redact.debug(&args.sort_value), args.batch_size),
+        (None, Operation::UpdateItem) => println!("Sort Key: {}, Values: {}, Update: {} with {:?} {:?}", 
+            sort_key, redact.debug(&args.sort_value), args.update_expression, args.expression_name, args.expression_value),
+        (None, Operation::ExecuteStatement | Operation::BatchExecuteStatement) => println!("Statement: {}", 
+            {
+                // with --key-range, the statement of the range's first key
+                let first = args.key_range.as_ref()
+                    .map(|range| BenchArgs { partition_value: vec![format!("{}{}", args.key_prefix, range.start)], ..args.clone() });
+                let statement = &workload::make_statements(cli, first.as_ref().unwrap_or(args))[0];
+                redact.text(statement, &request::literals(statement))
+            }),
+        (None, Operation::Error) => println!("Error kind: {:?}, expecting {}", 
+            args.error_kind, args.error_kind.expected_code()),
+        (None, Operation::PutItem) => {
+            println!("Sort Key: {}, Values: {}, Item size: {} bytes", 
+                sort_key, redact.debug(&args.sort_value), args.item_size);
+        }
+    }
+    if let Some(index) = &args.index_name {
+        println!("Index: {}", index);
+    }
+    if args.consistent_read {
+        println!("Reads: strongly consistent");
+    } else if args.compare_consistency {
+        println!("Reads: strongly consistent for even-numbered requests, eventually consistent for odd-numbered ones");
+    }
+    if let Some(limit) = args.limit {
+        println!("Query limit: {} items", limit);
+    }
+    if !args.projection.is_empty() {
+        println!("Projection: {}", args.projection.join(", "));
+    }
+    println!("Random numbers: {}", args.rng.describe(args.rng_seed));
+    println!("Latency histograms: {}", args.histogram.describe());
+    if args.consumed_capacity == request::CapacityDetail::Indexes {
+        println!("Consumed capacity: per table and index");
+    }
+    if args.descending {
+        println!("Sort order: descending");
+    }
+    if args.paginate {
+        println!("Pagination: every page of each query");
+    }
+    if args.select_count {
+        println!("Select: COUNT");
+    } else if args.compare_select_count {
+        println!("Select: COUNT for even-numbered requests, the items for odd-numbered ones");
+    }
+    if args.working_set < 1.0 {
+        println!("Working set: {} of {} requests ({:.1}%)", workload.len(), workload.keyspace(), args.working_set * 100.0);
+    }
+    if args.miss_ratio > 0.0 {
+        println!("Miss ratio: {}", args.miss_ratio);
+    }
+    if args.max_rcu_budget.is_some() || args.max_wcu_budget.is_some() {
+        println!("Capacity budget: {} RCU, {} WCU",
+            args.max_rcu_budget.map_or("unlimited".to_owned(), |b| b.to_string()),
+            args.max_wcu_budget.map_or("unlimited".to_owned(), |b| b.to_string()));
+    }
+    if let Some(rate) = args.max_throttle_rate {
+        println!("Pausing {}s whenever more than {}% of requests in a second are throttled", args.throttle_pause_secs, rate * 100.0);
+    }
+    if redact.is_enabled() {
+        println!("Key values are redacted as <sha256 prefix>");
+    }
+    println!("HTTP client: {}", cli.http_client.name());
+    println!("SDK client: config loaded and client built once, shared by all requests; endpoints resolved per request");
+    let table = match describe::snapshot(&client, &cli.table).await {
+        Ok(table) => {
+            describe::print(&table);
+            Some(table)
+        }
+        Err(e) => {
+            println!("Cannot describe {}, results won't include its snapshot: {}", cli.table, e);
+            None
+        }
+    };
+    let floor = if args.calibrate_floor { Some(calibrate::measure(&client, cli, args).await) } else { None };
+    if let Some(floor) = &floor {
+        println!("Measurement floor: {}", floor.describe());
+    }
+    let annotations = args.annotation_file.as_ref().map(|path| {
+        let annotations = annotate::Annotations::listen(path.clone()).unwrap_or_else(|e| Cli::command()
+            .error(ErrorKind::Io, format!("cannot listen for SIGUSR2: {}", e)).exit());
+        println!("Send SIGUSR2 to process {} to annotate the timeline with the contents of {}", std::process::id(), path);
+        annotations
+    });
 
-    println!("Starting benchmark with {} queries at {} QPS with parallelism of {}", 
-        args.num_queries, args.qps, args.parallelism);
-    println!("Table: {}, Partition Keys: {} = {:?}", 
-        cli.table, cli.partition_key, args.partition_value);
-    println!("Sort Key: {}, Range: {:?} to {:?}", 
-        cli.sort_key, args.sort_start, args.sort_end);
+    let namespaces = match &args.chalk_profile {
+        Some(_) => Some(mapping::Namespaces::load(&client, cli, args.chalk_environment.as_deref(), &args.mapping_cache).await),
+        None => None,
+    };
 
-    let (response_sender, responses) = std::sync::mpsc::channel();
+    let hedger = args.hedge_after.map(|quantile| Arc::new(hedge::Hedger::new(quantile)));
+    let deadline = args.retry_deadline_ms.map(|ms| {
+        let deadline = positive_secs(ms / 1000.0).expect("--retry-deadline-ms is validated");
+        Arc::new(deadline::Deadline::new(deadline, args.retry_attempts))
+    });
+    // with --worker-key-affinity, worker w of the most there are at once owns key shard w
+    let shards = args.stages().iter().map(|stage| stage.parallelism).max().unwrap_or(1);
+    // the `i`th request, sent by `worker` in the measured run, read with strong
+    // consistency under --consistent-read and for the even-numbered half of
+    // --compare-consistency, counting under --select-count and for the
+    // even-numbered half of --compare-select-count, and following every page
+    // under --paginate
+    let request_at = |i: usize, worker: Option<usize>| {
+        let (mut request, miss) = match worker.filter(|_| args.worker_key_affinity) {
+            Some(worker) => workload.request_on_shard(i, worker, shards),
+            None => workload.request(i),
+        };
+        if args.consistent_read || (args.compare_consistency && i.is_multiple_of(2)) {
+            request = request.consistent();
+        }
+        if args.select_count || (args.compare_select_count && i.is_multiple_of(2)) {
+            request = request.count_only();
+        }
+        (if args.paginate { request.paginated() } else { request }, miss)
+    };
+    // the key values to redact from a request's error, under --redact-keys
+    let key_values_of = |request: &request::Request| if redact.is_enabled() {
+        request.key_values(&[&cli.partition_key, &cli.sort_key])
+    } else {
+        Vec::new()
+    };
+    let pair = pair::Pair::connect(&args.pair).await;
+    let (warmup_sender, warmup_results) = std::sync::mpsc::channel();
     let semaphore = Arc::new(Semaphore::new(args.parallelism));
-    println!("Starting {} warmup queries", args.warmup_queries);
+    let mut tasks = JoinSet::new();
+    let budget = Arc::new(budget::Budget::new(args.max_rcu_budget, args.max_wcu_budget));
+    let warmup_qps = args.warmup_qps.unwrap_or(args.qps);
+    println!("Starting {} warmup queries with the measured request mix at {} QPS", args.warmup_queries, warmup_qps);
     let start = time::Instant::now();
-    let mut interval = time::interval_at(start, Duration::from_secs_f64(1.0 / args.qps as f64));
+    let mut interval = time::interval_at(start, Duration::from_secs_f64(1.0 / warmup_qps as f64));
     for i in 0..args.warmup_queries {
         interval.tick().await;
+        if budget.is_exhausted() {
+            break;
+        }
         let permit = semaphore.clone().acquire_owned().await.unwrap();
-        let query = queries[i % queries.len()].clone();
-        let response_sender = response_sender.clone();
-        tokio::spawn(async move {
+        let (query, _) = request_at(i, None);
+        let key_values = key_values_of(&query);
+        let warmup_sender = warmup_sender.clone();
+        let budget = budget.clone();
+        tasks.spawn(async move {
+            let request_start = Instant::now();
             let resp = query.send().await;
+            let latency = request_start.elapsed();
             drop(permit);
-            if let Err(e) = resp {
-                response_sender.send(Err(e)).unwrap();
-            }
+            budget.record(&resp);
+            warmup_sender.send((resp.map(|_| latency), key_values)).unwrap();
         });
     }
 
-    let _ = semaphore.acquire_many(args.parallelism as u32).await.unwrap();
+    join_all(&mut tasks).await;
     println!("Completed warmups in {}s", start.elapsed().as_secs_f64());
+    if budget.is_exhausted() {
+        let (read_units, write_units) = budget.consumed();
+        println!("Warmup consumed {:.1} RCU and {:.1} WCU, nothing left to measure with", read_units, write_units);
+        std::process::exit(1);
+    }
 
-    let (sender, durations) = std::sync::mpsc::sync_channel(args.num_queries);
+    drop(warmup_sender);
+    let (sender, samples) = std::sync::mpsc::channel();
+    let session = match pair {
+        Some(pair) => {
+            let window = args.report_interval.and_then(positive_secs).unwrap_or(Duration::from_secs(1));
+            Some(pair.start(&loadtest::request_name(args), window).await)
+        }
+        None => None,
+    };
 
     let start = time::Instant::now();
-    interval.reset_at(start);
-
-    for i in 0..args.num_queries {
-        interval.tick().await;
-        let permit = semaphore.clone().acquire_owned().await.unwrap();
-        let query = queries[i % queries.len()].clone();
-        let sender = sender.clone();
-        let response_sender = response_sender.clone();
-        tokio::spawn(async move {
-            let start = Instant::now();
-            let resp = query.send().await;
-            sender.try_send(start.elapsed()).unwrap();
-            drop(permit);
-            response_sender.send(resp.map(|resp| resp.count())).unwrap();
-        });
+    let started_at = SystemTime::now();
+    println!("Run started at {}", timestamp::both(started_at, cli.timezone));
+    let split_watch = splits::Watch::start(&client, cli, &args.split_watch, start, started_at);
+    // the keyspace as the measured run starts, warmup appends included
+    let initial_keys = args.key_range.as_ref().map_or(0, |range| range.end - range.start)
+        + growth.as_ref().map_or(0, keygen::Growth::appended);
+    let stages = args.stages();
+    let mut stage_starts = Vec::with_capacity(stages.len());
+    let mut peak_in_flight = 0;
+    let mut backoff = args.max_throttle_rate
+        .map(|rate| throttle::Backoff::new(rate, positive_secs(args.throttle_pause_secs).expect("--throttle-pause-secs is validated")));
+    // closed loop: a virtual user is ready for its next request once its
+    // previous response and think time are over
+    let (user_ready, mut ready_users) = tokio::sync::mpsc::unbounded_channel();
+    for user in 0..args.virtual_users.unwrap_or(0) {
+        user_ready.send((user, time::Instant::now())).unwrap();
+    }
+    let think_time = Duration::from_millis(args.think_time_ms);
+    let mut tuner = args.auto_parallelism
+        .map(|p99| tuner::Tuner::new(positive_secs(p99 / 1000.0).expect("--auto-parallelism is validated"), args.parallelism));
+    let mut i = 0;
+    'stages: for (stage_index, stage) in stages.iter().enumerate() {
+        if args.stage.len() > 1 {
+            println!("Stage {}: {}", stage_index + 1, stage);
+        }
+        let semaphore = Arc::new(Semaphore::new(stage.parallelism));
+        // open loop: each permit is held by one of these workers, idle ones queued in order
+        let idle_workers = Arc::new(Mutex::new((0..stage.parallelism).collect::<VecDeque<_>>()));
+        let mut cap = stage.parallelism;
+        let stage_start = time::Instant::now();
+        stage_starts.push(stage_start - start);
+        let mut interval = time::interval_at(stage_start, Duration::from_secs_f64(1.0 / stage.qps as f64));
+        let mut sent = 0;
+        loop {
+            let mut user = None;
+            let scheduled = if args.virtual_users.is_some() {
+                let (ready_user, ready_at) = ready_users.recv().await.unwrap();
+                time::sleep_until(ready_at).await;
+                user = Some(ready_user);
+                ready_at
+            } else {
+                // missed ticks fire at once, each still returning the instant it was due
+                interval.tick().await
+            };
+            if let Some(backoff) = &mut backoff {
+                if backoff.pause_if_throttled(start.elapsed()).await {
+                    interval.reset();
+                }
+            }
+            if let Some(parallelism) = tuner.as_mut().and_then(tuner::Tuner::adjust) {
+                println!("Parallelism {} -> {}", cap, parallelism);
+                tuner::resize(&semaphore, cap, parallelism);
+                idle_workers.lock().unwrap().extend(cap..parallelism);
+                cap = parallelism;
+            }
+            if !stage.continues(sent, stage_start.elapsed()) {
+                break;
+            }
+            if budget.is_exhausted() {
+                break 'stages;
+            }
+            let permit = semaphore.clone().acquire_owned().await.unwrap();
+            let worker = user.unwrap_or_else(|| idle_workers.lock().unwrap().pop_front().expect("a permit leaves a worker idle"));
+            let (query, miss) = request_at(i, Some(worker));
+            let log_metadata = args.capture.is_some() || (args.raw_log.is_some() && is_picked(i, args.raw_log_sample));
+            let operation = query.name();
+            let params = args.capture.is_some().then(|| query.params());
+            let keys = args.error_report.is_some().then(|| query.keys(&[&cli.partition_key, &cli.sort_key]));
+            let key_values = key_values_of(&query);
+            // a request's namespace falls back to its sort key value
+            let namespace = namespaces.as_ref().map(|namespaces| redact.text(&namespaces.of(&query, cli), &key_values));
+            let sender = sender.clone();
+            let counters = backoff.as_ref().map(throttle::Backoff::counters);
+            let budget = budget.clone();
+            let user_ready = args.virtual_users.is_some().then(|| user_ready.clone());
+            let idle_workers = idle_workers.clone();
+            let latencies = tuner.as_ref().map(tuner::Tuner::latencies);
+            let hedger = hedger.clone();
+            let deadline = deadline.clone();
+            let time_signing = args.compare_credentials;
+            let recorder = session.as_ref().map(pair::Session::recorder);
+            let index = i;
+            let intended = scheduled.saturating_duration_since(start);
+            peak_in_flight = peak_in_flight.max(in_flight(&mut tasks) + 1);
+            tasks.spawn(async move {
+                let offset = start.elapsed();
+                let request_start = Instant::now();
+                let send = async {
+                    if log_metadata {
+                        let (result, attempts) = metadata::capture(query.send()).await;
+                        (result, Some(attempts))
+                    } else if let Some(hedger) = hedger {
+                        (hedger.send(query, index).await, None)
+                    } else if let Some(deadline) = deadline {
+                        (deadline.send(query, index).await, None)
+                    } else {
+                        (query.send().await, None)
+                    }
+                };
+                let (result, attempts) = if time_signing { credentials::timed(send).await } else { send.await };
+                let latency = request_start.elapsed();
+                if user_ready.is_none() {
+                    idle_workers.lock().unwrap().push_back(worker);
+                }
+                if let Some(latencies) = latencies {
+                    latencies.lock().unwrap().push(latency);
+                }
+                if let Some(recorder) = recorder {
+                    recorder.record(latency, result.is_ok());
+                }
+                drop(permit);
+                if let Some(counters) = counters {
+                    counters.record(&result);
+                }
+                budget.record(&result);
+                sender.send(Sample { index, stage: stage_index, worker, offset, intended, latency, result, miss, operation, keys, key_values, namespace, params, attempts }).unwrap();
+                if let Some(user_ready) = user_ready {
+                    let _ = user_ready.send((worker, time::Instant::now() + think_time));
+                }
+            });
+            i += 1;
+            sent += 1;
+        }
+        // the next stage starts right away, overlapping this one's stragglers
     }
     drop(sender);
-    drop(response_sender);
 
-    // waits for all tasks to complete
-    let _ = semaphore.acquire_many(args.parallelism as u32).await.unwrap();
+    join_all(&mut tasks).await;
     let total_duration = start.elapsed();
+    let ended_at = SystemTime::now();
+    let split_watch = match split_watch {
+        Some(watch) => Some(watch.finish().await),
+        None => None,
+    };
+
+    let mut cooldown = Vec::new();
+    if let Some(secs) = args.cooldown_secs {
+        println!("Cooling down for {}s at {} QPS", secs, args.cooldown_qps);
+        let (cooldown_sender, cooldown_results) = std::sync::mpsc::channel();
+        let cooldown_start = time::Instant::now();
+        let mut interval = time::interval_at(cooldown_start, Duration::from_secs_f64(1.0 / args.cooldown_qps as f64));
+        while cooldown_start.elapsed().as_secs_f64() < secs {
+            interval.tick().await;
+            if budget.is_exhausted() {
+                break;
+            }
+            let permit = semaphore.clone().acquire_owned().await.unwrap();
+            let (query, _) = request_at(i, None);
+            let cooldown_sender = cooldown_sender.clone();
+            let budget = budget.clone();
+            tasks.spawn(async move {
+                let offset = cooldown_start.elapsed();
+                let request_start = Instant::now();
+                let resp = query.send().await;
+                let latency = request_start.elapsed();
+                drop(permit);
+                budget.record(&resp);
+                cooldown_sender.send((offset, latency, resp.is_ok())).unwrap();
+            });
+            i += 1;
+        }
+        drop(cooldown_sender);
+        join_all(&mut tasks).await;
+        cooldown.extend(cooldown_results);
+        cooldown.sort_by_key(|(offset, _, _)| *offset);
+    }
 
     let mut response_stats = HashMap::new();
-    for count_or_error in responses {
-        if let Err(e) = &count_or_error {
-            println!("{:?}", e);
+    let mut warmup_durations = Vec::new();
+    for (resp, key_values) in warmup_results {
+        match resp {
+            Ok(latency) => warmup_durations.push(latency),
+            Err(e) => {
+                println!("{}", redact.text(&e.to_string(), &key_values));
+                *response_stats.entry(Err(e.code)).or_insert(0) += 1;
+            }
         }
-        *response_stats.entry(count_or_error.ok()).or_insert(0) += 1;
     }
 
-    println!("\nResponse stats:");
-    for (num_items, num_responses) in response_stats {
-        let to_str = num_items.map(|x| format!("{} items", x));
-        println!("{}: {} responses", to_str.as_deref().unwrap_or("Error"), num_responses);
+    let latencies = || args.histogram.latencies();
+    let mut durations = latencies();
+    // latency from when each request was due to start, as its caller would have waited
+    let (mut response_times, mut late_starts) = (latencies(), 0);
+    let (mut hit_durations, mut miss_durations) = (latencies(), latencies());
+    let mut per_item_durations = latencies();
+    let (mut page_counts, mut page_durations) = (Vec::new(), Vec::new());
+    let (mut capacity_units, mut index_units) = (Vec::new(), HashMap::<String, (f64, f64)>::new());
+    let (mut first_page_durations, mut paginated_durations) = (Vec::new(), Vec::new());
+    let (mut committed_durations, mut cancelled_durations) = (latencies(), Vec::new());
+    let mut cancellation_reasons: HashMap<String, usize> = HashMap::new();
+    let mut durations_by_items: HashMap<usize, histogram::Latencies> = HashMap::new();
+    let (mut unprocessed, mut unprocessed_retries, mut partially_unprocessed) = (0, 0, 0);
+    let (mut items_total, mut response_bytes, mut failed_statements) = (0, 0, 0);
+    let mut raw_log = args.raw_log.as_ref().map(|path| {
+        BufWriter::new(File::create(path).unwrap_or_else(|e| panic!("cannot create {}: {}", path, e)))
+    });
+    let mut capture = args.capture.as_ref().map(|path| {
+        BufWriter::new(File::create(path).unwrap_or_else(|e| panic!("cannot create {}: {}", path, e)))
+    });
+    let mut error_report = Vec::new();
+    // kept request by request only for the reports that need the order of requests
+    let mut timeline = Vec::new();
+    let keep_timeline = args.report_interval.is_some() || !args.slo.is_empty();
+    let mut errors = 0;
+    let mut durations_by_operation: HashMap<&str, histogram::Latencies> = HashMap::new();
+    let mut stage_results = vec![(latencies(), 0); stages.len()];
+    let workers = stages.iter().map(|stage| stage.parallelism).max().unwrap_or(0);
+    let mut durations_by_worker = vec![latencies(); workers];
+    let mut durations_by_namespace: HashMap<String, Vec<Duration>> = HashMap::new();
+    let mut halves = [latencies(), latencies()];
+    let split_halves = hedger.is_some() || deadline.is_some() || args.variance_check || args.compare_consistency || args.compare_select_count;
+    let mut growth_samples = Vec::new();
+    for Sample { index, stage, worker, offset, intended, latency: elapsed, result: resp, miss, operation, keys, key_values, namespace, params, attempts } in samples {
+        if let (Some(capture), Some(params), Some(attempts)) = (&mut capture, params, &attempts) {
+            let line = json!({
+                "schema_version": schema::VERSION,
+                "index": index,
+                "operation": operation,
+                "params": params,
+                "start": timestamp::utc(started_at + offset),
+                "start_offset_ms": offset.as_secs_f64() * 1000.0,
+                "intended_offset_ms": intended.as_secs_f64() * 1000.0,
+                "latency_ms": elapsed.as_secs_f64() * 1000.0,
+                "items": resp.as_ref().ok().map(|r| r.items),
+                "bytes": resp.as_ref().ok().map(|r| r.bytes),
+                "error_code": resp.as_ref().err().and_then(|e| e.code.as_deref()),
+                "request_id": resp.as_ref().err().and_then(|e| e.request_id.as_deref())
+                    .or_else(|| metadata::request_id(attempts)),
+            });
+            writeln!(capture, "{}", line).expect("failed to write capture");
+        }
+        let attempts = attempts.filter(|_| is_picked(index, args.raw_log_sample));
+        if let (Some(log), Some(attempts)) = (&mut raw_log, attempts) {
+            let line = json!({
+                "schema_version": schema::VERSION,
+                "index": index,
+                "start_offset_ms": offset.as_secs_f64() * 1000.0,
+                "intended_offset_ms": intended.as_secs_f64() * 1000.0,
+                "latency_ms": elapsed.as_secs_f64() * 1000.0,
+                "miss": miss,
+                "items": resp.as_ref().ok().map(|r| r.items),
+                "error_code": resp.as_ref().err().and_then(|e| e.code.as_deref()),
+                "request_id": resp.as_ref().err().and_then(|e| e.request_id.as_deref()),
+                "attempts": attempts.iter().map(metadata::AttemptMetadata::to_json).collect::<Vec<_>>(),
+            });
+            writeln!(log, "{}", line).expect("failed to write raw log");
+        }
+        durations.record(elapsed);
+        let wait = offset.saturating_sub(intended);
+        response_times.record(elapsed + wait);
+        late_starts += (wait >= LATE_START) as usize;
+        if split_halves && !hedger.as_ref().is_some_and(|hedger| hedger.is_early(index)) {
+            halves[index % 2].record(elapsed);
+        }
+        if keep_timeline {
+            timeline.push((offset, elapsed, resp.is_ok()));
+        }
+        errors += resp.is_err() as usize;
+        if growth.is_some() {
+            growth_samples.push((offset, elapsed, resp.is_ok(), operation == "PutItem"));
+        }
+        stage_results[stage].0.record(elapsed);
+        stage_results[stage].1 += resp.is_err() as usize;
+        if worker >= durations_by_worker.len() {
+            durations_by_worker.resize(worker + 1, latencies());
+        }
+        durations_by_worker[worker].record(elapsed);
+        durations_by_operation.entry(operation).or_insert_with(latencies).record(elapsed);
+        if let Some(namespace) = namespace {
+            durations_by_namespace.entry(namespace).or_default().push(elapsed);
+        }
+        if miss { miss_durations.record(elapsed) } else { hit_durations.record(elapsed) }
+        if let (Err(e), Some(keys)) = (&resp, &keys) {
+            error_report.push(json!({
+                "timestamp": timestamp::utc(started_at + offset),
+                "index": index,
+                "operation": operation,
+                "latency_ms": elapsed.as_secs_f64() * 1000.0,
+                "request_id": e.request_id,
+                "error_code": e.code,
+                "keys_sha256": keys.iter().map(|key| request::hash_key(key)).collect::<Vec<_>>(),
+                "cancellation_reasons": e.cancellation_reasons,
+                "error_chain": redact.text(&e.message, &key_values),
+            }));
+        }
+        if let Err(e) = &resp {
+            println!("{}", redact.text(&e.to_string(), &key_values));
+            if e.code.as_deref() == Some("TransactionCanceledException") {
+                cancelled_durations.push(elapsed);
+                for reason in &e.cancellation_reasons {
+                    *cancellation_reasons.entry(reason.clone()).or_default() += 1;
+                }
+            }
+        }
+        if let Ok(resp) = &resp {
+            committed_durations.record(elapsed);
+            unprocessed += resp.unprocessed;
+            unprocessed_retries += resp.retries;
+            partially_unprocessed += (resp.unprocessed > 0) as usize;
+            items_total += resp.items;
+            failed_statements += resp.failed;
+            response_bytes += resp.bytes;
+            durations_by_items.entry(resp.items).or_insert_with(latencies).record(elapsed);
+            if resp.items > 0 {
+                per_item_durations.record(elapsed / resp.items as u32);
+            }
+            capacity_units.push((resp.read_units, resp.write_units));
+            for (name, (reads, writes)) in &resp.index_units {
+                let entry = index_units.entry(name.clone()).or_default();
+                entry.0 += reads;
+                entry.1 += writes;
+            }
+            if let Some(first) = resp.pages.first() {
+                page_counts.push(resp.pages.len());
+                first_page_durations.push(*first);
+                paginated_durations.push(elapsed);
+                page_durations.extend(&resp.pages);
+            }
+        }
+        *response_stats.entry(resp.map(|r| r.items).map_err(|e| e.code)).or_insert(0) += 1;
+    }
+    if let Some(log) = &mut raw_log {
+        log.flush().expect("failed to write raw log");
+    }
+    if let Some(capture) = &mut capture {
+        capture.flush().expect("failed to write capture");
+    }
+    if let Some(path) = &args.error_report {
+        let file = File::create(path).unwrap_or_else(|e| panic!("cannot create {}: {}", path, e));
+        let report = json!({ "schema_version": schema::VERSION, "table": table, "failed_requests": error_report });
+        serde_json::to_writer_pretty(BufWriter::new(file), &report).expect("failed to write error report");
+        println!("Wrote {} failed requests to {}", error_report.len(), path);
+    }
+    if let Some(path) = &args.export_load_test {
+        let stage_ends = stage_starts.iter().skip(1).copied().chain(std::iter::once(total_duration));
+        let phases: Vec<_> = stages.iter().zip(&stage_results).zip(stage_starts.iter().zip(stage_ends))
+            .map(|((stage, (durations, errors)), (stage_start, stage_end))| loadtest::Phase {
+                stage,
+                elapsed: stage_end - *stage_start,
+                requests: durations.len(),
+                errors: *errors,
+            })
+            .collect();
+        let file = File::create(path).unwrap_or_else(|e| panic!("cannot create {}: {}", path, e));
+        serde_json::to_writer_pretty(BufWriter::new(file), &loadtest::config(cli, args, &phases, table.as_ref())).expect("failed to write load-test config");
+        println!("Wrote the workload as a load-test config to {}", path);
     }
 
-    let mut durations: Vec<Duration> = durations.into_iter().collect();
-    durations.sort();
+    println!("\nResponse stats:");
+    for (num_items, num_responses) in &response_stats {
+        let to_str = match num_items {
+            Ok(x) => format!("{} items", x),
+            Err(code) => format!("Error ({})", code.as_deref().unwrap_or("unknown")),
+        };
+        println!("{}: {} responses", to_str, num_responses);
+    }
+    if args.operation == Operation::Error {
+        let expected = response_stats.iter()
+            .filter(|(resp, _)| matches!(resp, Err(Some(code)) if code == args.error_kind.expected_code()))
+            .map(|(_, n)| n)
+            .sum::<usize>();
+        println!("Expected {} responses: {} of {}", args.error_kind.expected_code(), expected, durations.len());
+    }
+    if args.operation.is_batch() {
+        println!("Unprocessed keys: {} returned across {} calls, {} follow-up requests", 
+            unprocessed, partially_unprocessed, unprocessed_retries);
+    }
+    if args.operation == Operation::BatchExecuteStatement {
+        println!("Statements that failed inside successful batches: {}", failed_statements);
+    }
+    if !args.writes() && args.operation != Operation::BatchGetItem {
+        print_result_sizes(&durations_by_items, &args.histogram);
+    }
+    if args.operation == Operation::BatchGetItem {
+        println!("Missing keys requested: {} per call", args.missing_keys);
+        println!("\nLatency by items returned (milliseconds):");
+        let mut by_items: Vec<_> = durations_by_items.into_iter().collect();
+        by_items.sort_by_key(|(items, _)| *items);
+        for (items, durations) in by_items {
+            println!("{} items: {} responses, p50 {:.3}, p99 {:.3}", 
+                items, durations.len(), durations.quantile_ms(0.5), durations.quantile_ms(0.99));
+        }
+    }
+    if response_bytes > 0 {
+        println!("Mean response size: {:.0} bytes", response_bytes as f64 / durations.len() as f64);
+    }
 
     println!("\nLatency Statistics (milliseconds):");
-    println!("Min: {:.3}", quantile_ms(&durations, 0.0));
-    println!("Max: {:.3}", quantile_ms(&durations, 1.0));
-    // println!("Mean: {:.3}", quantile_ms() );
-    // println!("Stddev: {:.3}", hist.stdev() / 1000.0);
+    println!("Min: {:.3}", durations.quantile_ms(0.0));
+    println!("Max: {:.3}", durations.quantile_ms(1.0));
+    if let Some(stats) = stats::Stats::of(&durations) {
+        stats.print();
+    }
+    if durations.clamped() > 0 {
+        println!("({} requests above --histogram-max-ms, counted at it)", durations.clamped());
+    }
     println!("\nPercentiles:");
-    println!("p50: {:.3}", quantile_ms(&durations, 0.5));
-    println!("p90: {:.3}", quantile_ms(&durations, 0.9));
-    println!("p95: {:.3}", quantile_ms(&durations, 0.95));
-    println!("p99: {:.3}", quantile_ms(&durations, 0.99));
-    println!("p99.9: {:.3}", quantile_ms(&durations, 0.999));
+    println!("p50: {}", durations.percentile_ms(0.5));
+    println!("p90: {}", durations.percentile_ms(0.9));
+    println!("p95: {}", durations.percentile_ms(0.95));
+    println!("p99: {}", durations.percentile_ms(0.99));
+    println!("p99.9: {}", durations.percentile_ms(0.999));
+    if is_low_confidence(durations.len(), 0.999) {
+        println!("(low confidence: fewer than {} samples above the percentile)", MIN_TAIL_SAMPLES);
+    }
+    print_response_time(&durations, &response_times, late_starts);
+    if let Some(hedger) = &hedger {
+        let [hedged, unhedged] = &halves;
+        hedger.report(hedged, unhedged);
+    }
+    if let Some(deadline) = &deadline {
+        let [retrying, waiting] = &halves;
+        deadline.report(retrying, waiting);
+    }
+    if args.variance_check {
+        print_variance(&halves);
+    }
+    if args.compare_consistency {
+        print_consistency(&halves);
+    }
+    if args.compare_select_count {
+        print_select_count(&halves);
+    }
+    if let (Some(floor), false) = (&floor, durations.is_empty()) {
+        println!("Above the measurement floor: p50 {:.3}, p99 {:.3}",
+            durations.quantile_ms(0.5) - floor.p50, durations.quantile_ms(0.99) - floor.p99);
+    }
+    if args.operation.is_transaction() {
+        println!("\nTransactions: {} committed, {} cancelled ({:.2}% cancellation rate)", committed_durations.len(),
+            cancelled_durations.len(), 100.0 * cancelled_durations.len() as f64 / durations.len() as f64);
+        committed_durations.print("Committed");
+        print_split_latency("Cancelled", &mut cancelled_durations);
+        for (reason, count) in &cancellation_reasons {
+            println!("Cancellation reason {}: {} items", reason, count);
+        }
+    }
+    if durations_by_operation.len() > 1 {
+        println!("\nLatency by operation (milliseconds):");
+        let mut by_operation: Vec<_> = durations_by_operation.into_iter().collect();
+        by_operation.sort_by_key(|(operation, _)| *operation);
+        for (operation, durations) in by_operation {
+            durations.print(operation);
+        }
+    }
+    if args.miss_ratio > 0.0 {
+        println!("\nHit vs miss latency (milliseconds):");
+        hit_durations.print("Hit");
+        miss_durations.print("Miss");
+    }
+    if args.operation.is_batch() && !per_item_durations.is_empty() {
+        println!("\nPer-item amortized latency (milliseconds):");
+        println!("p50: {}", per_item_durations.percentile_ms(0.5));
+        println!("p99: {}", per_item_durations.percentile_ms(0.99));
+    }
+    if !page_counts.is_empty() {
+        print_pages(&page_counts, &mut page_durations, &mut first_page_durations, &mut paginated_durations);
+    }
+    if args.stage.len() > 1 {
+        println!("\nLatency by stage (milliseconds):");
+        let stage_ends = stage_starts.iter().skip(1).copied().chain(std::iter::once(total_duration));
+        for (n, ((stage, (durations, errors)), (stage_start, stage_end))) in
+            stages.iter().zip(stage_results).zip(stage_starts.iter().zip(stage_ends)).enumerate()
+        {
+            let elapsed = stage_end - *stage_start;
+            println!("Stage {} ({}): {:.3}s, {:.1} queries/second, {} errors",
+                n + 1, stage, elapsed.as_secs_f64(), durations.len() as f64 / elapsed.as_secs_f64(), errors);
+            durations.print("  Latency");
+        }
+    }
+    if args.worker_stats {
+        let key_shards = args.key_range.as_ref().filter(|_| args.worker_key_affinity).map(|range| (range, shards));
+        print_workers(&durations_by_worker, key_shards);
+    }
+    if namespaces.is_some() {
+        print_namespaces(durations_by_namespace);
+    }
+    if let Some(mut watcher) = split_watch {
+        watcher.print();
+    }
     println!("\nThroughput: {:.1} queries/second", 
-        args.num_queries as f64 / total_duration.as_secs_f64());
-}
-
-async fn show_mapping(client: &Client, cli: &Cli, environment: &str) {
-    let mut stream = client.query()
-        .table_name(&cli.table)
-        .key_condition_expression("#pk = :pk")
-        .expression_attribute_names("#pk", &cli.partition_key)
-        .expression_attribute_values(":pk", AttributeValue::S(format!("__chalk_fqn_mapping__:{}", environment)))
-        .into_paginator()
-        .send();
-
-    let agg_regex = Regex::new("^(.+):([0-9]+)$").unwrap();
-
-    println!("FQN mappings for {}:", environment);
-    let mut table = vec![("Partition key prefix".to_owned(), "Aggregation key".to_owned(), "Bucket duration (ms)".to_owned())];
-    while let Some(resp) = stream.next().await {
-        let resp = match resp {
-            Err(e) => { 
-                println!("Encountered query error: {:?}", e);
-                continue
-            },
-            Ok(resp) => resp,
-        };
-        for item in resp.items() {
-            if let (Some(AttributeValue::S(key)), Some(AttributeValue::S(val))) = (item.get(&cli.sort_key), item.get("value")) {
-                if let Some(capture) = agg_regex.captures(key) {
-                    let agg_on = capture.get(1).unwrap().as_str();
-                    let bucket_duration = capture.get(2).unwrap().as_str();
-                    table.push((format!("{}:{}:", environment, val), agg_on.to_owned(), bucket_duration.to_owned()));
-                }
-            } else {
-                println!("Malformed item in query: {:?}", item);
-            }
+        durations.len() as f64 / total_duration.as_secs_f64());
+    println!("Peak in-flight requests: {}", peak_in_flight);
+    if !args.slo.is_empty() {
+        println!("\nError budget at the benchmarked rate:");
+        for slo in &args.slo {
+            slo.report(&timeline, durations.len() as f64 / total_duration.as_secs_f64());
+        }
+    }
+    if let (Some(tuner), Some(p99)) = (&tuner, args.auto_parallelism) {
+        println!("\nAuto parallelism (p99 bound {} ms):", p99);
+        for step in &tuner.steps {
+            println!("Parallelism {}: {:.1} queries/second, p99 {:.3}",
+                step.parallelism, step.throughput, step.p99.as_secs_f64() * 1000.0);
+        }
+        match (tuner.best(), tuner.settled) {
+            (Some(parallelism), true) => println!("Chosen parallelism: {}", parallelism),
+            (Some(parallelism), false) => println!("Best parallelism so far: {} (the run ended before the search settled)", parallelism),
+            (None, true) => println!("p99 exceeded the bound at the starting parallelism of {}; lower -k", args.parallelism),
+            (None, false) => println!("Too few requests to judge a parallelism level; run longer"),
+        }
+    }
+    let (read_units, write_units) = budget.consumed();
+    println!("Consumed capacity: {:.1} RCU, {:.1} WCU (whole run)", read_units, write_units);
+    if !capacity_units.is_empty() {
+        print_capacity(&capacity_units, &index_units);
+    }
+    if let Some(reason) = budget.exhausted() {
+        println!("Stopped early: capacity budget exhausted ({})", reason);
+    }
+    if matches!(args.operation, Operation::PutItem | Operation::BatchWriteItem) {
+        println!("Payload bandwidth: {:.3} MB/s", 
+            (items_total * request::item_bytes(&workload::base_item(args))) as f64 / total_duration.as_secs_f64() / 1_000_000.0);
+    }
+    println!("HTTP client: {}", cli.http_client.name());
+    if let Some(floor) = &floor {
+        println!("Measurement floor: {}", floor.describe());
+    }
+    let notes = annotations.as_ref().map(|annotations| annotations.since(start)).unwrap_or_default();
+    if let Some(window) = args.report_interval {
+        let window = positive_secs(window).expect("--report-interval is validated");
+        print_intervals(&timeline, &notes, window, started_at, cli.timezone);
+    }
+    if growth.is_some() {
+        let window = args.report_interval.and_then(positive_secs).unwrap_or(Duration::from_secs(10));
+        keygen::print_growth(&mut growth_samples, initial_keys, window, started_at, cli.timezone);
+    }
+    if annotations.is_some() {
+        println!("\nAnnotations: {}", notes.len());
+        for (at, note) in &notes {
+            println!("{} (+{:.3}s): {}", timestamp::both(started_at + *at, cli.timezone), at.as_secs_f64(), note);
         }
     }
+    if let Some(backoff) = &backoff {
+        println!("\nThrottle pauses: {}", backoff.interventions.len());
+        for intervention in &backoff.interventions {
+            println!("{}: {:.1}% throttled, paused {}s", timestamp::both(started_at + intervention.offset, cli.timezone),
+                intervention.throttle_rate * 100.0, args.throttle_pause_secs);
+        }
+    }
+    if args.cooldown_secs.is_some() {
+        print_cooldown(&mut warmup_durations, &cooldown, args.cooldown_tolerance, ended_at, cli.timezone);
+    }
+    println!("\nRun started at {}", timestamp::both(started_at, cli.timezone));
+    println!("Run ended at {}", timestamp::both(ended_at, cli.timezone));
+
+    let summary = Summary {
+        requests: durations.len(),
+        errors,
+        items: items_total,
+        read_units,
+        write_units,
+        percentiles: if durations.is_empty() {
+            [0.0; 4]
+        } else {
+            [0.5, 0.9, 0.99, 0.999].map(|quantile| durations.quantile_ms(quantile))
+        },
+        throughput: durations.len() as f64 / total_duration.as_secs_f64(),
+    };
+    if let Some(session) = session {
+        session.finish(&summary).await;
+    }
+    metrics::push(cli, args, &summary, started_at, ended_at).await;
+    summary
+}
 
-    let (w1, w2, w3) = table.iter().fold((0, 0, 0), |(a1, a2, a3), (c1, c2, c3)| (a1.max(c1.len()), a2.max(c2.len()), a3.max(c3.len())));
-    let (w1, w2, w3) = (w1 + 4, w2 + 4, w3 + 4);
-    for (pkey, agg_on, bucket_duration) in table {
-        println!("{pkey:w1$} {agg_on:w2$} {bucket_duration:w3$}");
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_stages() {
+        let stage = parse_stage("60s@100/8").unwrap();
+        assert!(matches!(stage.limit, StageLimit::Duration(d) if d == Duration::from_secs(60)));
+        assert_eq!((stage.qps, stage.parallelism), (100, 8));
+        let stage = parse_stage("500@20").unwrap();
+        assert!(matches!(stage.limit, StageLimit::Queries(500)));
+        assert_eq!(stage.parallelism, 0);
+        assert!(matches!(parse_stage("500ms@1").unwrap().limit, StageLimit::Duration(d) if d == Duration::from_millis(500)));
+        assert!(matches!(parse_stage("1.5m@1").unwrap().limit, StageLimit::Duration(d) if d == Duration::from_secs(90)));
+    }
+
+    #[test]
+    fn rejects_malformed_stages() {
+        for malformed in ["", "60s", "@100", "60s@", "60s@0", "0@100", "60s@100/0", "60s@100/", "-5s@1", "ms@1",
+            "60h@1", "60s@1.5", "infs@1", "NaNs@1", "1e300s@1", "60s@100/8/2"] {
+            assert!(parse_stage(malformed).is_err(), "{:?}", malformed);
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("4KB"), Ok(4096));
+        assert_eq!(parse_size("4kb"), Ok(4096));
+        assert_eq!(parse_size("1MB"), Ok(1024 * 1024));
+        assert_eq!(parse_size("100B"), Ok(100));
+        for malformed in ["", "0", "0KB", "KB", "B", "-1", "1.5KB", "4GB", "99999999999999999999", "18014398509481984MB"] {
+            assert!(parse_size(malformed).is_err(), "{:?}", malformed);
+        }
+    }
+
+    #[test]
+    fn parses_limits() {
+        assert_eq!(parse_limit("none"), Ok(None));
+        assert_eq!(parse_limit("NONE"), Ok(None));
+        assert_eq!(parse_limit("1"), Ok(Some(1)));
+        for malformed in ["", "0", "-1", "1.5", "2147483648"] {
+            assert!(parse_limit(malformed).is_err(), "{:?}", malformed);
+        }
+    }
+
+    #[test]
+    fn parses_percentiles() {
+        assert_eq!(parse_percentile("p99"), Ok(0.99));
+        assert!((parse_percentile("99.9").unwrap() - 0.999).abs() < 1e-12);
+        for outside in ["p0", "p100", "p-1", "p", "high"] {
+            assert!(parse_percentile(outside).is_err(), "{:?}", outside);
+        }
+    }
+
+    #[test]
+    fn min_samples_match_the_documented_sizes() {
+        let p99 = min_samples_for(0.99, 0.95);
+        let p999 = min_samples_for(0.999, 0.95);
+        assert!((1400..1800).contains(&p99), "{}", p99);
+        assert!((14000..18000).contains(&p999), "{}", p999);
+        // less confidence needs fewer samples
+        assert!(min_samples_for(0.99, 0.5) < p99);
+    }
+
+    #[test]
+    fn min_samples_at_the_percentile_bounds() {
+        // just above p0, nearly every sample is in the tail
+        let low = min_samples_for(0.000_01, 0.95);
+        assert!((10..=11).contains(&low), "{}", low);
+        let high = min_samples_for(0.9999, 0.99);
+        assert!(high > 100_000 && high < 250_000, "{}", high);
+        assert!(!is_low_confidence(high, 0.9999));
+    }
+
+    #[test]
+    fn positive_secs_only_takes_durations_that_fit() {
+        assert_eq!(positive_secs(1.5), Some(Duration::from_millis(1500)));
+        for invalid in [0.0, -1.0, 1e-12, f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1e300] {
+            assert_eq!(positive_secs(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn nearest_rank_quantiles() {
+        let durations: Vec<_> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(quantile_ms(&durations, 0.0), 1.0);
+        assert_eq!(quantile_ms(&durations, 0.5), 50.0);
+        assert_eq!(quantile_ms(&durations, 0.99), 99.0);
+        assert_eq!(quantile_ms(&durations, 1.0), 100.0);
+        assert_eq!(quantile_ms(&durations[..1], 0.999), 1.0);
+        // capacity units are ranked the same way as latencies
+        assert_eq!((nearest_rank(100, 0.5), nearest_rank(100, 0.99), nearest_rank(3, 0.5)), (49, 98, 1));
+    }
+}
@@ -0,0 +1,384 @@
+use crate::redact::Redactor;
+use crate::request::{key_string, Request};
+use crate::Cli;
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::Client;
+use clap::{Args, ValueEnum};
+use regex::Regex;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+#[derive(Args, Debug, Clone)]
+pub struct MappingArgs {
+    /// Chalk environment name
+    #[arg(short, long)]
+    environment: String,
+
+    /// Stop after this many mapping entries
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// Maximum items per Query page (DynamoDB's Limit)
+    #[arg(long)]
+    page_size: Option<i32>,
+
+    /// Print the mappings in this format instead of an aligned table
+    #[arg(long, value_enum)]
+    export: Option<Export>,
+
+    #[command(flatten)]
+    cache: CacheArgs,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CacheArgs {
+    /// Reuse mappings read within this many seconds, cached under ~/.cache/dynamodbbench (0 disables the cache)
+    #[arg(long, default_value = "600")]
+    cache_ttl: u64,
+
+    /// Read the mappings from the table even if a fresh cached copy exists
+    #[arg(long)]
+    refresh: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Export {
+    /// Comma-separated values with a header row
+    Csv,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct MappingDiffArgs {
+    /// First Chalk environment
+    environment_a: String,
+
+    /// Second Chalk environment
+    environment_b: String,
+
+    /// Maximum items per Query page (DynamoDB's Limit)
+    #[arg(long)]
+    page_size: Option<i32>,
+
+    #[command(flatten)]
+    cache: CacheArgs,
+}
+
+/// One FQN mapping entry.
+pub struct Mapping {
+    pub partition_key_prefix: String,
+    pub aggregation_key: String,
+    /// Bucket duration in milliseconds
+    pub bucket_duration: String,
+}
+
+impl Mapping {
+    /// Reads a mapping item, whose sort key is `<aggregation key>:<bucket duration ms>`.
+    /// Items without the expected attributes are `Err`, to be reported as malformed.
+    fn parse(item: &HashMap<String, AttributeValue>, sort_key: &str, environment: &str, agg_regex: &Regex) -> Option<Result<Mapping, ()>> {
+        let (Some(AttributeValue::S(key)), Some(AttributeValue::S(val))) = (item.get(sort_key), item.get("value")) else {
+            return Some(Err(()));
+        };
+        let capture = agg_regex.captures(key)?;
+        Some(Ok(Mapping {
+            partition_key_prefix: format!("{}:{}:", environment, val),
+            aggregation_key: capture.get(1).unwrap().as_str().to_owned(),
+            bucket_duration: capture.get(2).unwrap().as_str().to_owned(),
+        }))
+    }
+}
+
+fn agg_regex() -> Regex {
+    Regex::new("^(.+):([0-9]+)$").unwrap()
+}
+
+/// Quotes a CSV field when it contains a separator, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+type Page = Vec<HashMap<String, AttributeValue>>;
+
+/// Where the mappings of an environment are cached: one JSON file per region, table and environment.
+fn cache_path(cli: &Cli, environment: &str) -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    let name = format!("{}-{}-{}.json", cli.region, cli.table, environment)
+        .replace(|c: char| !c.is_ascii_alphanumeric() && !"-_.".contains(c), "_");
+    Some(base.join("dynamodbbench").join("mappings").join(name))
+}
+
+/// The cached (sort key, value) pairs of the mapping items and their age,
+/// unless the cache is missing, unreadable or older than `ttl`.
+fn read_cache(path: &Path, ttl: Duration) -> Option<(Vec<(String, String)>, Duration)> {
+    let cached: Value = serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+    let fetched_at = UNIX_EPOCH + Duration::from_millis(cached["fetched_at_ms"].as_u64()?);
+    let age = SystemTime::now().duration_since(fetched_at).ok().filter(|age| *age <= ttl)?;
+    let entries = cached["entries"].as_array()?.iter()
+        .filter_map(|entry| Some((entry["sort"].as_str()?.to_owned(), entry["value"].as_str()?.to_owned())))
+        .collect();
+    Some((entries, age))
+}
+
+fn write_cache(path: &Path, entries: &[(String, String)]) {
+    let cached = json!({
+        "fetched_at_ms": SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64,
+        "entries": entries.iter().map(|(sort, value)| json!({ "sort": sort, "value": value })).collect::<Vec<_>>(),
+    });
+    let written = std::fs::create_dir_all(path.parent().unwrap()).and_then(|_| std::fs::write(path, cached.to_string()));
+    if let Err(e) = written {
+        eprintln!("Cannot cache mappings in {}: {}", path.display(), e);
+    }
+}
+
+/// What [`fetch_pages`] reads, and where it caches a complete read.
+struct Source {
+    client: Client,
+    table: String,
+    partition_key: String,
+    sort_key: String,
+    environment: String,
+    page_size: Option<i32>,
+    cache_path: Option<PathBuf>,
+}
+
+/// Reads the mapping partition page by page into `pages`. Each page's
+/// LastEvaluatedKey is the next one's start, so the pages of the single
+/// mapping partition can't be fetched in parallel; running this as its own
+/// task at least overlaps fetching the next page with printing the last.
+async fn fetch_pages(source: Source, pages: mpsc::Sender<Page>) {
+    let mut stream = source.client.query()
+        .table_name(&source.table)
+        .key_condition_expression("#pk = :pk")
+        .expression_attribute_names("#pk", &source.partition_key)
+        .expression_attribute_values(":pk", AttributeValue::S(format!("__chalk_fqn_mapping__:{}", source.environment)))
+        .set_limit(source.page_size)
+        .into_paginator()
+        .send();
+
+    let mut entries = Vec::new();
+    let mut complete = true;
+    while let Some(resp) = stream.next().await {
+        let resp = match resp {
+            Err(e) => {
+                eprintln!("Encountered query error: {:?}", e);
+                complete = false;
+                continue
+            },
+            Ok(resp) => resp,
+        };
+        let page = resp.items.unwrap_or_default();
+        entries.extend(page.iter().filter_map(|item| match (item.get(&source.sort_key), item.get("value")) {
+            (Some(AttributeValue::S(sort)), Some(AttributeValue::S(value))) => Some((sort.clone(), value.clone())),
+            _ => None,
+        }));
+        if pages.send(page).await.is_err() {
+            // the reader has seen --limit entries
+            return;
+        }
+    }
+    if let (true, Some(path)) = (complete, &source.cache_path) {
+        write_cache(path, &entries);
+    }
+}
+
+/// Starts reading the mappings of `environment`, a page at a time, from the
+/// cache when it holds a fresh copy.
+fn mapping_pages(client: &Client, cli: &Cli, environment: &str, page_size: Option<i32>, cache: &CacheArgs) -> mpsc::Receiver<Page> {
+    let (sender, pages) = mpsc::channel(1);
+    let cache_path = (cache.cache_ttl > 0).then(|| cache_path(cli, environment)).flatten();
+    let cached = cache_path.as_deref()
+        .filter(|_| !cache.refresh)
+        .and_then(|path| read_cache(path, Duration::from_secs(cache.cache_ttl)));
+    if let Some((entries, age)) = cached {
+        eprintln!("Mappings for {} read {}s ago, from {}; --refresh to re-read them",
+            environment, age.as_secs(), cache_path.unwrap().display());
+        let page = entries.into_iter()
+            .map(|(sort, value)| HashMap::from([
+                (cli.sort_key.clone(), AttributeValue::S(sort)),
+                ("value".to_owned(), AttributeValue::S(value)),
+            ]))
+            .collect();
+        sender.try_send(page).expect("the channel has room for one page");
+        return pages;
+    }
+    let source = Source {
+        client: client.clone(),
+        table: cli.table.clone(),
+        partition_key: cli.partition_key.clone(),
+        sort_key: cli.sort_key.clone(),
+        environment: environment.to_owned(),
+        page_size,
+        cache_path,
+    };
+    tokio::spawn(fetch_pages(source, sender));
+    pages
+}
+
+/// Every FQN mapping of `environment`, skipping malformed items.
+pub async fn load(client: &Client, cli: &Cli, environment: &str, cache: &CacheArgs) -> Vec<Mapping> {
+    let mut pages = mapping_pages(client, cli, environment, None, cache);
+    let agg_regex = agg_regex();
+    let mut mappings = Vec::new();
+    while let Some(page) = pages.recv().await {
+        mappings.extend(page.iter().filter_map(|item| Mapping::parse(item, &cli.sort_key, environment, &agg_regex)?.ok()));
+    }
+    mappings
+}
+
+/// Groups requests by the feature namespace they read, for the latency
+/// breakdown of --chalk-profile runs.
+pub struct Namespaces {
+    /// Partition key prefixes and their aggregation keys, longest prefix first
+    prefixes: Vec<(String, String)>,
+}
+
+impl Namespaces {
+    /// Reads the FQN mappings of `environment`, if given, to resolve windowed
+    /// aggregation partitions to their aggregation key.
+    pub async fn load(client: &Client, cli: &Cli, environment: Option<&str>, cache: &CacheArgs) -> Namespaces {
+        let mut prefixes = Vec::new();
+        if let Some(environment) = environment {
+            prefixes.extend(load(client, cli, environment, cache).await.into_iter()
+                .map(|mapping| (mapping.partition_key_prefix, mapping.aggregation_key)));
+            prefixes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        }
+        Namespaces { prefixes }
+    }
+
+    /// The aggregation key whose partition key prefix the request's partition
+    /// value starts with, or else its sort key value, which is the namespace
+    /// (`__ns__`) in the online-store layouts.
+    pub fn of(&self, request: &Request, cli: &Cli) -> String {
+        let Some((partition, sort)) = request.primary_key(&cli.partition_key, &cli.sort_key) else {
+            return "(multiple items)".to_owned();
+        };
+        self.prefixes.iter()
+            .find(|(prefix, _)| partition.starts_with(prefix.as_str()))
+            .map(|(_, aggregation_key)| aggregation_key.clone())
+            .or(sort)
+            .unwrap_or_else(|| "(unresolved)".to_owned())
+    }
+}
+
+pub async fn run(client: &Client, cli: &Cli, args: &MappingArgs) {
+    let mut pages = mapping_pages(client, cli, &args.environment, args.page_size, &args.cache);
+    let agg_regex = agg_regex();
+    let redact = Redactor::new(cli.redact_keys);
+
+    let header = ("Partition key prefix".to_owned(), "Aggregation key".to_owned(), "Bucket duration (ms)".to_owned());
+    // Rows are printed a page at a time; columns widen as longer values turn
+    // up, rather than buffering every entry to size them up front.
+    let (mut w1, mut w2, mut w3) = (0, 0, 0);
+    let mut print = |(pkey, agg_on, bucket_duration): (String, String, String)| match args.export {
+        Some(Export::Csv) => println!("{},{},{}", csv_field(&pkey), csv_field(&agg_on), csv_field(&bucket_duration)),
+        None => {
+            (w1, w2, w3) = (w1.max(pkey.len() + 4), w2.max(agg_on.len() + 4), w3.max(bucket_duration.len() + 4));
+            println!("{pkey:w1$} {agg_on:w2$} {bucket_duration:w3$}");
+        }
+    };
+    if args.export.is_none() {
+        println!("FQN mappings for {}:", args.environment);
+    }
+    print(header);
+    let mut printed = 0;
+    while let Some(page) = pages.recv().await {
+        for item in page {
+            if args.limit.is_some_and(|limit| printed >= limit) {
+                return;
+            }
+            match Mapping::parse(&item, &cli.sort_key, &args.environment, &agg_regex) {
+                Some(Ok(mapping)) => {
+                    print((mapping.partition_key_prefix, mapping.aggregation_key, mapping.bucket_duration));
+                    printed += 1;
+                }
+                Some(Err(())) => eprintln!("{}", malformed(&item, [&cli.partition_key, &cli.sort_key], redact)),
+                None => {}
+            }
+        }
+    }
+}
+
+/// Bucket durations of each aggregation key in `environment`.
+async fn bucket_durations(client: &Client, cli: &Cli, environment: &str, args: &MappingDiffArgs) -> BTreeMap<String, BTreeSet<u64>> {
+    let mut pages = mapping_pages(client, cli, environment, args.page_size, &args.cache);
+    let agg_regex = agg_regex();
+    let redact = Redactor::new(cli.redact_keys);
+    let mut durations: BTreeMap<String, BTreeSet<u64>> = BTreeMap::new();
+    while let Some(page) = pages.recv().await {
+        for item in page {
+            match Mapping::parse(&item, &cli.sort_key, environment, &agg_regex) {
+                Some(Ok(mapping)) => {
+                    let duration = mapping.bucket_duration.parse().expect("bucket durations match [0-9]+");
+                    durations.entry(mapping.aggregation_key).or_default().insert(duration);
+                }
+                Some(Err(())) => eprintln!("{}", malformed(&item, [&cli.partition_key, &cli.sort_key], redact)),
+                None => {}
+            }
+        }
+    }
+    durations
+}
+
+/// The warning for an item of the mapping partition that isn't a mapping,
+/// its key values redacted with --redact-keys.
+fn malformed(item: &HashMap<String, AttributeValue>, key_names: [&str; 2], redact: Redactor) -> String {
+    let key_values: Vec<_> = key_names.iter().filter_map(|name| item.get(*name)).map(key_string).collect();
+    redact.text(&format!("Malformed item in query: {:?}", item), &key_values)
+}
+
+/// Compares the FQN mappings of two environments by aggregation key.
+pub async fn diff(client: &Client, cli: &Cli, args: &MappingDiffArgs) {
+    let (a, b) = (&args.environment_a, &args.environment_b);
+    let (in_a, in_b) = tokio::join!(
+        bucket_durations(client, cli, a, args),
+        bucket_durations(client, cli, b, args),
+    );
+    let only = |this: &BTreeMap<String, BTreeSet<u64>>, other: &BTreeMap<String, BTreeSet<u64>>| -> Vec<String> {
+        this.keys().filter(|key| !other.contains_key(*key)).cloned().collect()
+    };
+    let (only_a, only_b) = (only(&in_a, &in_b), only(&in_b, &in_a));
+    let differing: Vec<_> = in_a.iter()
+        .filter_map(|(key, durations)| in_b.get(key).filter(|other| *other != durations).map(|other| (key, durations, other)))
+        .collect();
+
+    println!("FQN mappings: {} aggregation keys in {}, {} in {}", in_a.len(), a, in_b.len(), b);
+    println!("\nOnly in {} ({}):", a, only_a.len());
+    for key in &only_a {
+        println!("  {}", key);
+    }
+    println!("\nOnly in {} ({}):", b, only_b.len());
+    for key in &only_b {
+        println!("  {}", key);
+    }
+    println!("\nDifferent bucket durations (ms) ({}):", differing.len());
+    for (key, durations_a, durations_b) in &differing {
+        println!("  {}: {:?} in {}, {:?} in {}", key, durations_a, a, durations_b, b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::hash_key;
+
+    #[test]
+    fn malformed_items_redact_their_keys() {
+        let item = HashMap::from([
+            ("__id__".to_owned(), AttributeValue::S("__chalk_fqn_mapping__:prod".to_owned())),
+            ("__ns__".to_owned(), AttributeValue::S("user.orders:3600".to_owned())),
+            ("value".to_owned(), AttributeValue::N("7".to_owned())),
+        ]);
+        let shown = malformed(&item, ["__id__", "__ns__"], Redactor::new(false));
+        assert!(shown.contains("__chalk_fqn_mapping__:prod") && shown.contains("user.orders:3600"));
+        let redacted = malformed(&item, ["__id__", "__ns__"], Redactor::new(true));
+        assert!(!redacted.contains("__chalk_fqn_mapping__:prod") && !redacted.contains("user.orders:3600"), "{}", redacted);
+        assert!(redacted.contains(&hash_key("user.orders:3600")) && redacted.contains("N(\"7\")"));
+    }
+}
@@ -0,0 +1,83 @@
+use aws_sdk_dynamodb::config::interceptors::BeforeDeserializationInterceptorContextRef;
+use aws_sdk_dynamodb::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_sdk_dynamodb::error::BoxError;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::future::Future;
+
+/// Response headers worth attaching to an AWS support case.
+const CAPTURED_HEADERS: &[&str] = &[
+    "x-amzn-requestid",
+    "x-amz-id-2",
+    "x-amz-crc32",
+    "date",
+    "server-timing",
+];
+
+/// HTTP-level metadata of a single attempt (retries get their own entry).
+#[derive(Debug, Clone)]
+pub struct AttemptMetadata {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+}
+
+impl AttemptMetadata {
+    pub fn to_json(&self) -> Value {
+        let headers: serde_json::Map<_, _> = self.headers.iter()
+            .map(|(k, v)| (k.clone(), Value::String(v.clone())))
+            .collect();
+        json!({ "status": self.status, "headers": headers })
+    }
+}
+
+/// Request ID of the last attempt, the one whose response was kept.
+pub fn request_id(attempts: &[AttemptMetadata]) -> Option<&str> {
+    attempts.last()?.headers.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("x-amzn-requestid"))
+        .map(|(_, value)| value.as_str())
+}
+
+tokio::task_local! {
+    static ATTEMPTS: RefCell<Vec<AttemptMetadata>>;
+}
+
+/// Runs `fut`, collecting the metadata of every attempt it makes through a
+/// client carrying [`MetadataInterceptor`].
+pub async fn capture<F: Future>(fut: F) -> (F::Output, Vec<AttemptMetadata>) {
+    ATTEMPTS.scope(RefCell::new(Vec::new()), async {
+        let output = fut.await;
+        (output, ATTEMPTS.with(|attempts| attempts.take()))
+    }).await
+}
+
+/// Records response metadata into the enclosing [`capture`] scope, if any.
+///
+/// The orchestrator runs interceptors inline in the future driving the
+/// request, so a task-local ties each response to the request that sent it.
+#[derive(Debug)]
+pub struct MetadataInterceptor;
+
+impl Intercept for MetadataInterceptor {
+    fn name(&self) -> &'static str {
+        "MetadataInterceptor"
+    }
+
+    fn read_before_deserialization(
+        &self,
+        context: &BeforeDeserializationInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let response = context.response();
+        let _ = ATTEMPTS.try_with(|attempts| {
+            attempts.borrow_mut().push(AttemptMetadata {
+                status: response.status().as_u16(),
+                headers: response.headers().iter()
+                    .filter(|(name, _)| CAPTURED_HEADERS.contains(&name.to_ascii_lowercase().as_str()))
+                    .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                    .collect(),
+            })
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{loadtest, schema, timestamp, BenchArgs, Cli, Summary};
+use clap::Args;
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime};
+
+/// How long a push may take before it is given up on.
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Args, Debug, Clone)]
+pub struct MetricsArgs {
+    /// Push the run summary to this Chalk metrics ingestion endpoint, as an HTTP POST of JSON
+    #[arg(long)]
+    metrics_endpoint: Option<String>,
+
+    /// Header authenticating the push, as `Name: value`; a value of `env:VAR` is read from
+    /// that environment variable, keeping the secret out of the command line
+    #[arg(long, value_parser = parse_header, requires = "metrics_endpoint")]
+    metrics_auth_header: Option<(String, String)>,
+
+    /// Tag attached to the pushed metrics besides table, region and request, as `key=value`, repeatable
+    #[arg(long, value_parser = crate::parse_assignment, requires = "metrics_endpoint")]
+    metrics_tag: Vec<(String, String)>,
+}
+
+fn parse_header(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s.split_once(':').ok_or_else(|| format!("expected Name: value, got {:?}", s))?;
+    let value = match value.trim().strip_prefix("env:") {
+        Some(var) => std::env::var(var).map_err(|_| format!("environment variable {} is not set", var))?,
+        None => value.trim().to_owned(),
+    };
+    Ok((name.trim().to_owned(), value))
+}
+
+/// The run summary in the metrics ingestion format: one gauge per headline
+/// number, all sharing the run's end time and tags.
+fn payload(cli: &Cli, args: &BenchArgs, summary: &Summary, started_at: SystemTime, ended_at: SystemTime) -> Value {
+    let mut tags = serde_json::Map::new();
+    tags.insert("table".to_owned(), json!(cli.table));
+    tags.insert("region".to_owned(), json!(cli.region));
+    tags.insert("request".to_owned(), json!(loadtest::request_name(args)));
+    for (key, value) in &args.metrics.metrics_tag {
+        tags.insert(key.clone(), json!(value));
+    }
+    let [p50, p90, p99, p999] = summary.percentiles;
+    let metrics = [
+        ("requests", summary.requests as f64, "count"),
+        ("errors", summary.errors as f64, "count"),
+        ("items", summary.items as f64, "count"),
+        ("throughput", summary.throughput, "qps"),
+        ("latency.p50", p50, "ms"),
+        ("latency.p90", p90, "ms"),
+        ("latency.p99", p99, "ms"),
+        ("latency.p99_9", p999, "ms"),
+    ];
+    json!({
+        "schema_version": schema::VERSION,
+        "source": "dynamodbbench",
+        "timestamp": timestamp::TimeFormat::Rfc3339.format(ended_at),
+        "started_at": timestamp::TimeFormat::Rfc3339.format(started_at),
+        "tags": tags,
+        "metrics": metrics.iter().map(|(name, value, unit)| json!({
+            "name": format!("dynamodbbench.{}", name),
+            "value": value,
+            "unit": unit,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+/// Pushes the run summary to --metrics-endpoint, if one is given. A failed
+/// push is reported but doesn't fail the run, whose results are already printed.
+pub async fn push(cli: &Cli, args: &BenchArgs, summary: &Summary, started_at: SystemTime, ended_at: SystemTime) {
+    let Some(endpoint) = &args.metrics.metrics_endpoint else { return };
+    let mut request = reqwest::Client::new()
+        .post(endpoint)
+        .timeout(PUSH_TIMEOUT)
+        .header("Content-Type", "application/json")
+        .body(payload(cli, args, summary, started_at, ended_at).to_string());
+    if let Some((name, value)) = &args.metrics.metrics_auth_header {
+        request = request.header(name, value);
+    }
+    match request.send().await.and_then(|response| response.error_for_status()) {
+        Ok(_) => println!("Pushed the run summary to {}", endpoint),
+        Err(e) => println!("Failed to push the run summary to {}: {}", endpoint, e),
+    }
+}
@@ -0,0 +1,76 @@
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// What the mock answers every call with: an empty JSON object, which every
+/// DynamoDB operation's output deserializes from (no items, no unprocessed keys).
+const RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\n\
+    Content-Type: application/x-amz-json-1.0\r\n\
+    x-amzn-RequestId: mock\r\n\
+    Content-Length: 2\r\n\
+    \r\n\
+    {}";
+
+/// A DynamoDB endpoint on loopback that answers every request at once and
+/// successfully, so whatever latency a client sees against it is the
+/// client's own.
+pub struct Mock {
+    pub endpoint_url: String,
+    served: Arc<AtomicUsize>,
+}
+
+impl Mock {
+    pub async fn start() -> io::Result<Mock> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let endpoint_url = format!("http://{}", listener.local_addr()?);
+        let served = Arc::new(AtomicUsize::new(0));
+        let counter = served.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let _ = stream.set_nodelay(true);
+                tokio::spawn(serve(stream, counter.clone()));
+            }
+        });
+        Ok(Mock { endpoint_url, served })
+    }
+
+    /// Requests answered so far.
+    pub fn served(&self) -> usize {
+        self.served.load(Ordering::Relaxed)
+    }
+}
+
+/// Answers the requests of one keep-alive connection until the client closes it.
+async fn serve(mut stream: TcpStream, served: Arc<AtomicUsize>) -> io::Result<()> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 16 * 1024];
+    loop {
+        let request_len = loop {
+            if let Some(len) = request_len(&buffer) {
+                break len;
+            }
+            let n = stream.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(());
+            }
+            buffer.extend_from_slice(&chunk[..n]);
+        };
+        buffer.drain(..request_len);
+        stream.write_all(RESPONSE).await?;
+        served.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Length of the first request in `buffer`, headers and body, once all of it has arrived.
+fn request_len(buffer: &[u8]) -> Option<usize> {
+    let head_len = buffer.windows(4).position(|w| w == b"\r\n\r\n")? + 4;
+    let head = String::from_utf8_lossy(&buffer[..head_len]);
+    let body_len = head.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    (buffer.len() >= head_len + body_len).then_some(head_len + body_len)
+}
@@ -0,0 +1,163 @@
+use crate::{load_config, make_client, percentile_ms, quantile_ms, workload, Cli};
+use aws_sdk_dynamodb::config::interceptors::{BeforeTransmitInterceptorContextMut, BeforeTransmitInterceptorContextRef};
+use aws_sdk_dynamodb::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_sdk_dynamodb::error::{BoxError, DisplayErrorContext};
+use aws_sdk_dynamodb::Client;
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory};
+use std::cell::Cell;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+tokio::task_local! {
+    /// When the current attempt started, and the endpoint resolution time of the request so far.
+    static RESOLVING: (Cell<Option<Instant>>, Cell<Duration>);
+}
+
+/// Runs `fut`, also returning the time its attempts spent resolving the
+/// endpoint, as timed by [`EndpointInterceptor`].
+async fn timed<F: Future>(fut: F) -> (F::Output, Duration) {
+    RESOLVING.scope((Cell::new(None), Cell::new(Duration::ZERO)), async {
+        let output = fut.await;
+        (output, RESOLVING.with(|(_, total)| total.get()))
+    }).await
+}
+
+/// Times endpoint resolution inside a [`timed`] scope: the orchestrator
+/// resolves the endpoint of every attempt between these two hooks, inline in
+/// the future driving the request.
+#[derive(Debug)]
+pub struct EndpointInterceptor;
+
+impl Intercept for EndpointInterceptor {
+    fn name(&self) -> &'static str {
+        "EndpointInterceptor"
+    }
+
+    fn read_before_attempt(
+        &self,
+        _context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let _ = RESOLVING.try_with(|(started, _)| started.set(Some(Instant::now())));
+        Ok(())
+    }
+
+    fn modify_before_signing(
+        &self,
+        _context: &mut BeforeTransmitInterceptorContextMut<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let _ = RESOLVING.try_with(|(started, total)| {
+            if let Some(started) = started.take() {
+                total.set(total.get() + started.elapsed());
+            }
+        });
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct OverheadArgs {
+    /// Timed requests per client lifecycle
+    #[arg(long, default_value = "200")]
+    requests: usize,
+
+    /// Untimed requests before them, to open connections and resolve credentials
+    #[arg(long, default_value = "20")]
+    warmup: usize,
+}
+
+/// Where the client behind each request comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Lifecycle {
+    /// One client, built at startup, shared by every request
+    Shared,
+    /// A client built from the loaded config for every request
+    ClientPerRequest,
+    /// The config loaded from the environment and a client built from it for
+    /// every request, as a handler that sets up its client on each invocation does
+    ConfigPerRequest,
+}
+
+impl Lifecycle {
+    fn describe(self) -> &'static str {
+        match self {
+            Lifecycle::Shared => "Shared client",
+            Lifecycle::ClientPerRequest => "Client per request",
+            Lifecycle::ConfigPerRequest => "Config per request",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Timings {
+    setup: Vec<Duration>,
+    endpoint: Vec<Duration>,
+    request: Vec<Duration>,
+    /// Setup plus request
+    total: Vec<Duration>,
+}
+
+/// Sends GetItems of a missing key through a shared client, through a client
+/// rebuilt for every request and through a config reloaded for every request,
+/// and reports what building the client and resolving the endpoint add. The
+/// SDK resolves the endpoint for every request, while config loading and
+/// client construction happen wherever the application does them.
+pub async fn run(client: &Client, cli: &Cli, args: &OverheadArgs) {
+    if args.requests == 0 {
+        Cli::command().error(ErrorKind::ValueValidation, "--requests must be positive").exit();
+    }
+    let config = load_config(cli).await;
+    let key = workload::missing_key(cli, 0);
+    println!("Timing {} GetItems of a missing key in {} for each client lifecycle, after {} warmup requests",
+        args.requests, cli.table, args.warmup);
+
+    let mut results = Vec::new();
+    for lifecycle in [Lifecycle::Shared, Lifecycle::ClientPerRequest, Lifecycle::ConfigPerRequest] {
+        let mut timings = Timings::default();
+        for n in 0..args.warmup + args.requests {
+            let setup_start = Instant::now();
+            let client = match lifecycle {
+                Lifecycle::Shared => client.clone(),
+                Lifecycle::ClientPerRequest => make_client(&config),
+                Lifecycle::ConfigPerRequest => make_client(&load_config(cli).await),
+            };
+            let setup = setup_start.elapsed();
+            let request_start = Instant::now();
+            let (result, endpoint) = timed(client.get_item().table_name(&cli.table).set_key(Some(key.clone())).send()).await;
+            let request = request_start.elapsed();
+            if let Err(e) = result {
+                Cli::command().error(ErrorKind::Io, format!("GetItem failed: {}", DisplayErrorContext(&e))).exit();
+            }
+            if n >= args.warmup {
+                timings.setup.push(setup);
+                timings.endpoint.push(endpoint);
+                timings.request.push(request);
+                timings.total.push(setup + request);
+            }
+        }
+        for durations in [&mut timings.setup, &mut timings.endpoint, &mut timings.request, &mut timings.total] {
+            durations.sort();
+        }
+        println!("\n{} (milliseconds):", lifecycle.describe());
+        println!("Client setup: p50 {}, p99 {}", percentile_ms(&timings.setup, 0.5), percentile_ms(&timings.setup, 0.99));
+        println!("Endpoint resolution: p50 {}, p99 {}", percentile_ms(&timings.endpoint, 0.5), percentile_ms(&timings.endpoint, 0.99));
+        println!("Request: p50 {}, p99 {}", percentile_ms(&timings.request, 0.5), percentile_ms(&timings.request, 0.99));
+        results.push((lifecycle, timings));
+    }
+
+    println!("\nClient lifecycle comparison (milliseconds, setup plus request):");
+    println!("{:<20} {:>10} {:>10} {:>10} {:>14} {:>14}", "Lifecycle", "p50", "p99", "Max", "Setup p50", "Endpoint p50");
+    for (lifecycle, timings) in &results {
+        println!("{:<20} {:>10.3} {:>10.3} {:>10.3} {:>14.3} {:>14.4}", lifecycle.describe(), quantile_ms(&timings.total, 0.5),
+            quantile_ms(&timings.total, 0.99), quantile_ms(&timings.total, 1.0), quantile_ms(&timings.setup, 0.5),
+            quantile_ms(&timings.endpoint, 0.5));
+    }
+    let baseline = quantile_ms(&results[0].1.total, 0.5);
+    for (lifecycle, timings) in &results[1..] {
+        println!("{} adds {:+.3} ms at p50", lifecycle.describe(), quantile_ms(&timings.total, 0.5) - baseline);
+    }
+}
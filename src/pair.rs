@@ -0,0 +1,308 @@
+use crate::{quantile_ms, Cli, Summary};
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory};
+use serde_json::{json, Value};
+use std::io::ErrorKind as IoErrorKind;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time;
+
+/// How far past the handshake the paired runs start, so each has the time to
+/// receive the other's proposal before sleeping until the later one.
+const START_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Args, Debug, Clone)]
+pub struct PairArgs {
+    /// Unix socket through which this run pairs with another instance given the same path,
+    /// e.g. one built with another SDK version: both start measuring together, print each
+    /// other's interval statistics as they go and end with a side-by-side report
+    #[arg(long, conflicts_with_all = ["compare_credentials", "sweep_item_size", "sweep_attribute_count", "sweep_limit"])]
+    pair_with: Option<String>,
+
+    /// Name of this instance in the paired reports, e.g. `new-sdk` (defaults to its process id)
+    #[arg(long, requires = "pair_with")]
+    pair_label: Option<String>,
+}
+
+/// Requests, errors and latency of one interval.
+#[derive(Clone, Copy, Default)]
+struct Stats {
+    requests: usize,
+    errors: usize,
+    p50: f64,
+    p99: f64,
+}
+
+impl Stats {
+    fn to_json(self) -> Value {
+        json!({ "requests": self.requests, "errors": self.errors, "p50_ms": self.p50, "p99_ms": self.p99 })
+    }
+
+    fn from_json(value: &Value) -> Option<Stats> {
+        Some(Stats {
+            requests: value["requests"].as_u64()? as usize,
+            errors: value["errors"].as_u64()? as usize,
+            p50: value["p50_ms"].as_f64()?,
+            p99: value["p99_ms"].as_f64()?,
+        })
+    }
+}
+
+/// Requests completed in the current interval.
+#[derive(Default)]
+struct Window {
+    latencies: Vec<Duration>,
+    errors: usize,
+}
+
+impl Window {
+    fn take(&mut self) -> Stats {
+        let Window { mut latencies, errors } = std::mem::take(self);
+        latencies.sort();
+        let requests = latencies.len() + errors;
+        if latencies.is_empty() {
+            return Stats { requests, errors, ..Stats::default() };
+        }
+        Stats { requests, errors, p50: quantile_ms(&latencies, 0.5), p99: quantile_ms(&latencies, 0.99) }
+    }
+}
+
+/// Records measured requests into the current interval of a [`Session`].
+#[derive(Clone)]
+pub struct Recorder(Arc<Mutex<Window>>);
+
+impl Recorder {
+    pub fn record(&self, latency: Duration, ok: bool) {
+        let mut window = self.0.lock().unwrap();
+        if ok {
+            window.latencies.push(latency);
+        } else {
+            window.errors += 1;
+        }
+    }
+}
+
+/// A connection to the paired instance, made before the warmup so that
+/// waiting for the other instance to start doesn't delay the measured run.
+pub struct Pair {
+    stream: UnixStream,
+    label: String,
+}
+
+fn fail(message: String) -> ! {
+    Cli::command().error(ErrorKind::Io, message).exit()
+}
+
+/// Connects to the instance listening on `path`, or listens there until the
+/// other instance connects, whichever of the two comes first.
+async fn connect(path: &str) -> std::io::Result<UnixStream> {
+    loop {
+        match UnixStream::connect(path).await {
+            Ok(stream) => return Ok(stream),
+            // a socket left by an earlier run, which nothing listens on
+            Err(e) if e.kind() == IoErrorKind::ConnectionRefused => std::fs::remove_file(path)?,
+            Err(e) if e.kind() == IoErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        match UnixListener::bind(path) {
+            Ok(listener) => {
+                println!("Waiting for the paired instance to connect to {}", path);
+                let (stream, _) = listener.accept().await?;
+                std::fs::remove_file(path)?;
+                return Ok(stream);
+            }
+            // the other instance bound it in the meantime
+            Err(e) if e.kind() == IoErrorKind::AddrInUse => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+async fn send(writer: &mut OwnedWriteHalf, message: Value) -> std::io::Result<()> {
+    writer.write_all(format!("{}\n", message).as_bytes()).await
+}
+
+async fn receive(lines: &mut Lines<BufReader<OwnedReadHalf>>) -> Option<Value> {
+    let line = lines.next_line().await.ok()??;
+    serde_json::from_str(&line).ok()
+}
+
+impl Pair {
+    /// Pairs with the instance --pair-with names, if given.
+    pub async fn connect(args: &PairArgs) -> Option<Pair> {
+        let path = args.pair_with.as_ref()?;
+        let stream = connect(path).await.unwrap_or_else(|e| fail(format!("cannot pair through {}: {}", path, e)));
+        let label = args.pair_label.clone().unwrap_or_else(|| format!("pid {}", std::process::id()));
+        Some(Pair { stream, label })
+    }
+
+    /// Waits for the other instance to finish its warmup, then sleeps until
+    /// the start time both agreed on, the later of their two proposals, and
+    /// starts exchanging statistics over windows of `window`.
+    pub async fn start(self, request: &str, window: Duration) -> Session {
+        let (reader, mut writer) = self.stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let proposal = (SystemTime::now() + START_DELAY).duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        let hello = json!({ "label": self.label, "request": request, "start_at_ms": proposal });
+        if let Err(e) = send(&mut writer, hello).await {
+            fail(format!("cannot reach the paired instance: {}", e));
+        }
+        let Some(peer) = receive(&mut lines).await else {
+            fail("the paired instance disconnected before starting".to_owned())
+        };
+        let peer_label = peer["label"].as_str().unwrap_or("peer").to_owned();
+        if peer["request"].as_str() != Some(request) {
+            println!("Warning: {} runs {}, not {}", peer_label, peer["request"], request);
+        }
+        let start_at = UNIX_EPOCH + Duration::from_millis(proposal.max(peer["start_at_ms"].as_u64().unwrap_or(proposal)));
+        time::sleep(start_at.duration_since(SystemTime::now()).unwrap_or_default()).await;
+        println!("Paired with {} as {}, both starting at the same instant", peer_label, self.label);
+
+        let window_stats = Arc::new(Mutex::new(Window::default()));
+        let (finish, finished) = oneshot::channel();
+        let exchange = Exchange { label: self.label.clone(), peer_label: peer_label.clone(), ..Exchange::default() };
+        let task = tokio::spawn(exchange.run(lines, writer, window_stats.clone(), window, finished));
+        Session { label: self.label, peer_label, window: window_stats, finish, task }
+    }
+}
+
+/// Interval statistics of both instances, as exchanged during the run.
+#[derive(Default)]
+struct Exchange {
+    label: String,
+    peer_label: String,
+    own: Vec<Stats>,
+    peer: Vec<Stats>,
+    /// The other instance's summary, once it finished
+    peer_summary: Option<Value>,
+    /// Whether the other instance disconnected without a summary
+    disconnected: bool,
+}
+
+impl Exchange {
+    /// Prints interval `n` once both instances have reported it.
+    fn print_interval(&self, n: usize) {
+        if let (Some(own), Some(peer)) = (self.own.get(n), self.peer.get(n)) {
+            println!("Paired interval {}: {} p50 {:.3} p99 {:.3} ({} requests, {} errors) | {} p50 {:.3} p99 {:.3} ({} requests, {} errors)",
+                n + 1, self.label, own.p50, own.p99, own.requests, own.errors,
+                self.peer_label, peer.p50, peer.p99, peer.requests, peer.errors);
+        }
+    }
+
+    /// Handles one message from the other instance, or its disconnection.
+    fn receive(&mut self, message: Option<Value>) {
+        let Some(message) = message else {
+            self.disconnected = true;
+            return;
+        };
+        if let Some(stats) = Stats::from_json(&message["interval"]) {
+            self.peer.push(stats);
+            self.print_interval(self.peer.len() - 1);
+        } else if message.get("summary").is_some() {
+            self.peer_summary = Some(message["summary"].clone());
+        }
+    }
+
+    /// Sends this instance's statistics every `window` and records the other
+    /// instance's until `finished` delivers this run's summary; then waits
+    /// for the other instance's summary, should it still be running.
+    async fn run(mut self, mut lines: Lines<BufReader<OwnedReadHalf>>, mut writer: OwnedWriteHalf,
+        window_stats: Arc<Mutex<Window>>, window: Duration, mut finished: oneshot::Receiver<Value>) -> Exchange {
+        let mut ticker = time::interval_at(time::Instant::now() + window, window);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let stats = window_stats.lock().unwrap().take();
+                    self.own.push(stats);
+                    // a peer that is gone shows up when reading
+                    let _ = send(&mut writer, json!({ "interval": stats.to_json() })).await;
+                    self.print_interval(self.own.len() - 1);
+                }
+                message = receive(&mut lines), if !self.disconnected => {
+                    self.receive(message);
+                }
+                summary = &mut finished => {
+                    let stats = window_stats.lock().unwrap().take();
+                    if stats.requests > 0 {
+                        self.own.push(stats);
+                        let _ = send(&mut writer, json!({ "interval": stats.to_json() })).await;
+                    }
+                    let _ = send(&mut writer, json!({ "summary": summary.unwrap_or_default() })).await;
+                    break;
+                }
+            }
+        }
+        while self.peer_summary.is_none() && !self.disconnected {
+            let message = receive(&mut lines).await;
+            self.receive(message);
+        }
+        self
+    }
+}
+
+/// A paired run in progress.
+pub struct Session {
+    label: String,
+    peer_label: String,
+    window: Arc<Mutex<Window>>,
+    finish: oneshot::Sender<Value>,
+    task: JoinHandle<Exchange>,
+}
+
+impl Session {
+    pub fn recorder(&self) -> Recorder {
+        Recorder(self.window.clone())
+    }
+
+    /// Sends this run's summary, waits for the other instance's and prints
+    /// both side by side, with the intervals both measured.
+    pub async fn finish(self, summary: &Summary) {
+        let [p50, p90, p99, p999] = summary.percentiles;
+        let own = json!({
+            "requests": summary.requests,
+            "errors": summary.errors,
+            "throughput": summary.throughput,
+            "p50_ms": p50,
+            "p90_ms": p90,
+            "p99_ms": p99,
+            "p99_9_ms": p999,
+        });
+        let _ = self.finish.send(own.clone());
+        let exchange = self.task.await.expect("pair exchange task panicked");
+
+        println!("\nPaired comparison: {} (this run) vs {}", self.label, self.peer_label);
+        let Some(peer) = &exchange.peer_summary else {
+            println!("{} disconnected before finishing; no comparison", self.peer_label);
+            return;
+        };
+        println!("{:<16} {:>14} {:>14} {:>12}", "", self.label, self.peer_label, "Difference");
+        let rows = [
+            ("Requests", "requests"),
+            ("Errors", "errors"),
+            ("Throughput", "throughput"),
+            ("p50 ms", "p50_ms"),
+            ("p90 ms", "p90_ms"),
+            ("p99 ms", "p99_ms"),
+            ("p99.9 ms", "p99_9_ms"),
+        ];
+        for (row, key) in rows {
+            let (own, peer) = (own[key].as_f64().unwrap_or_default(), peer[key].as_f64().unwrap_or_default());
+            let difference = if peer > 0.0 { format!("{:+.1}%", 100.0 * (own - peer) / peer) } else { "-".to_owned() };
+            println!("{:<16} {:>14.3} {:>14.3} {:>12}", row, own, peer, difference);
+        }
+        if !exchange.own.is_empty() && !exchange.peer.is_empty() {
+            println!("\nPaired intervals (milliseconds):");
+            println!("{:<10} {:>12} {:>12} {:>12} {:>12}", "Interval",
+                format!("{} p50", self.label), format!("{} p50", self.peer_label),
+                format!("{} p99", self.label), format!("{} p99", self.peer_label));
+            for (n, (own, peer)) in exchange.own.iter().zip(&exchange.peer).enumerate() {
+                println!("{:<10} {:>12.3} {:>12.3} {:>12.3} {:>12.3}", n + 1, own.p50, peer.p50, own.p99, peer.p99);
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use crate::redact::Redactor;
+use crate::request::{item_bytes, key_string, RequestError};
+use crate::{timestamp, Cli};
+use aws_sdk_dynamodb::Client;
+use clap::Args;
+use std::collections::HashMap;
+use std::time::{Instant, SystemTime};
+
+#[derive(Args, Debug, Clone)]
+pub struct AnalyzeArgs {
+    /// Number of segments the table is split into, each scanned by its own worker
+    #[arg(short = 'g', long, default_value = "8")]
+    total_segments: i32,
+
+    /// Scan pages read per segment; each page is up to 1 MB
+    #[arg(long, default_value = "50", conflicts_with = "full_scan")]
+    sample_pages: usize,
+
+    /// Scan the whole table instead of sampling it
+    #[arg(long)]
+    full_scan: bool,
+
+    /// Partition key values listed in each ranking
+    #[arg(long, default_value = "10")]
+    top: usize,
+}
+
+/// Items and bytes seen per partition key value.
+#[derive(Default)]
+struct Sample {
+    pages: usize,
+    partitions: HashMap<String, (usize, usize)>,
+    /// Whether the segment was read to its end
+    complete: bool,
+    error: Option<RequestError>,
+}
+
+async fn sample_segment(client: Client, table: String, partition_key: String, segment: i32, args: AnalyzeArgs) -> Sample {
+    let mut sample = Sample::default();
+    let mut exclusive_start_key = None;
+    loop {
+        let resp = client.scan()
+            .table_name(&table)
+            .segment(segment)
+            .total_segments(args.total_segments)
+            .set_exclusive_start_key(exclusive_start_key)
+            .send()
+            .await;
+        let resp = match resp {
+            Ok(resp) => resp,
+            Err(e) => {
+                sample.error = Some(e.into());
+                return sample;
+            }
+        };
+        sample.pages += 1;
+        for item in resp.items() {
+            let Some(value) = item.get(&partition_key) else { continue };
+            let (items, bytes) = sample.partitions.entry(key_string(value)).or_default();
+            *items += 1;
+            *bytes += item_bytes(item);
+        }
+        exclusive_start_key = resp.last_evaluated_key;
+        if exclusive_start_key.is_none() {
+            sample.complete = true;
+            return sample;
+        }
+        if !args.full_scan && sample.pages >= args.sample_pages {
+            return sample;
+        }
+    }
+}
+
+/// Samples --table with a parallel Scan and ranks the partition key values
+/// seen by item count and by size, to find candidates for hot-partition
+/// benchmarks. Scan returns each partition's items together, so only the
+/// partitions at the edges of a sampled stretch are undercounted.
+pub async fn run(client: &Client, cli: &Cli, args: &AnalyzeArgs) {
+    let extent = if args.full_scan { "all".to_owned() } else { format!("up to {}", args.sample_pages) };
+    println!("Sampling {} with {} segments, {} pages each", cli.table, args.total_segments, extent);
+    let started_at = SystemTime::now();
+    println!("Scan started at {}", timestamp::both(started_at, cli.timezone));
+    let start = Instant::now();
+    let workers: Vec<_> = (0..args.total_segments).map(|segment| {
+        tokio::spawn(sample_segment(client.clone(), cli.table.clone(), cli.partition_key.clone(), segment, args.clone()))
+    }).collect();
+    let mut partitions: HashMap<String, (usize, usize)> = HashMap::new();
+    let (mut pages, mut complete) = (0, 0);
+    for (segment, worker) in workers.into_iter().enumerate() {
+        let sample = worker.await.unwrap();
+        if let Some(e) = &sample.error {
+            println!("Segment {} stopped on error: {}", segment, e);
+        }
+        pages += sample.pages;
+        complete += sample.complete as usize;
+        for (value, (items, bytes)) in sample.partitions {
+            let entry = partitions.entry(value).or_default();
+            entry.0 += items;
+            entry.1 += bytes;
+        }
+    }
+
+    let items: usize = partitions.values().map(|(items, _)| items).sum();
+    let bytes: usize = partitions.values().map(|(_, bytes)| bytes).sum();
+    println!("\nRead {} pages in {:.3}s: {} items, {:.3} MB, {} partition key values; {} of {} segments read to the end",
+        pages, start.elapsed().as_secs_f64(), items, bytes as f64 / 1_000_000.0, partitions.len(), complete, args.total_segments);
+    if partitions.is_empty() {
+        return;
+    }
+    println!("Per partition: {:.1} items, {:.1} KB on average", items as f64 / partitions.len() as f64,
+        bytes as f64 / partitions.len() as f64 / 1000.0);
+
+    let redact = Redactor::new(cli.redact_keys);
+    let mut ranked: Vec<_> = partitions.iter().collect();
+    for (title, by_bytes) in [("Most items", false), ("Largest", true)] {
+        ranked.sort_by(|(a_value, (a_items, a_bytes)), (b_value, (b_items, b_bytes))| {
+            let (a, b) = if by_bytes { (a_bytes, b_bytes) } else { (a_items, b_items) };
+            b.cmp(a).then_with(|| a_value.cmp(b_value))
+        });
+        println!("\n{} ({}):", title, cli.partition_key);
+        println!("{:>4} {:>10} {:>12} {:>8}  Partition value", "Rank", "Items", "KB", "Share");
+        for (rank, (value, (count, size))) in ranked.iter().take(args.top).enumerate() {
+            let share = if by_bytes { *size as f64 / bytes as f64 } else { *count as f64 / items as f64 };
+            println!("{}", row(rank + 1, value, (*count, *size), share, redact));
+        }
+    }
+    if !args.full_scan {
+        println!("\nCounts cover the sampled pages only; pass --full-scan for exact ones");
+    }
+}
+
+/// A ranking line: a partition value, with its items, kilobytes and share.
+fn row(rank: usize, value: &str, (items, bytes): (usize, usize), share: f64, redact: Redactor) -> String {
+    format!("{:>4} {:>10} {:>12.1} {:>7.2}%  {}", rank, items, bytes as f64 / 1000.0, share * 100.0, redact.value(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::hash_key;
+
+    #[test]
+    fn rows_redact_partition_values() {
+        let row = |redact| row(1, "customer#42", (3, 1500), 0.25, Redactor::new(redact));
+        assert_eq!(row(false), "   1          3          1.5   25.00%  customer#42");
+        assert!(!row(true).contains("customer#42"));
+        assert!(row(true).ends_with(&format!("<{}>", hash_key("customer#42"))));
+    }
+}
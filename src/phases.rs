@@ -0,0 +1,148 @@
+use crate::{config, describe, hooks, run_command, timestamp, Cli, Commands};
+use aws_sdk_dynamodb::Client;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{Args, CommandFactory, FromArgMatches};
+use serde_json::json;
+use std::fs::File;
+use std::io::Write;
+use std::time::{Instant, SystemTime};
+
+#[derive(Args, Debug, Clone)]
+pub struct PhasesArgs {
+    /// YAML or TOML file whose `phases` list holds the phases to run, each a mapping of
+    /// its `name`, one subcommand with its settings and optional hooks (see README)
+    #[arg(long)]
+    config: String,
+
+    /// Describe the table before the first phase and after each one, and report its item
+    /// count, size and the capacity the phases consumed so far
+    #[arg(long)]
+    snapshot_table: bool,
+
+    /// Also append each table snapshot to this file as a JSON line, written as it is taken,
+    /// so the snapshots of an aborted run are kept
+    #[arg(long, requires = "snapshot_table")]
+    snapshot_file: Option<String>,
+}
+
+/// Table state between phases, for --snapshot-table.
+struct Snapshots {
+    file: Option<File>,
+    table: String,
+    /// Item count and size of the previous snapshot
+    previous: Option<(i64, i64)>,
+    /// Capacity units consumed by the phases so far
+    read_units: f64,
+    write_units: f64,
+}
+
+impl Snapshots {
+    /// Describes the table after `phase` (before the first one, for None),
+    /// prints how it changed and appends the snapshot to --snapshot-file.
+    async fn take(&mut self, client: &Client, phase: Option<&str>) {
+        let label = phase.map_or("before the first phase".to_owned(), |name| format!("after {}", name));
+        let table = match describe::snapshot(client, &self.table).await {
+            Ok(table) => table,
+            Err(e) => {
+                println!("\nCannot describe the table {}: {}", label, e);
+                return;
+            }
+        };
+        let (items, bytes) = (table["item_count"].as_i64().unwrap_or_default(), table["size_bytes"].as_i64().unwrap_or_default());
+        let change = self.previous.map_or(String::new(), |(previous_items, previous_bytes)| {
+            format!(" ({:+} items, {:+.3} MB)", items - previous_items, (bytes - previous_bytes) as f64 / 1_000_000.0)
+        });
+        println!("\nTable {}: {} items, {:.3} MB{}, estimated; {:.1} RCU and {:.1} WCU consumed so far",
+            label, items, bytes as f64 / 1_000_000.0, change, self.read_units, self.write_units);
+        self.previous = Some((items, bytes));
+        if let Some(file) = &mut self.file {
+            let line = json!({
+                "phase": phase,
+                "read_units_consumed": self.read_units,
+                "write_units_consumed": self.write_units,
+                "table": table,
+            });
+            writeln!(file, "{}", line).and_then(|_| file.flush()).expect("failed to write table snapshot");
+        }
+    }
+}
+
+/// Runs the phases of a config file in order, each with its own report, as
+/// one invocation instead of a shell script chaining runs, then lists what
+/// each took. Every phase is parsed before the first one starts, so a
+/// mistake in a later phase doesn't surface halfway through; a phase that
+/// fails stops the run. With --snapshot-table, the table is described around
+/// the phases, to document their effect on its state. The `before` and
+/// `after` hooks of a phase run outside its measured time.
+pub async fn run(client: &Client, config: &aws_config::SdkConfig, args: &PhasesArgs) {
+    let phases: Vec<_> = config::phases(&std::env::args().collect::<Vec<_>>(), &args.config).into_iter()
+        .map(|(name, phase_args, settings)| {
+            let matches = Cli::command().get_matches_from(phase_args);
+            let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+            // cold-start reruns its own command line in child processes
+            if matches!(cli.command, Commands::Phases(_) | Commands::Schema(_) | Commands::DiffRuns(_) | Commands::ColdStart(_) | Commands::ColdStartInvocation(_)) {
+                Cli::command().error(ErrorKind::InvalidValue,
+                    format!("phase {:?} runs `{}`, which can't run as a phase", name, matches.subcommand_name().unwrap())).exit();
+            }
+            let hooks = hooks::parse(&name, &settings);
+            (name, matches, cli, hooks)
+        })
+        .collect();
+
+    let mut snapshots = args.snapshot_table.then(|| Snapshots {
+        file: args.snapshot_file.as_ref().map(|path| File::create(path).unwrap_or_else(|e| panic!("cannot create {}: {}", path, e))),
+        table: phases[0].2.table.clone(),
+        previous: None,
+        read_units: 0.0,
+        write_units: 0.0,
+    });
+    if let Some(snapshots) = &mut snapshots {
+        snapshots.take(client, None).await;
+    }
+
+    let (total, start) = (phases.len(), Instant::now());
+    let mut results = Vec::new();
+    for (n, (name, matches, mut cli, hooks)) in phases.into_iter().enumerate() {
+        let subcommand = matches.subcommand_name().unwrap().to_owned();
+        println!("\n##### Phase {}/{}: {} ({}) #####", n + 1, total, name, subcommand);
+        let mut hook_time = hooks.run(&name, false).await;
+        println!("Phase started at {}", timestamp::both(SystemTime::now(), cli.timezone));
+        let phase_start = Instant::now();
+        // the table doesn't exist yet, or isn't the one requests go to
+        if !matches!(cli.command, Commands::CreateTable(_) | Commands::Selftest(_)) {
+            let explicit = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+            describe::detect_keys(client, &mut cli, explicit).await;
+        }
+        let summary = run_command(client, config, &cli).await;
+        let elapsed = phase_start.elapsed();
+        if let Some(snapshots) = &mut snapshots {
+            if let Some(summary) = &summary {
+                snapshots.read_units += summary.read_units;
+                snapshots.write_units += summary.write_units;
+            }
+            snapshots.take(client, Some(&name)).await;
+        }
+        hook_time += hooks.run(&name, true).await;
+        results.push((name, subcommand, elapsed, hook_time, summary));
+    }
+
+    println!("\nPhases ({} in {:.1}s):", results.len(), start.elapsed().as_secs_f64());
+    println!("{:<24} {:<20} {:>10} {:>10} {:>10} {:>8} {:>10} {:>10} {:>10}",
+        "Phase", "Command", "Seconds", "Hook s", "Requests", "Errors", "p50 ms", "p99 ms", "QPS");
+    for (name, subcommand, elapsed, hook_time, summary) in &results {
+        // only single benchmark runs have a summary to list
+        let cells = match summary {
+            Some(summary) => [
+                summary.requests.to_string(),
+                summary.errors.to_string(),
+                format!("{:.2}", summary.percentiles[0]),
+                format!("{:.2}", summary.percentiles[2]),
+                format!("{:.1}", summary.throughput),
+            ],
+            None => ["-"; 5].map(str::to_owned),
+        };
+        println!("{:<24} {:<20} {:>10.1} {:>10.1} {:>10} {:>8} {:>10} {:>10} {:>10}",
+            name, subcommand, elapsed.as_secs_f64(), hook_time.as_secs_f64(), cells[0], cells[1], cells[2], cells[3], cells[4]);
+    }
+}
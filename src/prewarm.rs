@@ -0,0 +1,192 @@
+use crate::request::{Request, RequestError, Response};
+use crate::seed::{self, MARKER};
+use crate::{keygen, throttle, timestamp, workload, Cli};
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::Client;
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::{self, Instant};
+
+/// A step whose throttled share stays under this doubles the rate of the next...
+const MAX_THROTTLE_SHARE: f64 = 0.01;
+/// ...by this factor, capped at --target-qps.
+const RAMP_FACTOR: u64 = 2;
+
+#[derive(Args, Debug, Clone)]
+pub struct PrewarmArgs {
+    /// Request rate to sustain without throttling, each request writing or reading one item
+    #[arg(long)]
+    target_qps: u64,
+
+    /// Rate of the first step, doubled by every step that is hardly throttled
+    #[arg(long, default_value = "1000")]
+    start_qps: u64,
+
+    /// Length of each step at a fixed rate
+    #[arg(long, default_value = "60")]
+    step_secs: u64,
+
+    /// Stop once the target rate ran unthrottled this long
+    #[arg(long, default_value = "120")]
+    hold_secs: u64,
+
+    /// Give up after this long
+    #[arg(long, default_value = "1800")]
+    max_secs: u64,
+
+    /// Share of requests that GetItem an item written earlier instead of writing a new one
+    #[arg(long, default_value = "0")]
+    read_ratio: f64,
+
+    /// Size in bytes of each written item's JSON payload attribute
+    #[arg(long, default_value = "1000")]
+    item_size: usize,
+
+    /// Prefix of the partition values of S partition keys, followed by the item number
+    #[arg(long, default_value = "prewarm#")]
+    key_prefix: String,
+
+    /// Maximum concurrent requests
+    #[arg(short = 'k', long, default_value = "256")]
+    parallelism: usize,
+
+    /// Refuse to prewarm tables carrying this tag, as `key=value`, repeatable
+    #[arg(long, value_parser = crate::parse_assignment, default_value = "env=production")]
+    protect_tag: Vec<(String, String)>,
+
+    /// Prewarm even tables matching --protect-tag
+    #[arg(long)]
+    allow_protected_table: bool,
+}
+
+/// What the requests of one step came to.
+#[derive(Default)]
+struct Step {
+    requests: usize,
+    throttled: usize,
+    errors: usize,
+}
+
+impl Step {
+    fn record(&mut self, result: &Result<Response, RequestError>) {
+        self.requests += 1;
+        match result {
+            Err(e) if throttle::is_throttle(e.code.as_deref()) => self.throttled += 1,
+            Err(_) => self.errors += 1,
+            Ok(_) => {}
+        }
+    }
+
+    fn throttled_share(&self) -> f64 {
+        self.throttled as f64 / self.requests.max(1) as f64
+    }
+}
+
+/// The key of the `n`th item of the run, numbered from `base`; each gets a partition of its own.
+fn key(cli: &Cli, args: &PrewarmArgs, base: u64, n: u64) -> HashMap<String, AttributeValue> {
+    let mut key = HashMap::from([(cli.partition_key.clone(), seed::numbered(cli.partition_type, &args.key_prefix, base + n, 0))]);
+    if !cli.no_sort_key {
+        key.insert(cli.sort_key.clone(), seed::numbered(cli.sort_type, "", 0, 0));
+    }
+    key
+}
+
+/// Ramps a write (and read) load on --table up to --target-qps, doubling
+/// the rate every --step-secs while DynamoDB keeps up, so that a new
+/// table's partitions split before a benchmark measures it. Each item gets
+/// a partition of its own and carries the `seed` marker, so `cleanup`
+/// removes them.
+pub async fn run(client: &Client, cli: &Cli, args: &PrewarmArgs) {
+    if args.target_qps == 0 || args.start_qps == 0 || args.step_secs == 0 || args.parallelism == 0 {
+        Cli::command().error(ErrorKind::ValueValidation,
+            "--target-qps, --start-qps, --step-secs and --parallelism must be positive").exit();
+    }
+    if !(0.0..1.0).contains(&args.read_ratio) {
+        Cli::command().error(ErrorKind::ValueValidation, "--read-ratio must be at least 0 and below 1").exit();
+    }
+    if !args.allow_protected_table {
+        seed::refuse_protected(client, cli, &args.protect_tag, "prewarm it").await;
+    }
+
+    let started_at = SystemTime::now();
+    let marker = timestamp::TimeFormat::Rfc3339.format(started_at);
+    // numbered from the start time, so reruns and seeded items aren't overwritten
+    let base = started_at.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64 * 1_000_000;
+    println!("Prewarming {} up to {} QPS ({:.0}% reads) in {}s steps from {} QPS, until {}s unthrottled at the target",
+        cli.table, args.target_qps, args.read_ratio * 100.0, args.step_secs, args.start_qps.min(args.target_qps), args.hold_secs);
+    println!("Prewarm started at {}", timestamp::both(started_at, cli.timezone));
+
+    let put = client.put_item().table_name(&cli.table);
+    let attributes = HashMap::from([
+        ("payload".to_owned(), AttributeValue::S(workload::json_payload(args.item_size))),
+        (MARKER.to_owned(), AttributeValue::S(marker.clone())),
+    ]);
+    let get = client.get_item().table_name(&cli.table);
+    let semaphore = Arc::new(Semaphore::new(args.parallelism));
+    let start = Instant::now();
+    let mut rate = args.start_qps.min(args.target_qps);
+    let (mut written, mut i) = (0, 0);
+    let mut unthrottled_since = None;
+    let mut last_throttled = None;
+
+    println!("\n{:>8} {:>10} {:>10} {:>10} {:>8}", "Seconds", "Target", "Achieved", "Throttled", "Errors");
+    let outcome = loop {
+        if start.elapsed() >= Duration::from_secs(args.max_secs) {
+            break format!("gave up after {}s", args.max_secs);
+        }
+        let step_start = Instant::now();
+        let mut ticker = time::interval(Duration::from_secs_f64(1.0 / rate as f64));
+        let mut tasks = JoinSet::new();
+        while step_start.elapsed() < Duration::from_secs(args.step_secs) {
+            ticker.tick().await;
+            let permit = semaphore.clone().acquire_owned().await.unwrap();
+            let request = if written > 0 && workload::is_picked(i, args.read_ratio) {
+                let n = (keygen::uniform(i) * written as f64) as u64;
+                Request::GetItem(Box::new(get.clone().set_key(Some(key(cli, args, base, n)))))
+            } else {
+                let mut item = key(cli, args, base, written);
+                item.extend(attributes.clone());
+                written += 1;
+                Request::PutItem(Box::new(put.clone().set_item(Some(item))))
+            };
+            i += 1;
+            tasks.spawn(async move {
+                let result = request.send().await;
+                drop(permit);
+                result
+            });
+        }
+        let mut step = Step::default();
+        while let Some(result) = tasks.join_next().await {
+            step.record(&result.expect("prewarm request task panicked"));
+        }
+        let elapsed = step_start.elapsed();
+        println!("{:>8.0} {:>10} {:>10.1} {:>9.2}% {:>8}", start.elapsed().as_secs_f64(), rate,
+            step.requests as f64 / elapsed.as_secs_f64(), step.throttled_share() * 100.0, step.errors);
+
+        if step.throttled > 0 {
+            last_throttled = Some(start.elapsed());
+            unthrottled_since = None;
+        } else if rate == args.target_qps {
+            let since = *unthrottled_since.get_or_insert(start.elapsed() - elapsed);
+            if start.elapsed() - since >= Duration::from_secs(args.hold_secs) {
+                break format!("held {} QPS unthrottled for {:.0}s", rate, (start.elapsed() - since).as_secs_f64());
+            }
+        }
+        if step.throttled_share() < MAX_THROTTLE_SHARE {
+            rate = (rate * RAMP_FACTOR).min(args.target_qps);
+        }
+    };
+
+    println!("\nPrewarm {} after {:.0}s, {} items written", outcome, start.elapsed().as_secs_f64(), written);
+    match last_throttled {
+        Some(at) => println!("Last throttled step ended {:.0}s into the prewarm", at.as_secs_f64()),
+        None => println!("No request was throttled"),
+    }
+    println!("Items are marked with {} = {}; remove them with `cleanup --seeded-at {}`", MARKER, marker, marker);
+}
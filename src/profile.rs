@@ -0,0 +1,62 @@
+use serde_json::{json, Value};
+
+/// Builds the settings of a profile.
+type Settings = fn() -> Value;
+
+/// Built-in settings for the standard Chalk online-store table layouts,
+/// selected with `bench --chalk-profile <name>`. Each is a settings mapping
+/// shaped like a --config file; a config file and command-line flags both
+/// take precedence over it.
+const PROFILES: &[(&str, Settings)] = &[
+    ("online-store", online_store),
+    ("online-store-batch", online_store_batch),
+    ("windowed-aggregation", windowed_aggregation),
+];
+
+pub fn names() -> impl Iterator<Item = &'static str> {
+    PROFILES.iter().map(|(name, _)| *name)
+}
+
+pub fn settings(name: &str) -> Option<serde_json::Map<String, Value>> {
+    PROFILES.iter().find(|(n, _)| *n == name).map(|(_, settings)| match settings() {
+        Value::Object(settings) => settings,
+        _ => unreachable!("profiles are mappings"),
+    })
+}
+
+/// Feature values keyed by entity id (`__id__`) and namespace (`__ns__`),
+/// read one entity at a time as online queries do.
+fn online_store() -> Value {
+    json!({
+        "partition-key": "__id__",
+        "sort-key": "__ns__",
+        "bench": {
+            "operation": "get-item",
+        },
+    })
+}
+
+/// The online-store layout read the way bulk resolvers fetch many entities at once.
+fn online_store_batch() -> Value {
+    json!({
+        "partition-key": "__id__",
+        "sort-key": "__ns__",
+        "bench": {
+            "operation": "batch-get-item",
+            "batch-size": 100,
+        },
+    })
+}
+
+/// Windowed aggregations: pre-aggregated buckets under one partition, with the
+/// bucket start (see `show-mapping` for bucket durations) as the sort key. A
+/// request reads a whole window of buckets, every page of it.
+fn windowed_aggregation() -> Value {
+    json!({
+        "partition-key": "__id__",
+        "sort-key": "__ns__",
+        "bench": {
+            "custom": "aggregate-range",
+        },
+    })
+}
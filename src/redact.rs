@@ -0,0 +1,125 @@
+use crate::request::hash_key;
+
+/// Replaces partition and sort key values with their hashes in anything we
+/// print or write, so benchmark artifacts can be shared outside the team.
+/// Values are redacted where they are printed, from the request they belong
+/// to, so generated and scripted keys are covered as well as given ones.
+#[derive(Clone, Copy)]
+pub struct Redactor {
+    enabled: bool,
+}
+
+/// A value printed with `{:?}` that holds key values.
+pub trait Redact {
+    fn redacted(&self) -> Self;
+}
+
+impl Redact for String {
+    fn redacted(&self) -> Self {
+        format!("<{}>", hash_key(self))
+    }
+}
+
+impl<T: Redact> Redact for Option<T> {
+    fn redacted(&self) -> Self {
+        self.as_ref().map(Redact::redacted)
+    }
+}
+
+impl<T: Redact> Redact for Vec<T> {
+    fn redacted(&self) -> Self {
+        self.iter().map(Redact::redacted).collect()
+    }
+}
+
+impl Redactor {
+    /// A redactor, or a no-op one when `enabled` is false.
+    pub fn new(enabled: bool) -> Self {
+        Redactor { enabled }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// `value`, or its hash.
+    pub fn value(&self, value: &str) -> String {
+        if self.enabled && !value.is_empty() { value.to_owned().redacted() } else { value.to_owned() }
+    }
+
+    /// Formats `value` with `{:?}`, its key values hashed.
+    pub fn debug<T: Redact + std::fmt::Debug>(&self, value: &T) -> String {
+        if self.enabled { format!("{:?}", value.redacted()) } else { format!("{:?}", value) }
+    }
+
+    /// Replaces each of `values`, the key values of the request `text` is
+    /// about (see [`crate::request::Request::key_values`]), occurring in
+    /// `text` as a whole token, i.e. not directly preceded or followed by an
+    /// alphanumeric character.
+    pub fn text(&self, text: &str, values: &[String]) -> String {
+        if !self.enabled {
+            return text.to_owned();
+        }
+        // longest first, so that values containing other values are replaced whole
+        let mut values: Vec<&String> = values.iter().filter(|value| !value.is_empty()).collect();
+        values.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        values.dedup();
+        values.into_iter().fold(text.to_owned(), |text, value| replace_token(&text, value, &value.redacted()))
+    }
+}
+
+fn replace_token(text: &str, value: &str, replacement: &str) -> String {
+    // every position would match an empty value
+    if value.is_empty() {
+        return text.to_owned();
+    }
+    let is_word = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find(value) {
+        let (before, after) = (&rest[..i], &rest[i + value.len()..]);
+        let boundary = !is_word(before.chars().next_back()) && !is_word(after.chars().next());
+        // a token boundary must also hold relative to what we've already emitted
+        let boundary = boundary && !(before.is_empty() && is_word(out.chars().next_back()));
+        out.push_str(before);
+        out.push_str(if boundary { replacement } else { value });
+        rest = after;
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_whole_tokens_only() {
+        assert_eq!(replace_token("key abc failed", "abc", "X"), "key X failed");
+        assert_eq!(replace_token("abcd abc xabc", "abc", "X"), "abcd X xabc");
+        assert_eq!(replace_token("abc", "abc", "X"), "X");
+        assert_eq!(replace_token("abc/abc", "abc", "X"), "X/X");
+        assert_eq!(replace_token("aabc", "abc", "X"), "aabc");
+    }
+
+    #[test]
+    fn empty_value_is_left_alone() {
+        assert_eq!(replace_token("some text", "", "X"), "some text");
+    }
+
+    #[test]
+    fn redacts_longest_values_first() {
+        let redact = Redactor::new(true);
+        let values = ["user".to_owned(), "user#1".to_owned()];
+        let text = redact.text("no user#1 for user", &values);
+        assert_eq!(text, format!("no <{}> for <{}>", hash_key("user#1"), hash_key("user")));
+    }
+
+    #[test]
+    fn disabled_leaves_values() {
+        let redact = Redactor::new(false);
+        assert_eq!(redact.text("user#1", &["user#1".to_owned()]), "user#1");
+        assert_eq!(redact.debug(&Some("user#1".to_owned())), "Some(\"user#1\")");
+        assert_eq!(redact.value("user#1"), "user#1");
+    }
+}
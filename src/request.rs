@@ -0,0 +1,870 @@
+use aws_sdk_dynamodb::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
+use aws_sdk_dynamodb::operation::execute_statement::builders::ExecuteStatementFluentBuilder;
+use aws_sdk_dynamodb::operation::get_item::builders::GetItemFluentBuilder;
+use aws_sdk_dynamodb::operation::put_item::builders::PutItemFluentBuilder;
+use aws_sdk_dynamodb::operation::query::builders::QueryFluentBuilder;
+use aws_sdk_dynamodb::operation::update_item::builders::UpdateItemFluentBuilder;
+use aws_sdk_dynamodb::operation::transact_get_items::TransactGetItemsError;
+use aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError;
+use aws_sdk_dynamodb::operation::RequestId;
+use aws_sdk_dynamodb::types::{
+    AttributeValue, BatchStatementRequest, CancellationReason, ConsumedCapacity, KeysAndAttributes,
+    ReturnConsumedCapacity, ScalarAttributeType, Select, TransactGetItem, TransactWriteItem, WriteRequest,
+};
+use aws_sdk_dynamodb::primitives::Blob;
+use aws_sdk_dynamodb::Client;
+use clap::ValueEnum;
+use crate::custom::{self, CustomRequest};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A fully built benchmark request, ready to be cloned and sent.
+#[derive(Clone)]
+pub enum Request {
+    Query(Box<QueryFluentBuilder>),
+    GetItem(Box<GetItemFluentBuilder>),
+    PutItem(Box<PutItemFluentBuilder>),
+    UpdateItem(Box<UpdateItemFluentBuilder>),
+    /// BatchGetItem, re-requesting UnprocessedKeys until everything is read
+    BatchGetItem {
+        client: Client,
+        request_items: HashMap<String, KeysAndAttributes>,
+    },
+    /// BatchWriteItem, re-sending UnprocessedItems until everything is written
+    BatchWriteItem {
+        client: Client,
+        request_items: HashMap<String, Vec<WriteRequest>>,
+    },
+    TransactWriteItems {
+        client: Client,
+        items: Vec<TransactWriteItem>,
+    },
+    TransactGetItems {
+        client: Client,
+        items: Vec<TransactGetItem>,
+    },
+    /// PartiQL ExecuteStatement (first page only, like Query)
+    ExecuteStatement(Box<ExecuteStatementFluentBuilder>),
+    BatchExecuteStatement {
+        client: Client,
+        statements: Vec<BatchStatementRequest>,
+    },
+    /// A read followed by a write of the same item, timed as one operation
+    ReadModifyWrite(Box<Request>, Box<Request>),
+    /// An operation shape from [`crate::custom`]
+    Custom(Arc<dyn CustomRequest>),
+}
+
+/// How much detail requests ask for about the capacity they consume.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CapacityDetail {
+    /// The total per request
+    Total,
+    /// The total split over the table and each index the request touched
+    Indexes,
+}
+
+/// Whether requests ask for their capacity per index, see [`set_capacity_detail`].
+static INDEX_CAPACITY: AtomicBool = AtomicBool::new(false);
+
+/// Sets the detail of the capacity every request asks for from here on.
+pub fn set_capacity_detail(detail: CapacityDetail) {
+    INDEX_CAPACITY.store(detail == CapacityDetail::Indexes, Ordering::Relaxed);
+}
+
+/// The ReturnConsumedCapacity every request is sent with.
+pub fn return_capacity() -> ReturnConsumedCapacity {
+    if INDEX_CAPACITY.load(Ordering::Relaxed) { ReturnConsumedCapacity::Indexes } else { ReturnConsumedCapacity::Total }
+}
+
+/// Follow-up batch calls made for UnprocessedKeys/UnprocessedItems before giving up.
+const MAX_UNPROCESSED_RETRIES: usize = 10;
+
+/// What we keep from a successful response.
+#[derive(Debug, Default)]
+pub struct Response {
+    /// Number of items returned (or written)
+    pub items: usize,
+    /// Approximate size of the returned items, using DynamoDB's item size rules
+    pub bytes: usize,
+    /// Keys or items DynamoDB handed back as unprocessed, summed over all attempts
+    pub unprocessed: usize,
+    /// Follow-up calls made to drain unprocessed keys
+    pub retries: usize,
+    /// Statements of a BatchExecuteStatement that failed individually
+    pub failed: usize,
+    /// Capacity units consumed, summed over all calls
+    pub read_units: f64,
+    pub write_units: f64,
+    /// Latency of each page, for requests that follow every page of their result
+    pub pages: Vec<Duration>,
+    /// Read and write units by the table (under its name) and each index, with
+    /// --consumed-capacity indexes
+    pub index_units: HashMap<String, (f64, f64)>,
+}
+
+impl Response {
+    /// Adds the capacity DynamoDB reports consumed. Responses don't always
+    /// split the total into reads and writes, so an unsplit total counts as
+    /// writes for writing operations and as reads otherwise.
+    pub fn add_capacity<'a>(&mut self, capacity: impl IntoIterator<Item = &'a ConsumedCapacity>, write: bool) {
+        let split = |total: Option<f64>, reads: Option<f64>, writes: Option<f64>| match (reads, writes) {
+            (None, None) if write => (0.0, total.unwrap_or(0.0)),
+            (None, None) => (total.unwrap_or(0.0), 0.0),
+            (reads, writes) => (reads.unwrap_or(0.0), writes.unwrap_or(0.0)),
+        };
+        for capacity in capacity {
+            let (reads, writes) = split(capacity.capacity_units(), capacity.read_capacity_units(), capacity.write_capacity_units());
+            self.read_units += reads;
+            self.write_units += writes;
+            let table = capacity.table().map(|table| (capacity.table_name().unwrap_or("table"), table));
+            let indexes = capacity.global_secondary_indexes().into_iter().flatten()
+                .chain(capacity.local_secondary_indexes().into_iter().flatten())
+                .map(|(name, index)| (name.as_str(), index));
+            for (name, units) in table.into_iter().chain(indexes) {
+                let (reads, writes) = split(units.capacity_units(), units.read_capacity_units(), units.write_capacity_units());
+                let entry = self.index_units.entry(name.to_owned()).or_default();
+                entry.0 += reads;
+                entry.1 += writes;
+            }
+        }
+    }
+
+    /// Adds the capacity `other` consumed, for responses made of several calls.
+    pub fn add_units(&mut self, other: &Response) {
+        self.read_units += other.read_units;
+        self.write_units += other.write_units;
+        for (name, (reads, writes)) in &other.index_units {
+            let entry = self.index_units.entry(name.clone()).or_default();
+            entry.0 += reads;
+            entry.1 += writes;
+        }
+    }
+}
+
+/// Operation-agnostic view of a failed request.
+#[derive(Debug)]
+pub struct RequestError {
+    /// DynamoDB error code, e.g. `ProvisionedThroughputExceededException`
+    pub code: Option<String>,
+    pub request_id: Option<String>,
+    /// Full error chain
+    pub message: String,
+    /// Per-item cancellation reason codes of a TransactionCanceledException
+    pub cancellation_reasons: Vec<String>,
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        if let Some(request_id) = &self.request_id {
+            write!(f, " (request id {})", request_id)?;
+        }
+        Ok(())
+    }
+}
+
+impl<E, R> From<SdkError<E, R>> for RequestError
+where
+    E: ProvideErrorMetadata + Error + 'static,
+    R: fmt::Debug,
+    SdkError<E, R>: RequestId,
+{
+    fn from(err: SdkError<E, R>) -> Self {
+        RequestError {
+            code: err.code().map(str::to_owned),
+            request_id: err.request_id().map(str::to_owned),
+            message: DisplayErrorContext(&err).to_string(),
+            cancellation_reasons: Vec::new(),
+        }
+    }
+}
+
+/// Approximate DynamoDB size of an attribute value in bytes.
+pub fn attribute_bytes(value: &AttributeValue) -> usize {
+    match value {
+        AttributeValue::S(s) | AttributeValue::N(s) => s.len(),
+        AttributeValue::B(b) => b.as_ref().len(),
+        AttributeValue::Ss(ss) | AttributeValue::Ns(ss) => ss.iter().map(String::len).sum(),
+        AttributeValue::Bs(bs) => bs.iter().map(|b| b.as_ref().len()).sum(),
+        AttributeValue::L(l) => 3 + l.iter().map(|v| 1 + attribute_bytes(v)).sum::<usize>(),
+        AttributeValue::M(m) => 3 + m.iter().map(|(k, v)| 1 + k.len() + attribute_bytes(v)).sum::<usize>(),
+        _ => 1,
+    }
+}
+
+/// Approximate DynamoDB size of an item in bytes.
+pub fn item_bytes(item: &HashMap<String, AttributeValue>) -> usize {
+    item.iter().map(|(k, v)| k.len() + attribute_bytes(v)).sum()
+}
+
+/// Renders a key attribute as plain text.
+pub fn key_string(value: &AttributeValue) -> String {
+    match value {
+        AttributeValue::S(s) | AttributeValue::N(s) => s.clone(),
+        AttributeValue::B(b) => b.as_ref().iter().map(|byte| format!("{:02x}", byte)).collect(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Attribute type of a table's partition or sort key.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyType {
+    /// String
+    S,
+    /// Number, compared numerically (e.g. epoch-millis timestamps)
+    N,
+    /// Binary, given in hex
+    B,
+}
+
+impl KeyType {
+    /// Parses a key value as given on the command line: numbers for N, hex for B.
+    pub fn parse(self, value: &str) -> Result<AttributeValue, String> {
+        match self {
+            KeyType::S => Ok(AttributeValue::S(value.to_owned())),
+            KeyType::N => value.parse::<f64>().ok().filter(|n| n.is_finite())
+                .map(|_| AttributeValue::N(value.to_owned()))
+                .ok_or_else(|| format!("{:?} is not a number, as N keys need", value)),
+            KeyType::B => {
+                let bytes = (0..value.len()).step_by(2)
+                    .map(|i| value.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+                    .collect::<Option<Vec<u8>>>();
+                bytes.filter(|bytes| !bytes.is_empty())
+                    .map(|bytes| AttributeValue::B(Blob::new(bytes)))
+                    .ok_or_else(|| format!("{:?} is not hex, as B keys need", value))
+            }
+        }
+    }
+
+    /// The key value for `value`, which is expected to have been validated
+    /// with [`KeyType::parse`].
+    pub fn value(self, value: &str) -> AttributeValue {
+        self.parse(value).unwrap_or_else(|e| panic!("invalid key value: {}", e))
+    }
+
+    /// [`KeyType::value`] of an owned value, moved into the attribute instead
+    /// of copied; expected to have been validated as well.
+    pub fn into_value(self, value: String) -> AttributeValue {
+        match self {
+            KeyType::S => AttributeValue::S(value),
+            KeyType::N => AttributeValue::N(value),
+            KeyType::B => self.value(&value),
+        }
+    }
+
+    /// The key type of a declared attribute type.
+    pub fn of(scalar: &ScalarAttributeType) -> Option<KeyType> {
+        match scalar {
+            ScalarAttributeType::S => Some(KeyType::S),
+            ScalarAttributeType::N => Some(KeyType::N),
+            ScalarAttributeType::B => Some(KeyType::B),
+            _ => None,
+        }
+    }
+
+    /// The attribute type to declare for a key of this type.
+    pub fn scalar(self) -> ScalarAttributeType {
+        match self {
+            KeyType::S => ScalarAttributeType::S,
+            KeyType::N => ScalarAttributeType::N,
+            KeyType::B => ScalarAttributeType::B,
+        }
+    }
+
+    /// A key value of this type no item is expected to have, distinct for each `n`.
+    pub fn missing(self, n: usize) -> String {
+        match self {
+            KeyType::S if n == 0 => "__dynamodbbench_missing__".to_owned(),
+            KeyType::S => format!("__dynamodbbench_missing__:{}", n),
+            KeyType::N => format!("-1{:09}e110", n),
+            KeyType::B => format!("d5d5d5d5{:016x}", n),
+        }
+    }
+
+    /// The `n`th key `--custom negative-cache` writes a marker at in run
+    /// `run`: absent until then, and apart from the [`KeyType::missing`] ones.
+    pub fn marker(self, run: u64, n: usize) -> String {
+        match self {
+            KeyType::S => format!("__dynamodbbench_negative__:{}:{}", run, n),
+            KeyType::N => format!("-2{:06}{:09}e110", run, n),
+            KeyType::B => format!("d6d6d6d6{:08x}{:016x}", run, n),
+        }
+    }
+}
+
+/// Renders an attribute value as DynamoDB JSON, e.g. `{"S": "abc"}`.
+pub fn attribute_json(value: &AttributeValue) -> Value {
+    let bytes = |b: &[u8]| b.iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+    match value {
+        AttributeValue::S(s) => json!({ "S": s }),
+        AttributeValue::N(n) => json!({ "N": n }),
+        AttributeValue::B(b) => json!({ "B": bytes(b.as_ref()) }),
+        AttributeValue::Bool(b) => json!({ "BOOL": b }),
+        AttributeValue::Null(n) => json!({ "NULL": n }),
+        AttributeValue::Ss(ss) => json!({ "SS": ss }),
+        AttributeValue::Ns(ns) => json!({ "NS": ns }),
+        AttributeValue::Bs(bs) => json!({ "BS": bs.iter().map(|b| bytes(b.as_ref())).collect::<Vec<_>>() }),
+        AttributeValue::L(l) => json!({ "L": l.iter().map(attribute_json).collect::<Vec<_>>() }),
+        AttributeValue::M(m) => json!({ "M": item_json(m) }),
+        other => json!({ "unknown": format!("{:?}", other) }),
+    }
+}
+
+/// Renders an item (or key) as a map of DynamoDB JSON attribute values.
+pub fn item_json(item: &HashMap<String, AttributeValue>) -> Value {
+    Value::Object(item.iter().map(|(k, v)| (k.clone(), attribute_json(v))).collect())
+}
+
+/// Short stable digest of a key, so it can be correlated across reports
+/// without revealing the value.
+pub fn hash_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes()).iter().take(8).map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn reason_codes(reasons: &[CancellationReason]) -> Vec<String> {
+    reasons.iter().map(|reason| reason.code().unwrap_or("None").to_owned()).collect()
+}
+
+/// The string literals of a PartiQL statement, which is where it carries its key values.
+pub fn literals(statement: &str) -> Vec<String> {
+    statement.split('\'').skip(1).step_by(2).map(str::to_owned).collect()
+}
+
+/// A key value as written in a PartiQL statement: numbers bare, strings quoted.
+fn partiql_literal(value: &AttributeValue) -> String {
+    match value {
+        AttributeValue::N(number) => number.clone(),
+        other => crate::template::partiql_string(&key_string(other)),
+    }
+}
+
+/// Renders the primary key of `item` as `partition/sort`.
+fn item_key(item: &HashMap<String, AttributeValue>, key_names: &[&str]) -> String {
+    key_names.iter()
+        .filter_map(|name| item.get(*name).map(key_string))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl Request {
+    /// The request with strongly consistent reads, for the operations that
+    /// offer the choice; the others are returned unchanged.
+    pub fn consistent(self) -> Request {
+        match self {
+            Request::Query(query) => Request::Query(Box::new(query.consistent_read(true))),
+            Request::GetItem(get) => Request::GetItem(Box::new(get.consistent_read(true))),
+            Request::BatchGetItem { client, request_items } => Request::BatchGetItem {
+                client,
+                request_items: request_items.into_iter().map(|(table, keys)| {
+                    let keys = KeysAndAttributes::builder()
+                        .set_keys(Some(keys.keys().to_vec()))
+                        .set_projection_expression(keys.projection_expression().map(str::to_owned))
+                        .set_expression_attribute_names(keys.expression_attribute_names().cloned())
+                        .consistent_read(true)
+                        .build()
+                        .expect("keys are set");
+                    (table, keys)
+                }).collect(),
+            },
+            Request::ExecuteStatement(statement) => Request::ExecuteStatement(Box::new(statement.consistent_read(true))),
+            Request::ReadModifyWrite(read, write) => Request::ReadModifyWrite(Box::new(read.consistent()), write),
+            other => other,
+        }
+    }
+
+    /// A copy of the request for another partition: `to` wherever the
+    /// partition key `name` is `from` in the keys and items it addresses, and
+    /// in place of `from`'s literal in its statements. Keys of other
+    /// partitions, such as the missing keys padding a batch, are left alone.
+    /// The request is copied whole, as sending consumes it; only the
+    /// partition values are replaced. None for custom requests.
+    pub fn with_partition(&self, name: &str, from: &AttributeValue, to: AttributeValue) -> Option<Request> {
+        let matches = |item: &Option<HashMap<String, AttributeValue>>| {
+            item.as_ref().and_then(|item| item.get(name)) == Some(from)
+        };
+        let swap = |item: &mut HashMap<String, AttributeValue>| {
+            if let Some(value) = item.get_mut(name).filter(|value| *value == from) {
+                *value = to.clone();
+            }
+        };
+        let request = match self {
+            Request::Query(query) if query.get_expression_attribute_values().as_ref().and_then(|values| values.get(":pk")) == Some(from) =>
+                Request::Query(Box::new((**query).clone().expression_attribute_values(":pk", to))),
+            Request::GetItem(get) if matches(get.get_key()) => Request::GetItem(Box::new((**get).clone().key(name, to))),
+            Request::PutItem(put) if matches(put.get_item()) => Request::PutItem(Box::new((**put).clone().item(name, to))),
+            Request::UpdateItem(update) if matches(update.get_key()) => Request::UpdateItem(Box::new((**update).clone().key(name, to))),
+            Request::BatchGetItem { client, request_items } => {
+                let mut request_items = request_items.clone();
+                request_items.values_mut().flat_map(|keys| &mut keys.keys).for_each(swap);
+                Request::BatchGetItem { client: client.clone(), request_items }
+            }
+            Request::BatchWriteItem { client, request_items } => {
+                let mut request_items = request_items.clone();
+                request_items.values_mut().flatten().filter_map(|write| write.put_request.as_mut()).for_each(|put| swap(&mut put.item));
+                Request::BatchWriteItem { client: client.clone(), request_items }
+            }
+            Request::TransactWriteItems { client, items } => {
+                let mut items = items.clone();
+                items.iter_mut().filter_map(|write| write.put.as_mut()).for_each(|put| swap(&mut put.item));
+                Request::TransactWriteItems { client: client.clone(), items }
+            }
+            Request::TransactGetItems { client, items } => {
+                let mut items = items.clone();
+                items.iter_mut().filter_map(|get| get.get.as_mut()).for_each(|get| swap(&mut get.key));
+                Request::TransactGetItems { client: client.clone(), items }
+            }
+            Request::ExecuteStatement(statement) => {
+                let text = statement.get_statement().as_deref().unwrap_or_default();
+                let text = text.replace(&partiql_literal(from), &partiql_literal(&to));
+                Request::ExecuteStatement(Box::new((**statement).clone().statement(text)))
+            }
+            Request::BatchExecuteStatement { client, statements } => {
+                let (from, to) = (partiql_literal(from), partiql_literal(&to));
+                let mut statements = statements.clone();
+                for statement in &mut statements {
+                    statement.statement = statement.statement.replace(&from, &to);
+                }
+                Request::BatchExecuteStatement { client: client.clone(), statements }
+            }
+            Request::ReadModifyWrite(read, write) => Request::ReadModifyWrite(
+                Box::new(read.with_partition(name, from, to.clone())?),
+                Box::new(write.with_partition(name, from, to)?),
+            ),
+            Request::Custom(_) => return None,
+            // a request without the partition value, such as a failing one
+            other => other.clone(),
+        };
+        Some(request)
+    }
+
+    /// The query with `Select=COUNT`, returning how many items match without
+    /// the items themselves; other requests are unchanged.
+    pub fn count_only(self) -> Request {
+        match self {
+            Request::Query(query) => Request::Query(Box::new(query.select(Select::Count))),
+            other => other,
+        }
+    }
+
+    /// The request following LastEvaluatedKey until its result is exhausted,
+    /// for --paginate: a query becomes a range aggregation, timed per page.
+    pub fn paginated(self) -> Request {
+        match self {
+            Request::Query(query) => custom::aggregate(*query),
+            other => other,
+        }
+    }
+
+    /// DynamoDB API name of the operation.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Request::Query(_) => "Query",
+            Request::GetItem(_) => "GetItem",
+            Request::PutItem(_) => "PutItem",
+            Request::UpdateItem(_) => "UpdateItem",
+            Request::BatchGetItem { .. } => "BatchGetItem",
+            Request::BatchWriteItem { .. } => "BatchWriteItem",
+            Request::TransactWriteItems { .. } => "TransactWriteItems",
+            Request::TransactGetItems { .. } => "TransactGetItems",
+            Request::ExecuteStatement(_) => "ExecuteStatement",
+            Request::BatchExecuteStatement { .. } => "BatchExecuteStatement",
+            Request::ReadModifyWrite(..) => "ReadModifyWrite",
+            Request::Custom(custom) => custom.name(),
+        }
+    }
+
+    /// The keys this request addresses, rendered as `partition/sort`. Queries
+    /// render their partition value and sort key bounds instead.
+    pub fn keys(&self, key_names: &[&str]) -> Vec<String> {
+        let empty = HashMap::new();
+        match self {
+            Request::Query(query) => {
+                let values = query.get_expression_attribute_values().as_ref().unwrap_or(&empty);
+                let bounds: Vec<_> = [":start", ":end"].iter()
+                    .map(|name| values.get(*name).map(key_string).unwrap_or_default())
+                    .collect();
+                vec![format!("{}/{}", values.get(":pk").map(key_string).unwrap_or_default(), bounds.join(".."))]
+            }
+            Request::GetItem(get) => vec![item_key(get.get_key().as_ref().unwrap_or(&empty), key_names)],
+            Request::PutItem(put) => vec![item_key(put.get_item().as_ref().unwrap_or(&empty), key_names)],
+            Request::UpdateItem(update) => vec![item_key(update.get_key().as_ref().unwrap_or(&empty), key_names)],
+            Request::BatchGetItem { request_items, .. } => request_items.values()
+                .flat_map(|keys| keys.keys())
+                .map(|key| item_key(key, key_names))
+                .collect(),
+            Request::BatchWriteItem { request_items, .. } => request_items.values()
+                .flatten()
+                .filter_map(|write| write.put_request().map(|put| put.item()))
+                .map(|item| item_key(item, key_names))
+                .collect(),
+            Request::TransactWriteItems { items, .. } => items.iter()
+                .filter_map(|write| write.put().map(|put| put.item()))
+                .map(|item| item_key(item, key_names))
+                .collect(),
+            Request::TransactGetItems { items, .. } => items.iter()
+                .filter_map(|get| get.get().map(|get| get.key()))
+                .map(|key| item_key(key, key_names))
+                .collect(),
+            // statements embed their keys as literals
+            Request::ExecuteStatement(statement) => statement.get_statement().iter().cloned().collect(),
+            Request::BatchExecuteStatement { statements, .. } => statements.iter()
+                .map(|statement| statement.statement().to_owned())
+                .collect(),
+            Request::ReadModifyWrite(read, _) => read.keys(key_names),
+            Request::Custom(custom) => custom.keys(),
+        }
+    }
+
+    /// Each key value the request carries, as [`key_string`] renders it, for
+    /// --redact-keys: the key attributes of its items, every expression
+    /// attribute value of a query (its partition, sort bounds and key
+    /// condition values), and the string literals of statements.
+    pub fn key_values(&self, key_names: &[&str]) -> Vec<String> {
+        let of_item = |item: &HashMap<String, AttributeValue>| -> Vec<String> {
+            key_names.iter().filter_map(|name| item.get(*name).map(key_string)).collect()
+        };
+        match self {
+            Request::Query(query) => query.get_expression_attribute_values().iter().flatten()
+                .map(|(_, value)| key_string(value))
+                .collect(),
+            Request::GetItem(get) => get.get_key().as_ref().map(of_item).unwrap_or_default(),
+            Request::PutItem(put) => put.get_item().as_ref().map(of_item).unwrap_or_default(),
+            Request::UpdateItem(update) => update.get_key().as_ref().map(of_item).unwrap_or_default(),
+            Request::BatchGetItem { request_items, .. } => request_items.values()
+                .flat_map(|keys| keys.keys())
+                .flat_map(of_item)
+                .collect(),
+            Request::BatchWriteItem { request_items, .. } => request_items.values()
+                .flatten()
+                .filter_map(|write| write.put_request().map(|put| put.item()))
+                .flat_map(of_item)
+                .collect(),
+            Request::TransactWriteItems { items, .. } => items.iter()
+                .filter_map(|write| write.put().map(|put| put.item()))
+                .flat_map(of_item)
+                .collect(),
+            Request::TransactGetItems { items, .. } => items.iter()
+                .filter_map(|get| get.get().map(|get| get.key()))
+                .flat_map(of_item)
+                .collect(),
+            Request::ExecuteStatement(statement) => statement.get_statement().as_deref().map(literals).unwrap_or_default(),
+            Request::BatchExecuteStatement { statements, .. } => statements.iter()
+                .flat_map(|statement| literals(statement.statement()))
+                .collect(),
+            Request::ReadModifyWrite(read, write) => read.key_values(key_names).into_iter()
+                .chain(write.key_values(key_names))
+                .collect(),
+            // rendered as `partition/sort` or `partition/start..end`
+            Request::Custom(custom) => custom.keys().iter()
+                .flat_map(|key| key.split(['/', '.']).filter(|part| !part.is_empty()).map(str::to_owned).collect::<Vec<_>>())
+                .collect(),
+        }
+    }
+
+    /// The partition key value of a request for one item or one partition, and
+    /// its sort key value (a query's range start). None for batches,
+    /// statements and custom requests, which may span several partitions.
+    pub fn primary_key(&self, partition_key: &str, sort_key: &str) -> Option<(String, Option<String>)> {
+        let key = |item: &HashMap<String, AttributeValue>| Some((
+            item.get(partition_key).map(key_string)?,
+            item.get(sort_key).map(key_string),
+        ));
+        match self {
+            Request::Query(query) => {
+                let values = query.get_expression_attribute_values().as_ref()?;
+                Some((values.get(":pk").map(key_string)?, values.get(":start").map(key_string)))
+            }
+            Request::GetItem(get) => key(get.get_key().as_ref()?),
+            Request::PutItem(put) => key(put.get_item().as_ref()?),
+            Request::UpdateItem(update) => key(update.get_key().as_ref()?),
+            Request::ReadModifyWrite(read, _) => read.primary_key(partition_key, sort_key),
+            Request::Custom(custom) => custom.primary_key(partition_key, sort_key),
+            _ => None,
+        }
+    }
+
+    /// The request's parameters as JSON, enough to send it again.
+    pub fn params(&self) -> Value {
+        let empty = HashMap::new();
+        let values = |values: Option<&HashMap<String, AttributeValue>>| item_json(values.unwrap_or(&empty));
+        match self {
+            Request::Query(query) => json!({
+                "table": query.get_table_name(),
+                "key_condition_expression": query.get_key_condition_expression(),
+                "expression_attribute_names": query.get_expression_attribute_names(),
+                "expression_attribute_values": values(query.get_expression_attribute_values().as_ref()),
+                "limit": query.get_limit(),
+                "projection_expression": query.get_projection_expression(),
+                "scan_index_forward": query.get_scan_index_forward(),
+                "select": query.get_select().as_ref().map(Select::as_str),
+            }),
+            Request::GetItem(get) => json!({
+                "table": get.get_table_name(),
+                "key": values(get.get_key().as_ref()),
+            }),
+            Request::PutItem(put) => json!({
+                "table": put.get_table_name(),
+                "item": values(put.get_item().as_ref()),
+            }),
+            Request::UpdateItem(update) => json!({
+                "table": update.get_table_name(),
+                "key": values(update.get_key().as_ref()),
+                "update_expression": update.get_update_expression(),
+                "expression_attribute_names": update.get_expression_attribute_names(),
+                "expression_attribute_values": values(update.get_expression_attribute_values().as_ref()),
+            }),
+            Request::BatchGetItem { request_items, .. } => json!({
+                "request_items": request_items.iter()
+                    .map(|(table, keys)| (table.clone(), keys.keys().iter().map(item_json).collect()))
+                    .collect::<serde_json::Map<_, _>>(),
+            }),
+            Request::BatchWriteItem { request_items, .. } => json!({
+                "request_items": request_items.iter()
+                    .map(|(table, writes)| (table.clone(), writes.iter()
+                        .filter_map(|write| write.put_request().map(|put| item_json(put.item())))
+                        .collect()))
+                    .collect::<serde_json::Map<_, _>>(),
+            }),
+            Request::TransactWriteItems { items, .. } => json!({
+                "puts": items.iter()
+                    .filter_map(|write| write.put())
+                    .map(|put| json!({ "table": put.table_name(), "item": item_json(put.item()) }))
+                    .collect::<Vec<_>>(),
+            }),
+            Request::TransactGetItems { items, .. } => json!({
+                "gets": items.iter()
+                    .filter_map(|get| get.get())
+                    .map(|get| json!({ "table": get.table_name(), "key": item_json(get.key()) }))
+                    .collect::<Vec<_>>(),
+            }),
+            Request::ExecuteStatement(statement) => json!({ "statement": statement.get_statement() }),
+            Request::BatchExecuteStatement { statements, .. } => json!({
+                "statements": statements.iter().map(|statement| statement.statement()).collect::<Vec<_>>(),
+            }),
+            Request::ReadModifyWrite(read, write) => json!({ "read": read.params(), "write": write.params() }),
+            Request::Custom(custom) => json!({ "keys": custom.keys() }),
+        }
+    }
+
+    pub async fn send(self) -> Result<Response, RequestError> {
+        match self {
+            Request::Custom(custom) => custom.send().await,
+            Request::ReadModifyWrite(read, write) => {
+                let read = Box::pin(read.send()).await?;
+                let write = Box::pin(write.send()).await?;
+                let mut response = Response { items: read.items + write.items, bytes: read.bytes, ..Default::default() };
+                response.add_units(&read);
+                response.add_units(&write);
+                Ok(response)
+            }
+            Request::Query(query) => {
+                let resp = query.return_consumed_capacity(return_capacity()).send().await?;
+                let mut response = Response {
+                    items: resp.count().max(0) as usize,
+                    bytes: resp.items().iter().map(item_bytes).sum(),
+                    ..Default::default()
+                };
+                response.add_capacity(resp.consumed_capacity(), false);
+                Ok(response)
+            }
+            Request::GetItem(get) => {
+                let resp = get.return_consumed_capacity(return_capacity()).send().await?;
+                let mut response = Response {
+                    items: resp.item().is_some() as usize,
+                    bytes: resp.item().map_or(0, item_bytes),
+                    ..Default::default()
+                };
+                response.add_capacity(resp.consumed_capacity(), false);
+                Ok(response)
+            }
+            Request::PutItem(put) => {
+                let resp = put.return_consumed_capacity(return_capacity()).send().await?;
+                let mut response = Response { items: 1, ..Default::default() };
+                response.add_capacity(resp.consumed_capacity(), true);
+                Ok(response)
+            }
+            Request::UpdateItem(update) => {
+                let resp = update.return_consumed_capacity(return_capacity()).send().await?;
+                let mut response = Response { items: 1, ..Default::default() };
+                response.add_capacity(resp.consumed_capacity(), true);
+                Ok(response)
+            }
+            Request::BatchGetItem { client, mut request_items } => {
+                let mut response = Response::default();
+                loop {
+                    let resp = client.batch_get_item()
+                        .set_request_items(Some(request_items))
+                        .return_consumed_capacity(return_capacity())
+                        .send()
+                        .await?;
+                    response.add_capacity(resp.consumed_capacity(), false);
+                    for items in resp.responses().into_iter().flat_map(HashMap::values) {
+                        response.items += items.len();
+                        response.bytes += items.iter().map(item_bytes).sum::<usize>();
+                    }
+                    request_items = resp.unprocessed_keys.unwrap_or_default();
+                    let unprocessed: usize = request_items.values().map(|keys| keys.keys().len()).sum();
+                    response.unprocessed += unprocessed;
+                    if unprocessed == 0 || response.retries == MAX_UNPROCESSED_RETRIES {
+                        return Ok(response);
+                    }
+                    response.retries += 1;
+                }
+            }
+            Request::BatchWriteItem { client, mut request_items } => {
+                let mut response = Response::default();
+                loop {
+                    let requested: usize = request_items.values().map(Vec::len).sum();
+                    let resp = client.batch_write_item()
+                        .set_request_items(Some(request_items))
+                        .return_consumed_capacity(return_capacity())
+                        .send()
+                        .await?;
+                    response.add_capacity(resp.consumed_capacity(), true);
+                    request_items = resp.unprocessed_items.unwrap_or_default();
+                    let unprocessed: usize = request_items.values().map(Vec::len).sum();
+                    response.items += requested - unprocessed;
+                    response.unprocessed += unprocessed;
+                    if unprocessed == 0 || response.retries == MAX_UNPROCESSED_RETRIES {
+                        return Ok(response);
+                    }
+                    response.retries += 1;
+                }
+            }
+            Request::TransactWriteItems { client, items } => {
+                let count = items.len();
+                let resp = client.transact_write_items()
+                    .set_transact_items(Some(items))
+                    .return_consumed_capacity(return_capacity())
+                    .send()
+                    .await
+                    .map_err(|err| {
+                        let reasons = match err.as_service_error() {
+                            Some(TransactWriteItemsError::TransactionCanceledException(e)) => reason_codes(e.cancellation_reasons()),
+                            _ => Vec::new(),
+                        };
+                        RequestError { cancellation_reasons: reasons, ..err.into() }
+                    })?;
+                let mut response = Response { items: count, ..Default::default() };
+                response.add_capacity(resp.consumed_capacity(), true);
+                Ok(response)
+            }
+            Request::TransactGetItems { client, items } => {
+                let resp = client.transact_get_items()
+                    .set_transact_items(Some(items))
+                    .return_consumed_capacity(return_capacity())
+                    .send()
+                    .await
+                    .map_err(|err| {
+                        let reasons = match err.as_service_error() {
+                            Some(TransactGetItemsError::TransactionCanceledException(e)) => reason_codes(e.cancellation_reasons()),
+                            _ => Vec::new(),
+                        };
+                        RequestError { cancellation_reasons: reasons, ..err.into() }
+                    })?;
+                let items: Vec<_> = resp.responses().iter().filter_map(|r| r.item()).collect();
+                let mut response = Response {
+                    items: items.len(),
+                    bytes: items.into_iter().map(item_bytes).sum(),
+                    ..Default::default()
+                };
+                response.add_capacity(resp.consumed_capacity(), false);
+                Ok(response)
+            }
+            Request::ExecuteStatement(statement) => {
+                let resp = statement.return_consumed_capacity(return_capacity()).send().await?;
+                let mut response = Response {
+                    items: resp.items().len(),
+                    bytes: resp.items().iter().map(item_bytes).sum(),
+                    ..Default::default()
+                };
+                // PartiQL statements may read or write; an unsplit total is counted as reads
+                response.add_capacity(resp.consumed_capacity(), false);
+                Ok(response)
+            }
+            Request::BatchExecuteStatement { client, statements } => {
+                let resp = client.batch_execute_statement()
+                    .set_statements(Some(statements))
+                    .return_consumed_capacity(return_capacity())
+                    .send()
+                    .await?;
+                let items: Vec<_> = resp.responses().iter().filter_map(|r| r.item()).collect();
+                let mut response = Response {
+                    items: items.len(),
+                    bytes: items.into_iter().map(item_bytes).sum(),
+                    failed: resp.responses().iter().filter(|r| r.error().is_some()).count(),
+                    ..Default::default()
+                };
+                response.add_capacity(resp.consumed_capacity(), false);
+                Ok(response)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_dynamodb::config::{BehaviorVersion, Region};
+
+    fn client() -> Client {
+        Client::from_conf(aws_sdk_dynamodb::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .build())
+    }
+
+    fn key(pk: &str, sk: &str) -> HashMap<String, AttributeValue> {
+        HashMap::from([("pk".to_owned(), AttributeValue::S(pk.to_owned())), ("sk".to_owned(), AttributeValue::S(sk.to_owned()))])
+    }
+
+    fn swap(request: &Request, to: &str) -> Request {
+        request.with_partition("pk", &AttributeValue::S("user#0".to_owned()), AttributeValue::S(to.to_owned())).unwrap()
+    }
+
+    #[test]
+    fn swaps_the_partition_of_single_item_requests() {
+        let get = Request::GetItem(Box::new(client().get_item().table_name("t").set_key(Some(key("user#0", "a")))));
+        assert_eq!(swap(&get, "user#7").key_values(&["pk", "sk"]), ["user#7", "a"]);
+        let query = Request::Query(Box::new(client().query().table_name("t")
+            .expression_attribute_values(":pk", AttributeValue::S("user#0".to_owned()))
+            .expression_attribute_values(":start", AttributeValue::S("user#0".to_owned()))));
+        let Request::Query(query) = swap(&query, "user#7") else { unreachable!() };
+        let values = query.get_expression_attribute_values().as_ref().unwrap();
+        // a sort bound equal to the partition value stays
+        assert_eq!((&values[":pk"], &values[":start"]), (&AttributeValue::S("user#7".to_owned()), &AttributeValue::S("user#0".to_owned())));
+    }
+
+    #[test]
+    fn leaves_other_partitions_in_batches() {
+        let keys = KeysAndAttributes::builder().keys(key("user#0", "a")).keys(key("user#0", "b")).keys(key("missing", "a"))
+            .build().unwrap();
+        let batch = Request::BatchGetItem { client: client(), request_items: HashMap::from([("t".to_owned(), keys)]) };
+        let swapped = swap(&batch, "user#7");
+        assert_eq!(swapped.key_values(&["pk"]), ["user#7", "user#7", "missing"]);
+        assert_eq!(batch.key_values(&["pk"]), ["user#0", "user#0", "missing"]);
+    }
+
+    #[test]
+    fn swaps_statement_literals() {
+        let statement = |text: &str| BatchStatementRequest::builder().statement(text).build().unwrap();
+        let batch = Request::BatchExecuteStatement { client: client(), statements: vec![
+            statement("SELECT * FROM t WHERE pk = 'user#0' AND sk = 'a'"),
+            statement("SELECT * FROM t WHERE pk = 'user#0' AND sk = 'user#01'"),
+        ] };
+        let Request::BatchExecuteStatement { statements, .. } = swap(&batch, "o'brien") else { unreachable!() };
+        assert_eq!(statements[0].statement(), "SELECT * FROM t WHERE pk = 'o''brien' AND sk = 'a'");
+        assert_eq!(statements[1].statement(), "SELECT * FROM t WHERE pk = 'o''brien' AND sk = 'user#01'");
+        let number = Request::ExecuteStatement(Box::new(client().execute_statement().statement("SELECT * FROM t WHERE pk = 5")));
+        let Request::ExecuteStatement(number) = number.with_partition("pk", &AttributeValue::N("5".to_owned()), AttributeValue::N("12".to_owned())).unwrap()
+            else { unreachable!() };
+        assert_eq!(number.get_statement().as_deref(), Some("SELECT * FROM t WHERE pk = 12"));
+    }
+}
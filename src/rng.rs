@@ -0,0 +1,144 @@
+use clap::ValueEnum;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+/// Where the random choices of workload generation come from.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// SplitMix64 of the draw's index: stateless, and the same draw for the same
+    /// index whatever the order, so runs are reproducible
+    Splitmix,
+    /// xoshiro256** seeded with --rng-seed, one generator per thread: fast, and
+    /// reproducible for a given seed as draws are made in request order
+    Xoshiro,
+    /// The operating system's cryptographic generator: not reproducible, and
+    /// a system call per draw
+    Os,
+}
+
+impl Backend {
+    pub fn describe(self, seed: u64) -> String {
+        match self {
+            Backend::Splitmix => "splitmix (indexed, reproducible)".to_owned(),
+            Backend::Xoshiro => format!("xoshiro256** (seed {}, reproducible)", seed),
+            Backend::Os => "os (cryptographic, not reproducible)".to_owned(),
+        }
+    }
+}
+
+/// The backend chosen by [`select`], as its position in [`Backend`]; SplitMix64 until then.
+static BACKEND: AtomicU8 = AtomicU8::new(Backend::Splitmix as u8);
+/// Seed of the xoshiro backend.
+static SEED: AtomicU64 = AtomicU64::new(0);
+/// How many times [`select`] has run, so each thread's generator knows when it was reseeded.
+static EPOCH: AtomicU64 = AtomicU64::new(0);
+/// Threads seeded in the current epoch, each taking its own stream.
+static STREAMS: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// This thread's xoshiro state, and the epoch it was seeded in.
+    static XOSHIRO: Cell<(u64, [u64; 4])> = const { Cell::new((0, [0; 4])) };
+}
+
+/// SplitMix64 of `i`.
+pub fn splitmix(i: u64) -> u64 {
+    let mut z = i.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn xoshiro(state: &mut [u64; 4]) -> u64 {
+    let result = state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+    let t = state[1] << 17;
+    state[2] ^= state[0];
+    state[3] ^= state[1];
+    state[1] ^= state[2];
+    state[0] ^= state[3];
+    state[2] ^= t;
+    state[3] = state[3].rotate_left(45);
+    result
+}
+
+/// Advances `state` by 2^128 draws, so streams started this many jumps
+/// apart never overlap.
+fn jump(state: &mut [u64; 4]) {
+    const JUMP: [u64; 4] = [0x180e_c6d3_3cfd_0aba, 0xd5a6_1266_f0c9_392c, 0xa958_2618_e03f_c9aa, 0x39ab_dc45_29b1_661c];
+    let mut jumped = [0; 4];
+    for word in JUMP {
+        for bit in 0..64 {
+            if word & (1 << bit) != 0 {
+                jumped.iter_mut().zip(state.iter()).for_each(|(jumped, state)| *jumped ^= state);
+            }
+            xoshiro(state);
+        }
+    }
+    *state = jumped;
+}
+
+/// The xoshiro state of stream `stream` of `seed`.
+fn seeded(seed: u64, stream: u64) -> [u64; 4] {
+    // seeded through SplitMix64, as the xoshiro authors recommend
+    let mut state = std::array::from_fn(|n| splitmix(seed.wrapping_add(n as u64)));
+    for _ in 0..stream {
+        jump(&mut state);
+    }
+    state
+}
+
+/// Chooses the generator for the draws from here on, reseeding xoshiro so
+/// each run (or phase) given the same seed draws the same sequence. The
+/// calling thread, which schedules the requests, draws the seed's first
+/// stream; any other thread drawing gets a later one.
+pub fn select(backend: Backend, seed: u64) {
+    SEED.store(seed, Ordering::Relaxed);
+    STREAMS.store(1, Ordering::Relaxed);
+    let epoch = EPOCH.fetch_add(1, Ordering::Relaxed) + 1;
+    XOSHIRO.set((epoch, seeded(seed, 0)));
+    BACKEND.store(backend as u8, Ordering::Relaxed);
+}
+
+/// A draw from this thread's xoshiro generator, seeding it first if
+/// [`select`] has run since it was.
+fn xoshiro_draw() -> u64 {
+    let (epoch, mut state) = XOSHIRO.get();
+    let current = EPOCH.load(Ordering::Relaxed);
+    if epoch != current {
+        state = seeded(SEED.load(Ordering::Relaxed), STREAMS.fetch_add(1, Ordering::Relaxed));
+    }
+    let result = xoshiro(&mut state);
+    XOSHIRO.set((current, state));
+    result
+}
+
+/// Random bits for draw `i`; only the splitmix backend looks at `i`, so the
+/// others are reproducible only as far as draws are made in the same order.
+pub fn draw(i: usize) -> u64 {
+    match BACKEND.load(Ordering::Relaxed) {
+        b if b == Backend::Xoshiro as u8 => xoshiro_draw(),
+        b if b == Backend::Os as u8 => {
+            let mut bytes = [0; 8];
+            getrandom::getrandom(&mut bytes).expect("the operating system's random number generator failed");
+            u64::from_le_bytes(bytes)
+        }
+        _ => splitmix(i as u64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xoshiro_reseeds_and_gives_threads_their_own_stream() {
+        select(Backend::Xoshiro, 7);
+        let first: Vec<_> = (0..4).map(draw).collect();
+        let other: Vec<_> = std::thread::spawn(|| (0..4).map(draw).collect()).join().unwrap();
+        select(Backend::Xoshiro, 7);
+        let again: Vec<_> = (0..4).map(draw).collect();
+        select(Backend::Splitmix, 0);
+        assert_eq!(first, again);
+        assert_ne!(first, other);
+        assert_eq!(draw(3), splitmix(3));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::Cli;
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Quantiles compared, with their labels.
+const QUANTILES: [(&str, f64); 5] = [("p50", 0.5), ("p90", 0.9), ("p99", 0.99), ("p99.9", 0.999), ("max", 1.0)];
+
+#[derive(Args, Debug, Clone)]
+pub struct DiffRunsArgs {
+    /// Latency of the baseline run: a --raw-log file, or an HdrHistogram percentile distribution
+    baseline: String,
+
+    /// Latency of the run compared with it, in either format
+    candidate: String,
+
+    /// Significant decimal digits of the buckets both runs are aligned on; use no more than
+    /// the coarser run was recorded with (HdrHistogram's is usually 2 or 3)
+    #[arg(long, default_value = "2", value_parser = clap::value_parser!(u32).range(1..=5))]
+    significant_digits: u32,
+
+    /// Units of an HdrHistogram distribution's values per millisecond, e.g. `1000` for one
+    /// printed in microseconds
+    #[arg(long, default_value = "1")]
+    hdr_units_per_ms: f64,
+}
+
+/// A run's latency counted in buckets of equal relative width, keyed by
+/// their lower bound in microseconds.
+struct Buckets {
+    digits: u32,
+    counts: BTreeMap<u64, u64>,
+    total: u64,
+}
+
+impl Buckets {
+    /// Lower bound and width of the bucket holding `micros`.
+    fn bucket(&self, micros: u64) -> (u64, u64) {
+        let magnitude = micros.max(1).ilog10() + 1;
+        let width = 10u64.pow(magnitude.saturating_sub(self.digits));
+        (micros / width * width, width)
+    }
+
+    fn add(&mut self, ms: f64, count: u64) {
+        let (lower, _) = self.bucket((ms * 1000.0).round() as u64);
+        *self.counts.entry(lower).or_default() += count;
+        self.total += count;
+    }
+
+    /// Upper bound in milliseconds of the bucket holding `quantile`, with the
+    /// nearest-rank rule the run reports use.
+    fn quantile_ms(&self, quantile: f64) -> f64 {
+        let rank = ((self.total as f64 * quantile).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (&lower, &count) in &self.counts {
+            seen += count;
+            if seen >= rank {
+                let (_, width) = self.bucket(lower);
+                return (lower + width) as f64 / 1000.0;
+            }
+        }
+        unreachable!("rank is at most the total count")
+    }
+}
+
+fn fail(message: String) -> ! {
+    Cli::command().error(ErrorKind::InvalidValue, message).exit()
+}
+
+/// Reads the latency of a run into `buckets`, returning the format it was in.
+fn load(path: &str, buckets: &mut Buckets, hdr_units_per_ms: f64) -> &'static str {
+    let contents = std::fs::read_to_string(path).unwrap_or_else(|e| fail(format!("cannot read {}: {}", path, e)));
+    let lines = contents.lines().map(str::trim).filter(|line| !line.is_empty());
+    if contents.trim_start().starts_with('{') {
+        for (n, line) in lines.enumerate() {
+            let line: Value = serde_json::from_str(line).unwrap_or_else(|e| fail(format!("{} line {}: {}", path, n + 1, e)));
+            // failed requests have a latency too, but not one to compare
+            if line["error_code"].is_null() {
+                let ms = line["latency_ms"].as_f64().unwrap_or_else(|| fail(format!("{} line {} has no latency_ms", path, n + 1)));
+                buckets.add(ms, 1);
+            }
+        }
+        return "raw log";
+    }
+    // columns Value, Percentile, TotalCount and 1/(1-Percentile), then a `#[...]` footer
+    let mut previous = 0;
+    for line in lines.filter(|line| !line.starts_with('#') && !line.starts_with("Value")) {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        let parsed = (columns.len() >= 3).then(|| (columns[0].parse::<f64>().ok(), columns[2].parse::<u64>().ok()));
+        let Some((Some(value), Some(total))) = parsed else {
+            fail(format!("{} is neither a raw log nor an HdrHistogram distribution: {:?}", path, line))
+        };
+        buckets.add(value / hdr_units_per_ms, total.saturating_sub(previous));
+        previous = previous.max(total);
+    }
+    "HdrHistogram distribution"
+}
+
+/// Compares the latency distributions of two runs after counting both in
+/// the same buckets, so runs recorded exactly and runs recorded into a
+/// histogram, or into histograms of different precision, are compared at a
+/// resolution both have rather than one's rounding passing for a change.
+pub fn run(args: &DiffRunsArgs) {
+    let mut runs = Vec::new();
+    for (label, path) in [("Baseline", &args.baseline), ("Candidate", &args.candidate)] {
+        let mut buckets = Buckets { digits: args.significant_digits, counts: BTreeMap::new(), total: 0 };
+        let format = load(path, &mut buckets, args.hdr_units_per_ms);
+        if buckets.total == 0 {
+            fail(format!("{} has no successful requests", path));
+        }
+        println!("{}: {} ({}, {} requests)", label, path, format, buckets.total);
+        runs.push(buckets);
+    }
+    let (baseline, candidate) = (&runs[0], &runs[1]);
+    println!("\nLatency aligned on buckets of {} significant digits (milliseconds, bucket upper bounds):", args.significant_digits);
+    println!("{:<10} {:>12} {:>12} {:>12} {:>10}", "", "Baseline", "Candidate", "Difference", "Change");
+    for (label, quantile) in QUANTILES {
+        let (a, b) = (baseline.quantile_ms(quantile), candidate.quantile_ms(quantile));
+        println!("{:<10} {:>12.3} {:>12.3} {:>+12.3} {:>9.1}%", label, a, b, b - a, 100.0 * (b - a) / a);
+    }
+    println!("Differences within one bucket, {:.1}% of the value or less, are below the common resolution",
+        100.0 / 10f64.powi(args.significant_digits as i32 - 1));
+}
@@ -0,0 +1,119 @@
+use crate::request::{item_bytes, RequestError};
+use crate::{percentile_ms, quantile_ms, timestamp, Cli};
+use aws_sdk_dynamodb::Client;
+use clap::Args;
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Args, Debug, Clone)]
+pub struct ScanArgs {
+    /// Number of segments the table is split into, each scanned by its own worker
+    #[arg(short = 'g', long, default_value = "4")]
+    total_segments: i32,
+
+    /// Maximum items per Scan page (DynamoDB's Limit)
+    #[arg(long)]
+    page_size: Option<i32>,
+
+    /// Stop each segment after this many pages instead of scanning it fully
+    #[arg(long)]
+    max_pages: Option<usize>,
+
+    /// Use eventually consistent reads
+    #[arg(long)]
+    eventually_consistent: bool,
+}
+
+#[derive(Default)]
+struct SegmentStats {
+    pages: usize,
+    items: usize,
+    bytes: usize,
+    elapsed: Duration,
+    page_latencies: Vec<Duration>,
+    error: Option<RequestError>,
+}
+
+async fn scan_segment(client: Client, table: String, segment: i32, args: ScanArgs) -> SegmentStats {
+    let mut stats = SegmentStats::default();
+    let start = Instant::now();
+    let mut exclusive_start_key = None;
+    loop {
+        let page_start = Instant::now();
+        let resp = client.scan()
+            .table_name(&table)
+            .segment(segment)
+            .total_segments(args.total_segments)
+            .set_limit(args.page_size)
+            .consistent_read(!args.eventually_consistent)
+            .set_exclusive_start_key(exclusive_start_key)
+            .send()
+            .await;
+        stats.page_latencies.push(page_start.elapsed());
+        let resp = match resp {
+            Ok(resp) => resp,
+            Err(e) => {
+                stats.error = Some(e.into());
+                break;
+            }
+        };
+        stats.pages += 1;
+        stats.items += resp.items().len();
+        stats.bytes += resp.items().iter().map(item_bytes).sum::<usize>();
+        exclusive_start_key = resp.last_evaluated_key;
+        if exclusive_start_key.is_none() || args.max_pages.is_some_and(|max| stats.pages >= max) {
+            break;
+        }
+    }
+    stats.elapsed = start.elapsed();
+    stats
+}
+
+fn rates(items: usize, bytes: usize, elapsed: Duration) -> (f64, f64) {
+    let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+    (items as f64 / secs, bytes as f64 / secs / 1_000_000.0)
+}
+
+pub async fn run(client: &Client, cli: &Cli, args: &ScanArgs) {
+    println!("Scanning {} with {} segments", cli.table, args.total_segments);
+
+    let start = Instant::now();
+    let started_at = SystemTime::now();
+    println!("Scan started at {}", timestamp::both(started_at, cli.timezone));
+    let workers: Vec<_> = (0..args.total_segments).map(|segment| {
+        tokio::spawn(scan_segment(client.clone(), cli.table.clone(), segment, args.clone()))
+    }).collect();
+    let mut segments = Vec::with_capacity(workers.len());
+    for worker in workers {
+        segments.push(worker.await.unwrap());
+    }
+    let total_duration = start.elapsed();
+
+    println!("\n{:>8} {:>8} {:>10} {:>10} {:>10} {:>12} {:>8}", "Segment", "Pages", "Items", "MB", "Seconds", "Items/s", "MB/s");
+    let mut page_latencies = Vec::new();
+    for (segment, stats) in segments.iter().enumerate() {
+        let (items_per_sec, mb_per_sec) = rates(stats.items, stats.bytes, stats.elapsed);
+        println!("{:>8} {:>8} {:>10} {:>10.3} {:>10.3} {:>12.1} {:>8.3}", segment, stats.pages, stats.items,
+            stats.bytes as f64 / 1_000_000.0, stats.elapsed.as_secs_f64(), items_per_sec, mb_per_sec);
+        if let Some(e) = &stats.error {
+            println!("Segment {} stopped on error: {}", segment, e);
+        }
+        page_latencies.extend_from_slice(&stats.page_latencies);
+    }
+
+    let items: usize = segments.iter().map(|s| s.items).sum();
+    let bytes: usize = segments.iter().map(|s| s.bytes).sum();
+    let (items_per_sec, mb_per_sec) = rates(items, bytes, total_duration);
+    println!("\nAggregate: {} items, {:.3} MB in {:.3}s", items, bytes as f64 / 1_000_000.0, total_duration.as_secs_f64());
+    println!("Throughput: {:.1} items/second, {:.3} MB/second", items_per_sec, mb_per_sec);
+
+    if !page_latencies.is_empty() {
+        page_latencies.sort();
+        println!("\nPage latency (milliseconds):");
+        println!("p50: {}", percentile_ms(&page_latencies, 0.5));
+        println!("p90: {}", percentile_ms(&page_latencies, 0.9));
+        println!("p99: {}", percentile_ms(&page_latencies, 0.99));
+        println!("Max: {:.3}", quantile_ms(&page_latencies, 1.0));
+    }
+    println!("\nScan started at {}", timestamp::both(started_at, cli.timezone));
+    println!("Scan ended at {}", timestamp::both(started_at + total_duration, cli.timezone));
+}
@@ -0,0 +1,159 @@
+use crate::rundiff;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde_json::{json, Value};
+
+/// Version of the machine-readable outputs, written into each of them as
+/// `schema_version`. Bumped whenever a field is removed or changes meaning;
+/// new fields may appear without a bump, so consumers should ignore unknown ones.
+pub const VERSION: u32 = 1;
+
+/// A machine-readable output of `bench`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Output {
+    /// --raw-log, one JSON object per line
+    RawLog,
+    /// --capture, one JSON object per line
+    Capture,
+    /// --error-report, one JSON document
+    ErrorReport,
+    /// --metrics-endpoint, one JSON document per run
+    Metrics,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SchemaArgs {
+    /// Output to print the schema of
+    #[arg(value_enum)]
+    output: Output,
+}
+
+/// `schema` or `diff-runs` on its own, without the table and region every
+/// other command needs.
+#[derive(Parser)]
+struct Standalone {
+    #[command(subcommand)]
+    command: StandaloneCommand,
+}
+
+#[derive(Subcommand)]
+pub enum StandaloneCommand {
+    Schema(SchemaArgs),
+    DiffRuns(rundiff::DiffRunsArgs),
+}
+
+/// A `schema` or `diff-runs` command given without global options.
+pub fn standalone(args: &[String]) -> Option<StandaloneCommand> {
+    if !matches!(args.get(1).map(String::as_str), Some("schema" | "diff-runs")) {
+        return None;
+    }
+    Some(Standalone::parse_from(args).command)
+}
+
+fn nullable(kind: &str) -> Value {
+    json!({ "type": [kind, "null"] })
+}
+
+/// The JSON Schema (draft 2020-12) of an output; for NDJSON outputs, of each line.
+pub fn schema(output: Output) -> Value {
+    let version = json!({ "const": VERSION, "description": "Version of this schema the output follows" });
+    let (title, properties, required): (&str, Value, &[&str]) = match output {
+        Output::RawLog => ("dynamodbbench raw log line", json!({
+            "schema_version": version,
+            "index": { "type": "integer", "minimum": 0, "description": "Position of the request in the measured run" },
+            "start_offset_ms": { "type": "number", "description": "When the request started, from the start of the run" },
+            "intended_offset_ms": { "type": "number", "description": "When the request was scheduled to start, from the start of the run" },
+            "latency_ms": { "type": "number" },
+            "miss": { "type": "boolean", "description": "Whether the request targeted a key known to be absent" },
+            "items": nullable("integer"),
+            "error_code": nullable("string"),
+            "request_id": nullable("string"),
+            "attempts": {
+                "type": "array",
+                "description": "One entry per attempt, retries included",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "status": { "type": "integer" },
+                        "headers": { "type": "object", "additionalProperties": { "type": "string" } },
+                    },
+                    "required": ["status", "headers"],
+                },
+            },
+        }), &["schema_version", "index", "start_offset_ms", "latency_ms", "miss", "attempts"]),
+        Output::Capture => ("dynamodbbench capture line", json!({
+            "schema_version": version,
+            "index": { "type": "integer", "minimum": 0 },
+            "operation": { "type": "string", "description": "DynamoDB API (or custom request) name" },
+            "params": { "type": "object", "description": "Request parameters, key values as DynamoDB JSON" },
+            "start": { "type": "string", "format": "date-time" },
+            "start_offset_ms": { "type": "number" },
+            "intended_offset_ms": { "type": "number" },
+            "latency_ms": { "type": "number" },
+            "items": nullable("integer"),
+            "bytes": nullable("integer"),
+            "error_code": nullable("string"),
+            "request_id": nullable("string"),
+        }), &["schema_version", "index", "operation", "params", "start", "start_offset_ms", "latency_ms"]),
+        Output::ErrorReport => ("dynamodbbench error report", json!({
+            "schema_version": version,
+            "table": {
+                "type": ["object", "null"],
+                "description": "The table as `describe --json` prints it, taken when the run started; null when it couldn't be described",
+            },
+            "failed_requests": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "timestamp": { "type": "string", "format": "date-time" },
+                        "index": { "type": "integer", "minimum": 0 },
+                        "operation": { "type": "string" },
+                        "latency_ms": { "type": "number" },
+                        "request_id": nullable("string"),
+                        "error_code": nullable("string"),
+                        "keys_sha256": { "type": "array", "items": { "type": "string" } },
+                        "cancellation_reasons": { "type": "array", "items": { "type": "string" } },
+                        "error_chain": { "type": "string" },
+                    },
+                    "required": ["timestamp", "index", "operation", "latency_ms", "keys_sha256", "error_chain"],
+                },
+            },
+        }), &["schema_version", "failed_requests"]),
+        Output::Metrics => ("dynamodbbench metrics push", json!({
+            "schema_version": version,
+            "source": { "const": "dynamodbbench" },
+            "timestamp": { "type": "string", "format": "date-time", "description": "When the run ended" },
+            "started_at": { "type": "string", "format": "date-time" },
+            "tags": {
+                "type": "object",
+                "additionalProperties": { "type": "string" },
+                "description": "table, region, request (the operation, preset or custom workload) and every --metrics-tag",
+            },
+            "metrics": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string", "description": "e.g. dynamodbbench.latency.p99" },
+                        "value": { "type": "number" },
+                        "unit": { "enum": ["count", "qps", "ms"] },
+                    },
+                    "required": ["name", "value", "unit"],
+                },
+            },
+        }), &["schema_version", "source", "timestamp", "started_at", "tags", "metrics"]),
+    };
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": format!("https://github.com/chalk-ai/dynamodb-benchmark-tool/schemas/v{}/{}.json",
+            VERSION, output.to_possible_value().expect("no variants are skipped").get_name()),
+        "title": title,
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+pub fn run(args: &SchemaArgs) {
+    println!("{}", serde_json::to_string_pretty(&schema(args.output)).unwrap());
+}
@@ -0,0 +1,92 @@
+use crate::request::Request;
+use crate::workload::{self, Workload};
+use crate::{BenchArgs, Cli};
+use aws_sdk_dynamodb::Client;
+use clap::error::ErrorKind;
+use clap::CommandFactory;
+use rhai::{Dynamic, Engine, Scope, AST};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Functions a --script may define; each is called with the request index.
+const HOOKS: &[&str] = &["partition_value", "sort_value", "sort_range", "payload"];
+
+/// Builds every request from values a Rhai script computes at send time,
+/// so workloads can depend on the index or the clock (e.g. "the bucket for
+/// the current minute"). Values the script doesn't provide come from the
+/// command line as usual.
+pub struct Scripted<'a> {
+    client: Client,
+    cli: &'a Cli,
+    args: &'a BenchArgs,
+    engine: Engine,
+    ast: AST,
+    hooks: Vec<&'static str>,
+}
+
+fn fail(message: String) -> ! {
+    Cli::command().error(ErrorKind::InvalidValue, message).exit()
+}
+
+impl<'a> Scripted<'a> {
+    pub fn load(path: &str, client: &Client, cli: &'a Cli, args: &'a BenchArgs) -> Scripted<'a> {
+        let mut engine = Engine::new();
+        engine.register_fn("now_ms", || SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64);
+        engine.register_fn("now_secs", || SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64);
+        let ast = engine.compile_file(path.into()).unwrap_or_else(|e| fail(format!("cannot load {}: {}", path, e)));
+        let hooks: Vec<_> = HOOKS.iter().copied()
+            .filter(|hook| ast.iter_functions().any(|f| f.name == *hook && f.params.len() == 1))
+            .collect();
+        if hooks.is_empty() {
+            fail(format!("{} defines none of {}, each taking the request index", path, HOOKS.join(", ")));
+        }
+        Scripted { client: client.clone(), cli, args, engine, ast, hooks }
+    }
+
+    /// Names of the hooks the script defines.
+    pub fn hooks(&self) -> &[&'static str] {
+        &self.hooks
+    }
+
+    fn call(&self, hook: &str, i: usize) -> Option<Dynamic> {
+        if !self.hooks.contains(&hook) {
+            return None;
+        }
+        let result = self.engine.call_fn::<Dynamic>(&mut Scope::new(), &self.ast, hook, (i as i64,));
+        Some(result.unwrap_or_else(|e| fail(format!("{}({}) failed: {}", hook, i, e))))
+    }
+
+    fn string(&self, hook: &str, i: usize) -> Option<String> {
+        self.call(hook, i).map(|value| value.to_string())
+    }
+}
+
+impl Workload for Scripted<'_> {
+    fn request(&self, i: usize) -> (Request, bool) {
+        let mut args = self.args.clone();
+        if let Some(value) = self.string("partition_value", i) {
+            args.partition_value = vec![value];
+        }
+        if let Some(value) = self.string("sort_value", i) {
+            args.sort_value = vec![value];
+        }
+        if let Some(range) = self.call("sort_range", i) {
+            let bound = |value: &Dynamic| (!value.is_unit()).then(|| value.to_string());
+            let range = range.into_array()
+                .unwrap_or_else(|t| fail(format!("sort_range({}) must return [start, end], not {}", i, t)));
+            args.sort_start = range.first().and_then(bound);
+            args.sort_end = range.get(1).and_then(bound);
+        }
+        if let Some(payload) = self.call("payload", i) {
+            let payload = payload.try_cast::<rhai::Map>()
+                .unwrap_or_else(|| fail(format!("payload({}) must return an object map", i)));
+            args.item_template = Some(rhai::format_map_as_json(&payload));
+        }
+        let requests = workload::make_requests(&self.client, self.cli, &args);
+        (requests[i % requests.len()].clone(), false)
+    }
+
+    /// Scripted requests are computed per call, so there is no fixed set to cycle through.
+    fn len(&self) -> usize {
+        1
+    }
+}
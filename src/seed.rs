@@ -0,0 +1,316 @@
+use crate::request::{KeyType, RequestError};
+use crate::{guard, timestamp, workload, Cli};
+use aws_sdk_dynamodb::types::{AttributeValue, DeleteRequest, PutRequest, ReturnConsumedCapacity, WriteRequest};
+use aws_sdk_dynamodb::Client;
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory};
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Attribute marking items written by `seed`, holding when the seeding started.
+pub const MARKER: &str = "dynamodbbench_seeded_at";
+/// Items per BatchWriteItem call, DynamoDB's maximum.
+const BATCH_SIZE: usize = 25;
+const MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+#[derive(Args, Debug, Clone)]
+pub struct SeedArgs {
+    /// Number of partitions to write
+    #[arg(long)]
+    partitions: u64,
+
+    /// Items written under each partition
+    #[arg(long)]
+    items_per_partition: u64,
+
+    /// Size in bytes of each item's JSON payload attribute
+    #[arg(long, default_value = "1024")]
+    item_size: usize,
+
+    /// Prefix of the partition values of S partition keys, followed by the partition number
+    #[arg(long, default_value = "seed#")]
+    key_prefix: String,
+
+    /// Prefix of the sort values of S sort keys, followed by the zero-padded item number
+    #[arg(long, default_value = "")]
+    sort_prefix: String,
+
+    /// Concurrent BatchWriteItem calls
+    #[arg(short = 'k', long, default_value = "16")]
+    parallelism: u64,
+
+    /// Refuse to seed tables carrying this tag, as `key=value`, repeatable
+    #[arg(long, value_parser = crate::parse_assignment, default_value = "env=production")]
+    protect_tag: Vec<(String, String)>,
+
+    /// Seed even tables matching --protect-tag
+    #[arg(long)]
+    allow_protected_table: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CleanupArgs {
+    /// Only delete the items of the seeding that started at this time, as printed by `seed`
+    #[arg(long)]
+    seeded_at: Option<String>,
+
+    /// Number of segments the table is scanned in, each by its own worker
+    #[arg(short = 'g', long, default_value = "8")]
+    total_segments: i32,
+
+    /// Count the seeded items without deleting them
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Refuse to clean up tables carrying this tag, as `key=value`, repeatable
+    #[arg(long, value_parser = crate::parse_assignment, default_value = "env=production")]
+    protect_tag: Vec<(String, String)>,
+
+    /// Clean up even tables matching --protect-tag
+    #[arg(long)]
+    allow_protected_table: bool,
+}
+
+#[derive(Default)]
+struct WorkerStats {
+    items: u64,
+    calls: usize,
+    retries: usize,
+    write_units: f64,
+    error: Option<RequestError>,
+}
+
+/// The key value of number `n` for a key of type `key_type`: the prefix and
+/// the number, zero-padded to `width` so that S keys sort numerically.
+pub fn numbered(key_type: KeyType, prefix: &str, n: u64, width: usize) -> AttributeValue {
+    match key_type {
+        KeyType::S => AttributeValue::S(format!("{}{:0width$}", prefix, n, width = width)),
+        KeyType::N => key_type.value(&n.to_string()),
+        KeyType::B => key_type.value(&format!("{:016x}", n)),
+    }
+}
+
+pub async fn run(client: &Client, cli: &Cli, args: &SeedArgs) {
+    if args.partitions == 0 || args.items_per_partition == 0 || args.parallelism == 0 {
+        Cli::command().error(ErrorKind::ValueValidation,
+            "--partitions, --items-per-partition and --parallelism must be positive").exit();
+    }
+    if cli.no_sort_key && args.items_per_partition > 1 {
+        Cli::command().error(ErrorKind::ArgumentConflict, format!(
+            "{} has no sort key, so it holds one item per partition; pass --items-per-partition 1", cli.table)).exit();
+    }
+    if !args.allow_protected_table {
+        refuse_protected(client, cli, &args.protect_tag, "seed it").await;
+    }
+
+    let started_at = SystemTime::now();
+    let marker = timestamp::TimeFormat::Rfc3339.format(started_at);
+    println!("Seeding {} with {} partitions of {} items, {} bytes each ({} items)", cli.table, args.partitions,
+        args.items_per_partition, args.item_size, args.partitions * args.items_per_partition);
+    println!("Seed started at {}", timestamp::both(started_at, cli.timezone));
+
+    let start = Instant::now();
+    let workers: Vec<_> = (0..args.parallelism.min(args.partitions)).map(|worker| {
+        tokio::spawn(seed_partitions(client.clone(), cli.table.clone(), worker, items(cli, args, &marker), args.clone()))
+    }).collect();
+    let mut stats = Vec::with_capacity(workers.len());
+    for worker in workers {
+        stats.push(worker.await.unwrap());
+    }
+    let elapsed = start.elapsed();
+
+    let items: u64 = stats.iter().map(|s| s.items).sum();
+    println!("\nWrote {} items in {:.3}s ({:.1} items/second), {} BatchWriteItem calls, {} retried for unprocessed items",
+        items, elapsed.as_secs_f64(), items as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        stats.iter().map(|s| s.calls).sum::<usize>(), stats.iter().map(|s| s.retries).sum::<usize>());
+    println!("Consumed capacity: {:.1} WCU", stats.iter().map(|s| s.write_units).sum::<f64>());
+    println!("Items are marked with {} = {}", MARKER, marker);
+    for (worker, stats) in stats.iter().enumerate() {
+        if let Some(e) = &stats.error {
+            println!("Worker {} stopped on error: {}", worker, e);
+        }
+    }
+}
+
+/// Exits when the table carries one of the --protect-tag tags, or its tags can't be read.
+pub async fn refuse_protected(client: &Client, cli: &Cli, protect_tag: &[(String, String)], action: &str) {
+    match guard::protected_by(client, &cli.table, protect_tag).await {
+        Ok(tags) if tags.is_empty() => {}
+        Ok(tags) => Cli::command().error(ErrorKind::ArgumentConflict, format!(
+            "{} is tagged {}; refusing to {} without --allow-protected-table", cli.table, tags.join(", "), action)).exit(),
+        Err(e) => Cli::command().error(ErrorKind::Io, format!(
+            "cannot check the tags of {} before writing to it ({}); pass --allow-protected-table to skip the check",
+            cli.table, e)).exit(),
+    }
+}
+
+/// Builds the item numbered `(partition, n)`.
+fn items(cli: &Cli, args: &SeedArgs, marker: &str) -> impl Fn(u64, u64) -> HashMap<String, AttributeValue> + Send + 'static {
+    let (partition_key, sort_key) = (cli.partition_key.clone(), (!cli.no_sort_key).then(|| cli.sort_key.clone()));
+    let (partition_type, sort_type) = (cli.partition_type, cli.sort_type);
+    let (key_prefix, sort_prefix) = (args.key_prefix.clone(), args.sort_prefix.clone());
+    let width = (args.items_per_partition - 1).to_string().len();
+    let payload = AttributeValue::S(workload::json_payload(args.item_size));
+    let marker = AttributeValue::S(marker.to_owned());
+    move |partition, n| {
+        let mut item = HashMap::from([
+            (partition_key.clone(), numbered(partition_type, &key_prefix, partition, 0)),
+            ("payload".to_owned(), payload.clone()),
+            (MARKER.to_owned(), marker.clone()),
+        ]);
+        if let Some(sort_key) = &sort_key {
+            item.insert(sort_key.clone(), numbered(sort_type, &sort_prefix, n, width));
+        }
+        item
+    }
+}
+
+/// Writes every partition numbered `worker` modulo --parallelism, retrying
+/// unprocessed items with exponential backoff.
+async fn seed_partitions(
+    client: Client,
+    table: String,
+    worker: u64,
+    items: impl Fn(u64, u64) -> HashMap<String, AttributeValue>,
+    args: SeedArgs,
+) -> WorkerStats {
+    let mut stats = WorkerStats::default();
+    let mut keys = (worker..args.partitions).step_by(args.parallelism as usize)
+        .flat_map(|partition| (0..args.items_per_partition).map(move |n| (partition, n)));
+    loop {
+        let batch: Vec<WriteRequest> = keys.by_ref().take(BATCH_SIZE).map(|(partition, n)| {
+            let put = PutRequest::builder().set_item(Some(items(partition, n))).build().unwrap();
+            WriteRequest::builder().put_request(put).build()
+        }).collect();
+        if batch.is_empty() {
+            return stats;
+        }
+        if let Err(e) = write(&client, &table, batch, &mut stats).await {
+            stats.error = Some(e);
+            return stats;
+        }
+    }
+}
+
+/// Sends `batch` with BatchWriteItem, retrying unprocessed requests with
+/// exponential backoff until all are processed.
+async fn write(client: &Client, table: &str, mut batch: Vec<WriteRequest>, stats: &mut WorkerStats) -> Result<(), RequestError> {
+    let mut backoff = Duration::from_millis(50);
+    loop {
+        let written = batch.len();
+        let resp = client.batch_write_item()
+            .request_items(table, batch)
+            .return_consumed_capacity(ReturnConsumedCapacity::Total)
+            .send()
+            .await;
+        stats.calls += 1;
+        let resp = resp?;
+        stats.write_units += resp.consumed_capacity().iter().filter_map(|c| c.capacity_units()).sum::<f64>();
+        batch = resp.unprocessed_items.and_then(|mut unprocessed| unprocessed.remove(table)).unwrap_or_default();
+        stats.items += (written - batch.len()) as u64;
+        if batch.is_empty() {
+            return Ok(());
+        }
+        stats.retries += 1;
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// Deletes every item `seed` wrote, found by its marker attribute with a
+/// parallel Scan. Items the benchmarks wrote themselves have no marker and stay.
+pub async fn cleanup(client: &Client, cli: &Cli, args: &CleanupArgs) {
+    if args.total_segments <= 0 {
+        Cli::command().error(ErrorKind::ValueValidation, "--total-segments must be positive").exit();
+    }
+    if !args.dry_run && !args.allow_protected_table {
+        refuse_protected(client, cli, &args.protect_tag, "clean it up").await;
+    }
+    println!("{} items seeded{} in {} with {} segments", if args.dry_run { "Counting" } else { "Deleting" },
+        args.seeded_at.as_ref().map(|at| format!(" at {}", at)).unwrap_or_default(), cli.table, args.total_segments);
+
+    let start = Instant::now();
+    let workers: Vec<_> = (0..args.total_segments).map(|segment| {
+        tokio::spawn(clean_segment(client.clone(), cli.partition_key.clone(), (!cli.no_sort_key).then(|| cli.sort_key.clone()),
+            cli.table.clone(), segment, args.clone()))
+    }).collect();
+    let mut stats = Vec::with_capacity(workers.len());
+    for worker in workers {
+        stats.push(worker.await.unwrap());
+    }
+    let elapsed = start.elapsed();
+
+    let items: u64 = stats.iter().map(|s| s.items).sum();
+    if args.dry_run {
+        println!("\nFound {} seeded items in {:.3}s", items, elapsed.as_secs_f64());
+    } else {
+        println!("\nDeleted {} seeded items in {:.3}s, {} BatchWriteItem calls, {} retried for unprocessed items",
+            items, elapsed.as_secs_f64(), stats.iter().map(|s| s.calls).sum::<usize>(),
+            stats.iter().map(|s| s.retries).sum::<usize>());
+        println!("Consumed capacity: {:.1} WCU", stats.iter().map(|s| s.write_units).sum::<f64>());
+    }
+    for (segment, stats) in stats.iter().enumerate() {
+        if let Some(e) = &stats.error {
+            println!("Segment {} stopped on error: {}", segment, e);
+        }
+    }
+}
+
+async fn clean_segment(
+    client: Client,
+    partition_key: String,
+    sort_key: Option<String>,
+    table: String,
+    segment: i32,
+    args: CleanupArgs,
+) -> WorkerStats {
+    let mut stats = WorkerStats::default();
+    let filter = match &args.seeded_at {
+        Some(_) => "#marker = :seeded_at",
+        None => "attribute_exists(#marker)",
+    };
+    // the key attributes, which are all a delete needs
+    let projection = if sort_key.is_some() { "#pk, #sk" } else { "#pk" };
+    let mut names = HashMap::from([("#pk".to_owned(), partition_key), ("#marker".to_owned(), MARKER.to_owned())]);
+    names.extend(sort_key.map(|sort_key| ("#sk".to_owned(), sort_key)));
+    let mut exclusive_start_key = None;
+    loop {
+        let resp = client.scan()
+            .table_name(&table)
+            .segment(segment)
+            .total_segments(args.total_segments)
+            .projection_expression(projection)
+            .filter_expression(filter)
+            .set_expression_attribute_names(Some(names.clone()))
+            .set_expression_attribute_values(args.seeded_at.as_ref()
+                .map(|at| HashMap::from([(":seeded_at".to_owned(), AttributeValue::S(at.clone()))])))
+            .set_exclusive_start_key(exclusive_start_key)
+            .send()
+            .await;
+        let resp = match resp {
+            Ok(resp) => resp,
+            Err(e) => {
+                stats.error = Some(e.into());
+                return stats;
+            }
+        };
+        if args.dry_run {
+            stats.items += resp.items().len() as u64;
+        } else {
+            for keys in resp.items().chunks(BATCH_SIZE) {
+                let batch = keys.iter().map(|key| {
+                    let delete = DeleteRequest::builder().set_key(Some(key.clone())).build().unwrap();
+                    WriteRequest::builder().delete_request(delete).build()
+                }).collect();
+                if let Err(e) = write(&client, &table, batch, &mut stats).await {
+                    stats.error = Some(e);
+                    return stats;
+                }
+            }
+        }
+        exclusive_start_key = resp.last_evaluated_key;
+        if exclusive_start_key.is_none() {
+            return stats;
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::mock::Mock;
+use crate::{bench, metadata, BenchArgs, Cli, Summary};
+use aws_sdk_dynamodb::config::{Credentials, Region};
+use aws_sdk_dynamodb::Client;
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory};
+
+/// A rate step is sustained when it has no errors and achieves this share of its target.
+const MIN_ACHIEVED: f64 = 0.9;
+
+fn sustained(qps: u32, summary: &Summary) -> bool {
+    summary.errors == 0 && summary.throughput >= qps as f64 * MIN_ACHIEVED
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SelftestArgs {
+    /// Target rate of the first step; each following step doubles it
+    #[arg(long, default_value = "1000")]
+    start_qps: u32,
+
+    /// Highest target rate to try
+    #[arg(long, default_value = "1000000")]
+    max_qps: u32,
+
+    /// How long each rate step runs for
+    #[arg(long, default_value = "5")]
+    step_secs: u32,
+
+    /// The workload to run, as for `bench`
+    #[command(flatten)]
+    bench: BenchArgs,
+}
+
+/// Runs the `bench` pipeline against an in-process [`Mock`] at doubling rates
+/// to find the highest rate this host can generate and measure, and the
+/// latency the client stack itself adds to every request.
+pub async fn run(cli: &Cli, args: &SelftestArgs) {
+    if !args.bench.stage.is_empty() || args.bench.min_samples_for.is_some() {
+        Cli::command().error(ErrorKind::ArgumentConflict,
+            "selftest sets the rate and length of each step itself; drop --stage and --min-samples-for").exit();
+    }
+    if args.start_qps == 0 || args.start_qps > args.max_qps || args.step_secs == 0 {
+        Cli::command().error(ErrorKind::ValueValidation,
+            "--start-qps must be positive and at most --max-qps, and --step-secs positive").exit();
+    }
+    let mock = Mock::start().await
+        .unwrap_or_else(|e| Cli::command().error(ErrorKind::Io, format!("cannot start the mock endpoint: {}", e)).exit());
+    let config = aws_config::from_env()
+        .region(Region::new(cli.region.clone()))
+        .endpoint_url(&mock.endpoint_url)
+        .credentials_provider(Credentials::new("selftest", "selftest", None, None, "selftest"))
+        .load()
+        .await;
+    let client = Client::from_conf(
+        aws_sdk_dynamodb::config::Builder::from(&config)
+            .interceptor(metadata::MetadataInterceptor)
+            .build(),
+    );
+    println!("Mock DynamoDB endpoint listening on {}", mock.endpoint_url);
+
+    let mut steps: Vec<(u32, Summary)> = Vec::new();
+    let mut qps = args.start_qps;
+    loop {
+        println!("\n=== Self-test at {} QPS for {}s ===", qps, args.step_secs);
+        let step = BenchArgs {
+            qps,
+            num_queries: qps as usize * args.step_secs as usize,
+            // The mock has no tags, and nothing it is sent is kept
+            allow_protected_table: true,
+            ..args.bench.clone()
+        };
+        let summary = bench(client.clone(), cli, &step).await;
+        let done = !sustained(qps, &summary) || qps >= args.max_qps;
+        steps.push((qps, summary));
+        if done {
+            break;
+        }
+        qps = qps.saturating_mul(2).min(args.max_qps);
+    }
+
+    println!("\nSelf-test summary ({} requests answered by the mock):", mock.served());
+    println!("{:>12} {:>12} {:>8} {:>10} {:>10} {:>10}", "Target QPS", "Achieved", "Errors", "p50 ms", "p99 ms", "p99.9 ms");
+    for (qps, summary) in &steps {
+        let [p50, _, p99, p999] = summary.percentiles;
+        println!("{:>12} {:>12.1} {:>8} {:>10.3} {:>10.3} {:>10.3}", qps, summary.throughput, summary.errors, p50, p99, p999);
+    }
+    match steps.iter().rfind(|(qps, summary)| sustained(*qps, summary)) {
+        Some((qps, summary)) => {
+            println!("\nMaximum sustainable rate on this host: {} QPS ({:.1} achieved){}", qps, summary.throughput,
+                if *qps == args.max_qps { ", the --max-qps ceiling" } else { "" });
+            let [p50, _, p99, _] = steps[0].1.percentiles;
+            println!("Measurement overhead (client stack latency at {} QPS): p50 {:.3} ms, p99 {:.3} ms", steps[0].0, p50, p99);
+            println!("Above {} QPS, or for latencies close to this overhead, results measure the client rather than DynamoDB", qps);
+        }
+        None => println!("\nNot even {} QPS was sustained; lower --start-qps or raise --parallelism", args.start_qps),
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+use std::time::Duration;
+
+/// A latency SLO such as `99%<20ms/30d`: the share of requests that must
+/// succeed within the threshold, over a rolling window.
+#[derive(Debug, Clone)]
+pub struct Slo {
+    target: f64,
+    threshold: Duration,
+    window_days: f64,
+}
+
+impl fmt::Display for Slo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}% < {}ms over {}d", self.target * 100.0, self.threshold.as_secs_f64() * 1000.0, self.window_days)
+    }
+}
+
+/// Parses `TARGET%<THRESHOLDms[/WINDOWd]`, e.g. `99.9%<20ms/30d`; the window defaults to 30 days.
+pub fn parse(s: &str) -> Result<Slo, String> {
+    let invalid = || format!("invalid SLO {:?}, expected e.g. 99%<20ms/30d", s);
+    let (target, rest) = s.split_once("%<").ok_or_else(invalid)?;
+    let (threshold, window) = rest.split_once('/').unwrap_or((rest, "30d"));
+    let target = target.trim().parse::<f64>().map_err(|_| invalid())? / 100.0;
+    let threshold = threshold.trim().strip_suffix("ms").and_then(|ms| ms.parse::<f64>().ok()).ok_or_else(invalid)?;
+    let window_days = window.trim().strip_suffix('d').and_then(|d| d.parse::<f64>().ok()).ok_or_else(invalid)?;
+    let positive = |value: f64| value.is_finite() && value > 0.0;
+    if !(target > 0.0 && target < 1.0 && positive(threshold) && positive(window_days)) {
+        return Err(invalid());
+    }
+    let threshold = Duration::try_from_secs_f64(threshold / 1000.0).map_err(|_| invalid())?;
+    Ok(Slo { target, threshold, window_days })
+}
+
+impl Slo {
+    /// Prints how fast the run's traffic, sustained, would burn the error
+    /// budget: the share of requests failing or slower than the threshold,
+    /// over the share the SLO allows.
+    pub fn report(&self, timeline: &[(Duration, Duration, bool)], throughput: f64) {
+        if timeline.is_empty() {
+            return;
+        }
+        let bad = timeline.iter().filter(|(_, latency, ok)| !ok || *latency >= self.threshold).count();
+        let bad_share = bad as f64 / timeline.len() as f64;
+        let burn_rate = bad_share / (1.0 - self.target);
+        println!("SLO {}: {} of {} requests ({:.3}%) failed or missed it, burn rate {:.2}",
+            self, bad, timeline.len(), bad_share * 100.0, burn_rate);
+        let bad_per_day = bad_share * throughput * 86_400.0;
+        if bad == 0 {
+            println!("  No error budget spent at {:.1} queries/second", throughput);
+        } else if burn_rate <= 1.0 {
+            println!("  At {:.1} queries/second: {:.0} bad requests a day, {:.1}% of the budget used over the window",
+                throughput, bad_per_day, burn_rate * 100.0);
+        } else {
+            println!("  At {:.1} queries/second: {:.0} bad requests a day, the {}d budget exhausted in {:.1}d",
+                throughput, bad_per_day, self.window_days, self.window_days / burn_rate);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_slos() {
+        let slo = parse("99.9%<20ms/7d").unwrap();
+        assert!((slo.target - 0.999).abs() < 1e-12);
+        assert_eq!((slo.threshold, slo.window_days), (Duration::from_millis(20), 7.0));
+        assert_eq!(parse("99%<0.5ms").unwrap().window_days, 30.0);
+    }
+
+    #[test]
+    fn rejects_malformed_slos() {
+        for malformed in ["", "99%", "99%<20", "99%<ms", "100%<20ms", "0%<20ms", "NaN%<20ms", "99%<0ms", "99%<-1ms",
+            "99%<NaNms", "99%<infms", "99%<1e300ms", "99%<20ms/0d", "99%<20ms/-1d", "99%<20ms/NaNd", "99%<20ms/infd"] {
+            assert!(parse(malformed).is_err(), "{:?}", malformed);
+        }
+    }
+}
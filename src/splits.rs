@@ -0,0 +1,236 @@
+use crate::{quantile_ms, timestamp, Cli};
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::Client;
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory};
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime};
+use ::time::UtcOffset;
+use tokio::sync::oneshot;
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time;
+
+/// Rounds of a key's latency its baseline is the median of.
+const BASELINE_ROUNDS: usize = 20;
+/// Rounds each probe latency is smoothed over, so a single slow probe isn't an elevation.
+const SMOOTHING_ROUNDS: usize = 3;
+/// Longest elevation still taken for a split; a longer one is a change of load.
+const MAX_ELEVATION: Duration = Duration::from_secs(60);
+
+#[derive(Args, Debug, Clone)]
+pub struct SplitWatchArgs {
+    /// Probe a fixed set of keys throughout a writing run and report latency bumps that
+    /// look like partition splits: a brief elevation, then latency back at or below where it was
+    #[arg(long)]
+    split_watch: bool,
+
+    /// Keys probed, each a GetItem of a missing key, which lands in its own spot of the keyspace
+    #[arg(long, default_value = "8", requires = "split_watch", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    split_watch_keys: usize,
+
+    /// Milliseconds between probe rounds
+    #[arg(long, default_value = "500", requires = "split_watch", value_parser = clap::value_parser!(u64).range(1..))]
+    split_watch_interval_ms: u64,
+
+    /// How many times its baseline a key's smoothed latency must reach to count as elevated
+    #[arg(long, default_value = "2", requires = "split_watch")]
+    split_watch_threshold: f64,
+}
+
+impl SplitWatchArgs {
+    /// Rejects --split-watch for runs that don't write, which don't split partitions.
+    pub fn validate(&self, writes: bool) {
+        if self.split_watch && !writes {
+            Cli::command().error(ErrorKind::ArgumentConflict, "--split-watch needs a writing workload, which is what splits partitions").exit();
+        }
+        if self.split_watch && self.split_watch_threshold <= 1.0 {
+            Cli::command().error(ErrorKind::ValueValidation, "--split-watch-threshold must be above 1").exit();
+        }
+    }
+}
+
+/// A suspected partition split, as one key's latency saw it.
+struct Split {
+    key: usize,
+    /// When the elevation started and ended, relative to the start of the run
+    started: Duration,
+    ended: Duration,
+    baseline: f64,
+    peak: f64,
+    /// Smoothed latency once back down
+    after: f64,
+}
+
+/// An elevation of one key's latency in progress.
+struct Elevation {
+    started: Duration,
+    baseline: f64,
+    peak: f64,
+}
+
+/// The latency history of one probed key.
+#[derive(Default)]
+struct Tracker {
+    recent: Vec<f64>,
+    /// Smoothed latencies outside elevations, since the last level change
+    level: Vec<f64>,
+    elevation: Option<Elevation>,
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut values = values.to_vec();
+    values.sort_by(f64::total_cmp);
+    values[values.len() / 2]
+}
+
+impl Tracker {
+    /// Adds a probe latency of key `key` at `offset`, returning the split it ends, if any.
+    fn add(&mut self, key: usize, ms: f64, offset: Duration, threshold: f64) -> Option<Split> {
+        self.recent.push(ms);
+        if self.recent.len() < SMOOTHING_ROUNDS {
+            return None;
+        }
+        let smoothed = median(&self.recent[self.recent.len() - SMOOTHING_ROUNDS..]);
+        let Some(elevation) = &mut self.elevation else {
+            let baseline = (self.level.len() >= BASELINE_ROUNDS).then(|| median(&self.level[self.level.len() - BASELINE_ROUNDS..]));
+            match baseline {
+                Some(baseline) if smoothed > threshold * baseline => {
+                    self.elevation = Some(Elevation { started: offset, baseline, peak: smoothed });
+                }
+                _ => self.level.push(smoothed),
+            }
+            return None;
+        };
+        elevation.peak = elevation.peak.max(smoothed);
+        if smoothed <= elevation.baseline {
+            let split = Split { key, started: elevation.started, ended: offset, baseline: elevation.baseline, peak: elevation.peak, after: smoothed };
+            // a split leaves the key at a new level, which the next baseline is taken from
+            self.elevation = None;
+            self.level = vec![smoothed];
+            return Some(split);
+        }
+        if offset - elevation.started > MAX_ELEVATION {
+            self.elevation = None;
+            self.level.clear();
+        }
+        None
+    }
+}
+
+/// Probe latencies and suspected splits of a run.
+pub struct Watcher {
+    keys: usize,
+    interval: Duration,
+    started_at: SystemTime,
+    timezone: UtcOffset,
+    rounds: usize,
+    latencies: Vec<Duration>,
+    errors: usize,
+    splits: Vec<Split>,
+}
+
+impl Watcher {
+    fn describe(&self, split: &Split) -> String {
+        format!("probe key {} at {:.3} ms against a baseline of {:.3} ms for {:.1}s, then {:.3} ms",
+            split.key + 1, split.peak, split.baseline, (split.ended - split.started).as_secs_f64(), split.after)
+    }
+
+    async fn run(mut self, client: Client, table: String, keys: Vec<HashMap<String, AttributeValue>>,
+        start: time::Instant, threshold: f64, mut stop: oneshot::Receiver<()>) -> Watcher {
+        let mut trackers: Vec<Tracker> = keys.iter().map(|_| Tracker::default()).collect();
+        let mut ticker = time::interval(self.interval);
+        ticker.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = &mut stop => break,
+            }
+            let offset = start.elapsed();
+            let mut probes = JoinSet::new();
+            for (n, key) in keys.iter().enumerate() {
+                let get = client.get_item().table_name(&table).set_key(Some(key.clone()));
+                probes.spawn(async move {
+                    let probe_start = Instant::now();
+                    (n, get.send().await.map(|_| probe_start.elapsed()))
+                });
+            }
+            while let Some(result) = probes.join_next().await {
+                let (n, latency) = result.expect("split watch probe panicked");
+                let Ok(latency) = latency else {
+                    self.errors += 1;
+                    continue;
+                };
+                self.latencies.push(latency);
+                if let Some(split) = trackers[n].add(n, latency.as_secs_f64() * 1000.0, offset, threshold) {
+                    println!("Suspected partition split at {}: {}",
+                        timestamp::both(self.started_at + split.started, self.timezone), self.describe(&split));
+                    self.splits.push(split);
+                }
+            }
+            self.rounds += 1;
+        }
+        self
+    }
+
+    /// Prints the probe latency and the suspected splits, in the order they started.
+    pub fn print(&mut self) {
+        println!("\nPartition split watch: {} rounds of {} probe keys, every {} ms; {} probes failed",
+            self.rounds, self.keys, self.interval.as_millis(), self.errors);
+        if !self.latencies.is_empty() {
+            self.latencies.sort();
+            println!("Probe latency: p50 {:.3}, p99 {:.3}, max {:.3}",
+                quantile_ms(&self.latencies, 0.5), quantile_ms(&self.latencies, 0.99), quantile_ms(&self.latencies, 1.0));
+        }
+        if self.splits.is_empty() {
+            println!("No suspected partition splits");
+            return;
+        }
+        self.splits.sort_by_key(|split| split.started);
+        println!("Suspected partition splits ({}):", self.splits.len());
+        for split in &self.splits {
+            println!("  {}: {}", timestamp::both(self.started_at + split.started, self.timezone), self.describe(split));
+        }
+    }
+}
+
+/// The probing of a run in progress.
+pub struct Watch {
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<Watcher>,
+}
+
+impl Watch {
+    /// Starts probing, if --split-watch is given, with offsets relative to
+    /// `start`, the start of the run at `started_at`.
+    pub fn start(client: &Client, cli: &Cli, args: &SplitWatchArgs, start: time::Instant, started_at: SystemTime) -> Option<Watch> {
+        if !args.split_watch {
+            return None;
+        }
+        let keys = (0..args.split_watch_keys).map(|n| {
+            let mut key = HashMap::from([(cli.partition_key.clone(), cli.partition_type.value(&cli.partition_type.missing(n)))]);
+            if !cli.no_sort_key {
+                key.insert(cli.sort_key.clone(), cli.sort_type.value(&cli.sort_type.missing(0)));
+            }
+            key
+        }).collect();
+        let watcher = Watcher {
+            keys: args.split_watch_keys,
+            interval: Duration::from_millis(args.split_watch_interval_ms),
+            started_at,
+            timezone: cli.timezone,
+            rounds: 0,
+            latencies: Vec::new(),
+            errors: 0,
+            splits: Vec::new(),
+        };
+        let (stop, stopped) = oneshot::channel();
+        let task = tokio::spawn(watcher.run(client.clone(), cli.table.clone(), keys, start, args.split_watch_threshold, stopped));
+        Some(Watch { stop, task })
+    }
+
+    /// Stops probing once the current round is over.
+    pub async fn finish(self) -> Watcher {
+        let _ = self.stop.send(());
+        self.task.await.expect("split watch task panicked")
+    }
+}
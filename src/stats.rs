@@ -0,0 +1,84 @@
+use crate::histogram::Latencies;
+
+/// Share of the fastest and of the slowest requests the trimmed mean leaves out.
+const TRIM: f64 = 0.05;
+
+/// Summary statistics of a run's latency, in milliseconds.
+pub struct Stats {
+    pub mean: f64,
+    pub stddev: f64,
+    /// Mean of the requests left once the fastest and slowest [`TRIM`] are dropped
+    pub trimmed_mean: f64,
+    /// Standard deviation relative to the mean
+    pub cv: f64,
+    /// Spread of the middle half, p75 minus p25
+    pub iqr: f64,
+}
+
+impl Stats {
+    /// Computes the statistics of `latencies`, None if there are none.
+    pub fn of(latencies: &Latencies) -> Option<Stats> {
+        if latencies.is_empty() {
+            return None;
+        }
+        let (mean, stddev) = (latencies.mean_ms(), latencies.stddev_ms());
+        let trim = (latencies.len() as f64 * TRIM).floor() as u64;
+        let (kept_from, kept_to) = (trim, latencies.len() as u64 - trim);
+        // the requests ranked kept_from..kept_to, counted by value
+        let (mut seen, mut sum, mut kept) = (0, 0.0, 0);
+        for (value, count) in latencies.counts() {
+            let overlap = (seen + count).min(kept_to).saturating_sub(seen.max(kept_from));
+            sum += value * overlap as f64;
+            kept += overlap;
+            seen += count;
+        }
+        Some(Stats {
+            mean,
+            stddev,
+            trimmed_mean: sum / kept.max(1) as f64,
+            cv: if mean > 0.0 { stddev / mean } else { 0.0 },
+            iqr: latencies.quantile_ms(0.75) - latencies.quantile_ms(0.25),
+        })
+    }
+
+    pub fn print(&self) {
+        println!("Mean: {:.3}", self.mean);
+        println!("Stddev: {:.3}", self.stddev);
+        println!("Trimmed mean ({:.0}% off each end): {:.3}", TRIM * 100.0, self.trimmed_mean);
+        println!("Coefficient of variation: {:.3}", self.cv);
+        println!("IQR (p75 - p25): {:.3}", self.iqr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn none_without_latencies() {
+        assert!(Stats::of(&Latencies::from_ms(60_000, [])).is_none());
+    }
+
+    #[test]
+    fn single_latency() {
+        let stats = Stats::of(&Latencies::from_ms(60_000, [20])).unwrap();
+        assert!((stats.mean - 20.0).abs() < 0.02);
+        assert!((stats.trimmed_mean - 20.0).abs() < 0.02);
+        assert_eq!((stats.stddev, stats.cv, stats.iqr), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn too_few_to_trim_keeps_all() {
+        // 19 requests: 5% of each end is less than one request
+        let stats = Stats::of(&Latencies::from_ms(60_000, (1..=19).map(|n| n * 10))).unwrap();
+        assert!((stats.trimmed_mean - stats.mean).abs() < 1e-9);
+    }
+
+    #[test]
+    fn trims_outliers() {
+        let stats = Stats::of(&Latencies::from_ms(60_000, (0..19).map(|_| 10).chain([10_000]))).unwrap();
+        assert!(stats.mean > 500.0);
+        assert!((stats.trimmed_mean - 10.0).abs() < 0.02);
+        assert_eq!(stats.iqr, 0.0);
+    }
+}
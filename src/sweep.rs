@@ -0,0 +1,75 @@
+use crate::{bench, BenchArgs, Cli, Operation, Summary};
+use aws_sdk_dynamodb::Client;
+use clap::error::ErrorKind;
+use clap::CommandFactory;
+
+/// Runs the write benchmark once for every combination of --sweep-item-size
+/// and --sweep-attribute-count, each with its full report, then compares them
+/// side by side; with --sweep-limit, runs the query once per Limit instead.
+pub async fn run(client: Client, cli: &Cli, args: &BenchArgs) {
+    if !args.sweep_limit.is_empty() {
+        return limits(client, cli, args).await;
+    }
+    if !matches!(args.operation, Operation::PutItem | Operation::BatchWriteItem | Operation::TransactWriteItems) {
+        Cli::command().error(ErrorKind::ArgumentConflict,
+            "--sweep-item-size/--sweep-attribute-count require a write --operation (put-item, batch-write-item or transact-write-items)").exit();
+    }
+    let sizes = if args.sweep_item_size.is_empty() { vec![args.item_size] } else { args.sweep_item_size.clone() };
+    let counts = if args.sweep_attribute_count.is_empty() { vec![args.attribute_count] } else { args.sweep_attribute_count.clone() };
+
+    let mut results: Vec<(usize, usize, Summary)> = Vec::new();
+    for &item_size in &sizes {
+        for &attribute_count in &counts {
+            println!("\n=== Item size {} bytes, {} attribute(s) ===", item_size, attribute_count);
+            let point = BenchArgs {
+                item_size,
+                attribute_count,
+                sweep_item_size: Vec::new(),
+                sweep_attribute_count: Vec::new(),
+                ..args.clone()
+            };
+            results.push((item_size, attribute_count, bench(client.clone(), cli, &point).await));
+        }
+    }
+
+    println!("\nSweep summary:");
+    println!("{:>10} {:>10} {:>10} {:>8} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "Bytes", "Attributes", "Requests", "Errors", "p50 ms", "p90 ms", "p99 ms", "p99.9 ms", "QPS");
+    for (item_size, attribute_count, summary) in &results {
+        let [p50, p90, p99, p999] = summary.percentiles;
+        println!("{:>10} {:>10} {:>10} {:>8} {:>10.2} {:>10.2} {:>10.2} {:>10.2} {:>10.1}",
+            item_size, attribute_count, summary.requests, summary.errors, p50, p90, p99, p999, summary.throughput);
+    }
+}
+
+/// Runs the query once for every --sweep-limit, each with its full report,
+/// then compares them side by side, with the latency each additional item
+/// returned adds at p50 over the previous Limit.
+async fn limits(client: Client, cli: &Cli, args: &BenchArgs) {
+    if args.operation != Operation::Query {
+        Cli::command().error(ErrorKind::ArgumentConflict, "--sweep-limit requires --operation query").exit();
+    }
+    let describe = |limit: Option<i32>| limit.map_or("none".to_owned(), |limit| limit.to_string());
+
+    let mut results: Vec<(Option<i32>, Summary)> = Vec::new();
+    for &limit in &args.sweep_limit {
+        println!("\n=== Limit {} ===", describe(limit));
+        let point = BenchArgs { limit, sweep_limit: Vec::new(), ..args.clone() };
+        results.push((limit, bench(client.clone(), cli, &point).await));
+    }
+
+    println!("\nSweep summary:");
+    println!("{:>10} {:>10} {:>8} {:>12} {:>10} {:>10} {:>10} {:>10} {:>10} {:>14}",
+        "Limit", "Requests", "Errors", "Items/req", "p50 ms", "p90 ms", "p99 ms", "p99.9 ms", "QPS", "ms/extra item");
+    let mut previous: Option<(f64, f64)> = None;
+    for (limit, summary) in &results {
+        let [p50, p90, p99, p999] = summary.percentiles;
+        let items = summary.items as f64 / summary.requests.max(1) as f64;
+        // only meaningful when the Limit actually changed how many items came back
+        let per_item = previous.filter(|(previous_items, _)| (items - previous_items).abs() >= 1.0)
+            .map_or("-".to_owned(), |(previous_items, previous_p50)| format!("{:.4}", (p50 - previous_p50) / (items - previous_items)));
+        println!("{:>10} {:>10} {:>8} {:>12.1} {:>10.2} {:>10.2} {:>10.2} {:>10.2} {:>10.1} {:>14}",
+            describe(*limit), summary.requests, summary.errors, items, p50, p90, p99, p999, summary.throughput, per_item);
+        previous = Some((items, p50));
+    }
+}
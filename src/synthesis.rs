@@ -0,0 +1,97 @@
+use crate::custom;
+use crate::keygen;
+use crate::mapping::Mapping;
+use crate::request::Request;
+use crate::workload::{self, Workload};
+use crate::{BenchArgs, Cli};
+use aws_sdk_dynamodb::Client;
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// One FQN mapping that synthesized requests read.
+struct Target {
+    partition_key_prefix: String,
+    bucket_ms: u64,
+    weight: f64,
+}
+
+/// Read traffic approximating a whole Chalk environment, synthesized from its
+/// FQN mappings for --synthesize-from. Each request reads the latest
+/// --synth-buckets buckets of one aggregation key for one entity (-P), the way
+/// a windowed aggregation does: every page of the window, as one request.
+pub struct Synthesized<'a> {
+    client: Client,
+    cli: &'a Cli,
+    args: &'a BenchArgs,
+    targets: Vec<Target>,
+}
+
+impl<'a> Synthesized<'a> {
+    /// Weighs aggregation keys evenly, or by --aggregation-weight when given,
+    /// in which case only the listed keys are read. A key's weight is split
+    /// between its mappings.
+    pub fn new(mappings: Vec<Mapping>, client: &Client, cli: &'a Cli, args: &'a BenchArgs) -> Result<Synthesized<'a>, String> {
+        let known: HashSet<&str> = mappings.iter().map(|mapping| mapping.aggregation_key.as_str()).collect();
+        if let Some((key, _)) = args.aggregation_weight.iter().find(|(key, _)| !known.contains(key.as_str())) {
+            return Err(format!("--aggregation-weight names {:?}, which has no FQN mapping", key));
+        }
+        let weight_of = |key: &str| match args.aggregation_weight.as_slice() {
+            [] => Some(1.0),
+            weights => weights.iter().find(|(k, _)| k == key).map(|(_, weight)| *weight),
+        };
+        let mut targets = Vec::new();
+        for mapping in &mappings {
+            let Some(weight) = weight_of(&mapping.aggregation_key) else {
+                continue;
+            };
+            let bucket_ms: u64 = mapping.bucket_duration.parse().expect("bucket durations match [0-9]+");
+            if bucket_ms == 0 {
+                continue;
+            }
+            let share = mappings.iter().filter(|m| m.aggregation_key == mapping.aggregation_key).count();
+            targets.push(Target {
+                partition_key_prefix: mapping.partition_key_prefix.clone(),
+                bucket_ms,
+                weight: weight / share as f64,
+            });
+        }
+        if targets.is_empty() {
+            return Err("no FQN mappings to synthesize requests from".to_owned());
+        }
+        Ok(Synthesized { client: client.clone(), cli, args, targets })
+    }
+
+    /// Mappings that requests are drawn from.
+    pub fn mappings(&self) -> usize {
+        self.targets.len()
+    }
+}
+
+impl Workload for Synthesized<'_> {
+    fn request(&self, i: usize) -> (Request, bool) {
+        const GOLDEN: f64 = 0.618_033_988_749_894_9;
+        let target = &self.targets[workload::pick_weighted(self.targets.iter().map(|t| t.weight), ((i as f64 + 0.5) * GOLDEN).fract())];
+        let entities = &self.args.partition_value;
+        let entity = &entities[((keygen::uniform(i) * entities.len() as f64) as usize).min(entities.len() - 1)];
+
+        // the latest --synth-buckets buckets, the current (partial) one included
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        let end = now - now % target.bucket_ms;
+        let start = end.saturating_sub((self.args.synth_buckets - 1) * target.bucket_ms);
+        let bucket = |ms: u64| self.args.time_format.format(UNIX_EPOCH + Duration::from_millis(ms));
+        let args = BenchArgs {
+            partition_value: vec![format!("{}{}", target.partition_key_prefix, entity)],
+            sort_start: Some(bucket(start)),
+            sort_end: Some(bucket(end)),
+            ..self.args.clone()
+        };
+        match workload::make_query(&self.client, self.cli, &args).remove(0) {
+            Request::Query(query) => (custom::aggregate(*query), false),
+            _ => unreachable!("make_query builds queries"),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.targets.len() * self.args.partition_value.len()
+    }
+}
@@ -0,0 +1,136 @@
+use crate::keygen;
+use aws_sdk_dynamodb::types::AttributeValue;
+use regex::{Captures, Regex};
+use serde_json::Value;
+use std::collections::HashMap;
+
+const LOREM: &[&str] = &[
+    "lorem", "ipsum", "dolor", "sit", "amet", "consectetur", "adipiscing", "elit", "sed", "do", "eiusmod",
+    "tempor", "incididunt", "ut", "labore", "et", "dolore", "magna", "aliqua", "enim", "ad", "minim", "veniam",
+    "quis", "nostrud", "exercitation", "ullamco", "laboris", "nisi", "aliquip", "ex", "ea", "commodo", "consequat",
+];
+
+/// An --item-template whose strings may hold placeholders, rendered afresh
+/// for every item so generated items vary like real records:
+///
+/// - `{{uuid}}`: a random (version 4 format) UUID
+/// - `{{randint LO HI}}`: an integer from LO to HI inclusive; a string that is
+///   only this placeholder becomes a number (`N`)
+/// - `{{lorem N}}`: N bytes of lorem ipsum text
+///
+/// Strings map to `S`, numbers to `N`, booleans to `BOOL`, null to `NULL`,
+/// arrays to `L` and objects to `M`. Draws are seeded by the item's index,
+/// so runs write the same items.
+pub struct ItemTemplate {
+    fields: serde_json::Map<String, Value>,
+    placeholder: Regex,
+}
+
+impl ItemTemplate {
+    pub fn parse(json: &str) -> Result<ItemTemplate, String> {
+        let fields = match serde_json::from_str(json).map_err(|e| format!("invalid item template: {}", e))? {
+            Value::Object(fields) => fields,
+            _ => return Err("item template must be a JSON object".to_owned()),
+        };
+        let template = ItemTemplate { fields, placeholder: Regex::new(r"\{\{\s*([a-z]+)((?:\s+[^\s}]+)*)\s*\}\}").unwrap() };
+        // every placeholder is checked by rendering once
+        let mut error = None;
+        template.render_value(&Value::Object(template.fields.clone()), &mut 0, 0, &mut error);
+        match error {
+            Some(e) => Err(e),
+            None => Ok(template),
+        }
+    }
+
+    /// The attributes of the `n`th item.
+    pub fn render(&self, n: usize) -> HashMap<String, AttributeValue> {
+        let mut draws = 0;
+        self.fields.iter()
+            .map(|(k, v)| (k.clone(), to_attribute(self.render_value(v, &mut draws, n, &mut None))))
+            .collect()
+    }
+
+    fn render_value(&self, value: &Value, draws: &mut usize, n: usize, error: &mut Option<String>) -> Value {
+        match value {
+            Value::String(s) => {
+                if let Some(whole) = self.placeholder.captures(s).filter(|c| c[0].len() == s.len() && &c[1] == "randint") {
+                    return self.expand(&whole, draws, n, error).parse::<i64>().map(Value::from).unwrap_or(Value::Null);
+                }
+                Value::String(self.placeholder.replace_all(s, |c: &Captures| self.expand(c, draws, n, error)).into_owned())
+            }
+            Value::Array(values) => Value::Array(values.iter().map(|v| self.render_value(v, draws, n, error)).collect()),
+            Value::Object(fields) => Value::Object(fields.iter()
+                .map(|(k, v)| (k.clone(), self.render_value(v, draws, n, error)))
+                .collect()),
+            other => other.clone(),
+        }
+    }
+
+    /// The text for one placeholder, each draw of an item seeded apart.
+    fn expand(&self, placeholder: &Captures, draws: &mut usize, n: usize, error: &mut Option<String>) -> String {
+        let mut draw = || {
+            *draws += 1;
+            keygen::mix(n.wrapping_mul(0x1_0000).wrapping_add(*draws))
+        };
+        let args: Vec<&str> = placeholder[2].split_whitespace().collect();
+        let numbers: Vec<i64> = args.iter().filter_map(|arg| arg.parse().ok()).collect();
+        match (&placeholder[1], numbers.as_slice()) {
+            ("uuid", []) if args.is_empty() => {
+                let (a, b) = (draw(), draw());
+                format!("{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}", a >> 32, (a >> 16) & 0xffff, a & 0xfff,
+                    0x8000 | ((b >> 48) & 0x3fff), b & 0xffff_ffff_ffff)
+            }
+            ("randint", [lo, hi]) if args.len() == 2 && lo <= hi => {
+                let span = (hi - lo) as u64 + 1;
+                (lo + (draw() % span) as i64).to_string()
+            }
+            ("lorem", [len]) if args.len() == 1 && *len >= 0 => {
+                let mut text = String::new();
+                let mut word = draw() as usize;
+                while text.len() < *len as usize {
+                    if !text.is_empty() {
+                        text.push(' ');
+                    }
+                    text.push_str(LOREM[word % LOREM.len()]);
+                    word += 1;
+                }
+                text.truncate(*len as usize);
+                text
+            }
+            _ => {
+                error.get_or_insert_with(|| format!(
+                    "invalid item template placeholder {:?}, expected {{{{uuid}}}}, {{{{randint LO HI}}}} or {{{{lorem N}}}}",
+                    &placeholder[0]));
+                String::new()
+            }
+        }
+    }
+}
+
+/// Parses a single JSON value into an attribute value.
+pub fn parse_value(json: &str) -> Result<AttributeValue, String> {
+    serde_json::from_str(json)
+        .map(to_attribute)
+        .map_err(|e| format!("invalid attribute value {:?}: {}", json, e))
+}
+
+fn to_attribute(value: Value) -> AttributeValue {
+    match value {
+        Value::Null => AttributeValue::Null(true),
+        Value::Bool(b) => AttributeValue::Bool(b),
+        Value::Number(n) => AttributeValue::N(n.to_string()),
+        Value::String(s) => AttributeValue::S(s),
+        Value::Array(values) => AttributeValue::L(values.into_iter().map(to_attribute).collect()),
+        Value::Object(fields) => AttributeValue::M(fields.into_iter().map(|(k, v)| (k, to_attribute(v))).collect()),
+    }
+}
+
+/// Replaces each `{name}` in `template` with its value.
+pub fn fill(template: &str, vars: &[(&str, &str)]) -> String {
+    vars.iter().fold(template.to_owned(), |text, (name, value)| text.replace(&format!("{{{}}}", name), value))
+}
+
+/// Quotes `value` as a PartiQL string literal.
+pub fn partiql_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
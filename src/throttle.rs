@@ -0,0 +1,102 @@
+use crate::request::{RequestError, Response};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Error codes DynamoDB returns when it rejects a request for capacity.
+const THROTTLE_CODES: &[&str] = &[
+    "ProvisionedThroughputExceededException",
+    "ThrottlingException",
+    "RequestLimitExceeded",
+];
+
+/// The throttle rate is judged over windows of this length...
+const WINDOW: Duration = Duration::from_secs(1);
+/// ...once at least this many requests completed in them.
+const MIN_WINDOW_REQUESTS: usize = 20;
+
+pub fn is_throttle(code: Option<&str>) -> bool {
+    code.is_some_and(|code| THROTTLE_CODES.contains(&code))
+}
+
+/// Completed and throttled requests, counted by the request tasks as they finish.
+#[derive(Default)]
+pub struct Counters {
+    completed: AtomicUsize,
+    throttled: AtomicUsize,
+}
+
+impl Counters {
+    pub fn record(&self, result: &Result<Response, RequestError>) {
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        if result.as_ref().is_err_and(|e| is_throttle(e.code.as_deref())) {
+            self.throttled.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn load(&self) -> (usize, usize) {
+        (self.completed.load(Ordering::Relaxed), self.throttled.load(Ordering::Relaxed))
+    }
+}
+
+/// A pause the scheduler took because too many requests were throttled.
+pub struct Intervention {
+    /// When the pause started, relative to the start of the run
+    pub offset: Duration,
+    pub throttle_rate: f64,
+}
+
+/// Pauses the scheduler while the share of throttled requests stays above
+/// --max-throttle-rate, so a run can't keep hammering a table that shares
+/// capacity with production traffic.
+pub struct Backoff {
+    max_rate: f64,
+    pause: Duration,
+    counters: Arc<Counters>,
+    window_start: Instant,
+    window_counts: (usize, usize),
+    pub interventions: Vec<Intervention>,
+}
+
+impl Backoff {
+    pub fn new(max_rate: f64, pause: Duration) -> Backoff {
+        Backoff {
+            max_rate,
+            pause,
+            counters: Arc::default(),
+            window_start: Instant::now(),
+            window_counts: (0, 0),
+            interventions: Vec::new(),
+        }
+    }
+
+    pub fn counters(&self) -> Arc<Counters> {
+        self.counters.clone()
+    }
+
+    /// Closes the current window if it is over, and sleeps out a pause when its
+    /// throttle rate was too high. Returns whether it paused.
+    pub async fn pause_if_throttled(&mut self, offset: Duration) -> bool {
+        if self.window_start.elapsed() < WINDOW {
+            return false;
+        }
+        let (completed, throttled) = self.counters.load();
+        let (window_completed, window_throttled) = (completed - self.window_counts.0, throttled - self.window_counts.1);
+        if window_completed < MIN_WINDOW_REQUESTS {
+            return false;
+        }
+        let throttle_rate = window_throttled as f64 / window_completed as f64;
+        let paused = throttle_rate > self.max_rate;
+        if paused {
+            println!("{:.1}% of requests throttled at {:.1}s, pausing for {}s",
+                throttle_rate * 100.0, offset.as_secs_f64(), self.pause.as_secs_f64());
+            self.interventions.push(Intervention { offset, throttle_rate });
+            tokio::time::sleep(self.pause).await;
+        }
+        // requests that finished during the pause are not judged again
+        self.window_start = Instant::now();
+        self.window_counts = self.counters.load();
+        paused
+    }
+}
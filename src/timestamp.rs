@@ -0,0 +1,96 @@
+use ::time::format_description::well_known::Rfc3339;
+use ::time::{OffsetDateTime, UtcOffset};
+use clap::ValueEnum;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How relative sort-key bounds such as `now-1h` are written.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// Milliseconds since the Unix epoch
+    EpochMillis,
+    /// Seconds since the Unix epoch
+    EpochSecs,
+    /// RFC 3339 in UTC, e.g. 2024-05-01T12:00:00Z
+    Rfc3339,
+}
+
+impl TimeFormat {
+    pub fn format(self, time: SystemTime) -> String {
+        match self {
+            TimeFormat::EpochMillis => time.duration_since(UNIX_EPOCH).unwrap().as_millis().to_string(),
+            TimeFormat::EpochSecs => time.duration_since(UNIX_EPOCH).unwrap().as_secs().to_string(),
+            TimeFormat::Rfc3339 => utc(time),
+        }
+    }
+}
+
+/// Parses a time relative to when a query is sent: `now`, or `now` plus or
+/// minus a duration such as `500ms`, `30m`, `1h` or `7d`, as milliseconds
+/// from then. Values that don't look like that (`Ok(None)`) are literal.
+pub fn parse_relative(s: &str) -> Result<Option<i64>, String> {
+    let Some(rest) = s.strip_prefix("now") else {
+        return Ok(None);
+    };
+    let (sign, amount) = match rest.as_bytes().first() {
+        None => return Ok(Some(0)),
+        Some(b'+') => (1, &rest[1..]),
+        Some(b'-') => (-1, &rest[1..]),
+        Some(_) => return Ok(None),
+    };
+    let invalid = || format!("invalid relative time {:?}, expected e.g. now-1h", s);
+    let (n, unit) = amount.split_at(amount.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?);
+    let n: i64 = n.parse().map_err(|_| invalid())?;
+    let scale = match unit {
+        "ms" => 1,
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        "d" => 86_400_000,
+        _ => return Err(invalid()),
+    };
+    n.checked_mul(scale).map(|ms| Some(sign * ms)).ok_or_else(invalid)
+}
+
+/// `time` moved by `offset_ms`, which may be negative.
+pub fn shift(time: SystemTime, offset_ms: i64) -> SystemTime {
+    let offset = Duration::from_millis(offset_ms.unsigned_abs());
+    if offset_ms < 0 {
+        time.checked_sub(offset).unwrap_or(UNIX_EPOCH)
+    } else {
+        time + offset
+    }
+}
+
+/// Parses a fixed UTC offset such as `-07:00`, `+0530` or `UTC`.
+pub fn parse_offset(s: &str) -> Result<UtcOffset, String> {
+    if s.eq_ignore_ascii_case("utc") || s == "Z" {
+        return Ok(UtcOffset::UTC);
+    }
+    let invalid = || format!("invalid UTC offset {:?}, expected e.g. -07:00", s);
+    let (sign, rest) = match s.as_bytes().first() {
+        Some(b'+') => (1, &s[1..]),
+        Some(b'-') => (-1, &s[1..]),
+        _ => return Err(invalid()),
+    };
+    let digits: String = rest.chars().filter(|c| *c != ':').collect();
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let hours: i8 = digits[..2].parse().map_err(|_| invalid())?;
+    let minutes: i8 = digits[2..].parse().map_err(|_| invalid())?;
+    UtcOffset::from_hms(sign * hours, sign * minutes, 0).map_err(|_| invalid())
+}
+
+/// Formats `time` as RFC 3339 in UTC.
+pub fn utc(time: SystemTime) -> String {
+    OffsetDateTime::from(time).format(&Rfc3339).unwrap()
+}
+
+/// Formats `time` in UTC, followed by the same instant at `offset` unless that is UTC.
+pub fn both(time: SystemTime, offset: UtcOffset) -> String {
+    if offset == UtcOffset::UTC {
+        return utc(time);
+    }
+    let local = OffsetDateTime::from(time).to_offset(offset).format(&Rfc3339).unwrap();
+    format!("{} ({})", utc(time), local)
+}
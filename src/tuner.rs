@@ -0,0 +1,108 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+
+/// Each concurrency level is judged over at least this long...
+const WINDOW: Duration = Duration::from_secs(2);
+/// ...and at least this many completed requests, so its p99 means something.
+const MIN_WINDOW_REQUESTS: usize = 100;
+/// Throughput has to improve by this factor for a higher level to be kept.
+const MIN_GAIN: f64 = 1.05;
+const MAX_PARALLELISM: usize = 1024;
+
+/// One concurrency level tried by [`Tuner`].
+pub struct Step {
+    pub parallelism: usize,
+    pub throughput: f64,
+    pub p99: Duration,
+}
+
+/// Searches for --auto-parallelism: doubles the concurrency cap while
+/// throughput keeps improving and p99 stays under the bound, then settles on
+/// the best level seen.
+pub struct Tuner {
+    max_p99: Duration,
+    /// Latencies of requests completed in the current window
+    latencies: Arc<Mutex<Vec<Duration>>>,
+    window_start: Instant,
+    parallelism: usize,
+    best: Option<(usize, f64)>,
+    /// Whether the search is over
+    pub settled: bool,
+    pub steps: Vec<Step>,
+}
+
+impl Tuner {
+    pub fn new(max_p99: Duration, parallelism: usize) -> Tuner {
+        Tuner {
+            max_p99,
+            latencies: Arc::default(),
+            window_start: Instant::now(),
+            parallelism,
+            best: None,
+            settled: false,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Where request tasks report their latency.
+    pub fn latencies(&self) -> Arc<Mutex<Vec<Duration>>> {
+        self.latencies.clone()
+    }
+
+    /// The best concurrency level so far: the highest throughput within the p99 bound.
+    pub fn best(&self) -> Option<usize> {
+        self.best.map(|(parallelism, _)| parallelism)
+    }
+
+    /// Judges the current level once its window is complete, and returns the
+    /// new concurrency cap when it changes.
+    pub fn adjust(&mut self) -> Option<usize> {
+        if self.settled || self.window_start.elapsed() < WINDOW {
+            return None;
+        }
+        let mut latencies = std::mem::take(&mut *self.latencies.lock().unwrap());
+        if latencies.len() < MIN_WINDOW_REQUESTS {
+            self.latencies.lock().unwrap().extend(latencies);
+            return None;
+        }
+        latencies.sort();
+        let p99 = latencies[((latencies.len() as f64 * 0.99).ceil() as usize).max(1) - 1];
+        let throughput = latencies.len() as f64 / self.window_start.elapsed().as_secs_f64();
+        self.steps.push(Step { parallelism: self.parallelism, throughput, p99 });
+        self.window_start = Instant::now();
+
+        let improved = self.best.is_none_or(|(_, best)| throughput > best * MIN_GAIN);
+        if p99 <= self.max_p99 && improved {
+            self.best = Some((self.parallelism, throughput));
+            if self.parallelism < MAX_PARALLELISM {
+                self.parallelism = (self.parallelism * 2).min(MAX_PARALLELISM);
+                return Some(self.parallelism);
+            }
+        }
+        self.settled = true;
+        let chosen = self.best().unwrap_or(self.parallelism);
+        (chosen != self.parallelism).then(|| {
+            self.parallelism = chosen;
+            chosen
+        })
+    }
+}
+
+/// Moves `semaphore`'s capacity from `from` to `to` permits. Permits held by
+/// requests in flight are taken back as those requests complete.
+pub fn resize(semaphore: &Arc<Semaphore>, from: usize, to: usize) {
+    if to >= from {
+        semaphore.add_permits(to - from);
+        return;
+    }
+    let excess = from - to;
+    let forgotten = semaphore.forget_permits(excess);
+    if forgotten < excess {
+        let semaphore = semaphore.clone();
+        tokio::spawn(async move {
+            semaphore.acquire_many_owned((excess - forgotten) as u32).await.unwrap().forget();
+        });
+    }
+}
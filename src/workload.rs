@@ -0,0 +1,674 @@
+use crate::keygen;
+use crate::request::{KeyType, Request};
+use crate::timestamp::{self, TimeFormat};
+use crate::{template, BenchArgs, Cli, FailureKind, Operation};
+use aws_sdk_dynamodb::operation::query::builders::QueryFluentBuilder;
+use aws_sdk_dynamodb::types::{
+    AttributeValue, BatchStatementRequest, Get, KeysAndAttributes, Put, PutRequest, TransactGetItem, TransactWriteItem,
+    WriteRequest,
+};
+use aws_sdk_dynamodb::Client;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, ValueEnum};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Built-in operation mixes modelled on the YCSB core workloads, so results
+/// can be compared with published numbers for other databases.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// Update heavy: 50% reads, 50% updates
+    YcsbA,
+    /// Read mostly: 95% reads, 5% updates
+    YcsbB,
+    /// Read only
+    YcsbC,
+    /// Read latest: 95% reads of the last 10% of --sort-value, 5% inserts
+    YcsbD,
+    /// Short ranges: 95% range queries, 5% inserts
+    YcsbE,
+    /// Read-modify-write: 50% reads, 50% GetItem followed by PutItem
+    YcsbF,
+}
+
+/// Sort key conditions other than the range given by --sort-start/--sort-end.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortOp {
+    /// Sort keys starting with --sort-start
+    #[value(alias = "begins_with")]
+    BeginsWith,
+    /// Sort keys after --sort-start
+    Gt,
+    /// Sort keys before --sort-end
+    Lt,
+    /// The sort key --sort-start
+    Eq,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Component {
+    Read,
+    ReadLatest,
+    Update,
+    Insert,
+    Scan,
+    ReadModifyWrite,
+}
+
+impl Preset {
+    /// Human readable mix, e.g. `Read 95%, Update 5%`.
+    pub fn describe(self) -> String {
+        self.mix().iter()
+            .map(|(component, weight)| format!("{:?} {}%", component, weight * 100.0))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Whether the mix includes writes.
+    pub fn writes(self) -> bool {
+        self.mix().iter().any(|(component, _)| matches!(component, Component::Update | Component::Insert | Component::ReadModifyWrite))
+    }
+
+    fn mix(self) -> &'static [(Component, f64)] {
+        match self {
+            Preset::YcsbA => &[(Component::Read, 0.5), (Component::Update, 0.5)],
+            Preset::YcsbB => &[(Component::Read, 0.95), (Component::Update, 0.05)],
+            Preset::YcsbC => &[(Component::Read, 1.0)],
+            Preset::YcsbD => &[(Component::ReadLatest, 0.95), (Component::Insert, 0.05)],
+            Preset::YcsbE => &[(Component::Scan, 0.95), (Component::Insert, 0.05)],
+            Preset::YcsbF => &[(Component::Read, 0.5), (Component::ReadModifyWrite, 0.5)],
+        }
+    }
+}
+
+/// One kind of request in a workload and its share of the traffic.
+struct Entry {
+    weight: f64,
+    hits: Vec<Request>,
+    /// Requests for keys known to be absent
+    misses: Vec<Request>,
+    next: Cell<usize>,
+    /// With --partition-weight, the share of `hits` built for each partition
+    /// value; empty when partition values are cycled evenly
+    partitions: Vec<Partition>,
+}
+
+struct Partition {
+    weight: f64,
+    hits: Range<usize>,
+    next: Cell<usize>,
+}
+
+/// The requests a run draws from. Warmup and measurement draw through the
+/// same `request` so the table is warmed with the traffic that gets measured.
+///
+/// [`Mix`] covers --operation and --workload; custom operation shapes
+/// implement this trait in [`crate::custom`].
+pub trait Workload {
+    /// The `i`th request of a phase, and whether it targets an absent key.
+    fn request(&self, i: usize) -> (Request, bool);
+
+    /// Distinct requests the workload cycles through.
+    fn len(&self) -> usize;
+
+    /// Requests available before --working-set was applied.
+    fn keyspace(&self) -> usize {
+        self.len()
+    }
+
+    /// The `i`th request of worker `shard` of `shards`, each owning a shard of
+    /// the keyspace under --worker-key-affinity; workloads that don't generate
+    /// their keys draw from all of them.
+    fn request_on_shard(&self, i: usize, _shard: usize, _shards: usize) -> (Request, bool) {
+        self.request(i)
+    }
+}
+
+/// The workload of an --operation, or a weighted mix for --workload.
+pub struct Mix {
+    entries: Vec<Entry>,
+    miss_ratio: f64,
+    sort_window: Option<SortWindow>,
+    relative_bounds: Option<RelativeBounds>,
+    /// Requests available before --working-set was applied
+    keyspace: usize,
+}
+
+impl Mix {
+    pub fn build(client: &Client, cli: &Cli, args: &BenchArgs) -> Mix {
+        let components: Vec<(Option<Component>, f64)> = match args.workload {
+            Some(preset) => preset.mix().iter().map(|(c, w)| (Some(*c), *w)).collect(),
+            None => vec![(None, 1.0)],
+        };
+        let mut keyspace = 0;
+        let entries = components.into_iter().map(|(component, weight)| {
+            let build = |args: &BenchArgs| match component {
+                Some(component) => make_component(client, cli, args, component),
+                None => make_requests(client, cli, args),
+            };
+            let mut partitions = Vec::new();
+            let mut hits = if args.partition_weight.is_empty() {
+                build(args)
+            } else {
+                // built per partition value, so requests can be drawn by its weight
+                let mut hits = Vec::new();
+                for (value, weight) in args.partition_value.iter().zip(&args.partition_weight) {
+                    let start = hits.len();
+                    hits.extend(build(&BenchArgs { partition_value: vec![value.clone()], ..args.clone() }));
+                    partitions.push(Partition { weight: *weight, hits: start..hits.len(), next: Cell::new(0) });
+                }
+                hits
+            };
+            keyspace += hits.len();
+            hits.truncate(((hits.len() as f64 * args.working_set).ceil() as usize).max(1));
+            // only reads get misses; a "missing" write would just create the item
+            let reads = match component {
+                Some(component) => matches!(component, Component::Read | Component::ReadLatest | Component::Scan),
+                None => matches!(args.operation, Operation::Query | Operation::GetItem),
+            };
+            let misses = if reads {
+                build(&BenchArgs {
+                    partition_value: vec![cli.partition_type.missing(0)],
+                    ..args.clone()
+                })
+            } else {
+                Vec::new()
+            };
+            Entry { weight, hits, misses, next: Cell::new(0), partitions }
+        }).collect();
+        let sort_window = args.sort_window.map(|width| SortWindow::new(args, width, cli.sort_type));
+        Mix { entries, miss_ratio: args.miss_ratio, sort_window, relative_bounds: RelativeBounds::new(cli, args), keyspace }
+    }
+
+    /// Moves the sort range of request `i` as --sort-window and relative bounds ask.
+    fn place(&self, mut request: Request, i: usize) -> Request {
+        if let Some(window) = &self.sort_window {
+            request = window.apply(request, i);
+        }
+        match &self.relative_bounds {
+            Some(bounds) => bounds.apply(request),
+            None => request,
+        }
+    }
+
+    /// Interleaves entries in proportion to their weights using a
+    /// low-discrepancy sequence, so mixes are reproducible without an RNG.
+    fn pick_entry(&self, i: usize) -> usize {
+        const GOLDEN: f64 = 0.618_033_988_749_894_9;
+        pick_weighted(self.entries.iter().map(|entry| entry.weight), ((i as f64 + 0.5) * GOLDEN).fract())
+    }
+}
+
+/// The index whose share of the total weight `u` (in [0, 1)) falls in.
+pub fn pick_weighted(weights: impl Iterator<Item = f64> + Clone, u: f64) -> usize {
+    let total: f64 = weights.clone().sum();
+    let mut u = u * total;
+    let mut last = 0;
+    for (n, weight) in weights.enumerate() {
+        if u < weight {
+            return n;
+        }
+        u -= weight;
+        last = n;
+    }
+    last
+}
+
+impl Workload for Mix {
+    fn request(&self, i: usize) -> (Request, bool) {
+        let entry = &self.entries[self.pick_entry(i)];
+        let n = entry.next.replace(entry.next.get() + 1);
+        let miss = is_picked(i, self.miss_ratio) && !entry.misses.is_empty();
+        if !miss && !entry.partitions.is_empty() {
+            // a second sequence, so partition picks don't line up with entry picks
+            const SILVER: f64 = 0.414_213_562_373_095_1;
+            let u = ((i as f64 + 0.5) * SILVER).fract();
+            let partition = &entry.partitions[pick_weighted(entry.partitions.iter().map(|p| p.weight), u)];
+            let n = partition.next.replace(partition.next.get() + 1);
+            let request = entry.hits[partition.hits.start + n % partition.hits.len()].clone();
+            return (self.place(request, i), false);
+        }
+        let requests = if miss { &entry.misses } else { &entry.hits };
+        (self.place(requests[n % requests.len()].clone(), i), miss)
+    }
+
+    /// Requests left after --working-set was applied.
+    fn len(&self) -> usize {
+        self.entries.iter().map(|entry| entry.hits.len()).sum()
+    }
+
+    fn keyspace(&self) -> usize {
+        self.keyspace
+    }
+}
+
+/// A --sort-window of numeric sort keys, moved across --sort-start..--sort-end
+/// from query to query so repeated queries don't all hit the same warm range.
+struct SortWindow {
+    start: u64,
+    /// Window starts range over `start..=start + span`
+    span: u64,
+    width: u64,
+    /// String sort values are zero-padded to the width of --sort-start, so
+    /// they keep sorting as strings
+    digits: usize,
+    key_type: KeyType,
+}
+
+impl SortWindow {
+    /// Expects --sort-start and --sort-end to be validated as numbers at least `width` apart.
+    fn new(args: &BenchArgs, width: u64, key_type: KeyType) -> SortWindow {
+        let start_value = args.sort_start.as_deref().expect("--sort-window requires --sort-start");
+        let start: u64 = start_value.parse().expect("--sort-start is validated as a number");
+        let end: u64 = args.sort_end.as_deref().and_then(|end| end.parse().ok()).expect("--sort-end is validated as a number");
+        SortWindow { start, span: end - start - width, width, digits: start_value.len(), key_type }
+    }
+
+    /// Moves the key condition of a query to the window for request `i`.
+    fn apply(&self, request: Request, i: usize) -> Request {
+        let Request::Query(query) = request else {
+            return request;
+        };
+        let start = self.start + ((keygen::uniform(i) * (self.span + 1) as f64) as u64).min(self.span);
+        let value = |n: u64| match self.key_type {
+            KeyType::N => AttributeValue::N(n.to_string()),
+            _ => AttributeValue::S(format!("{:0width$}", n, width = self.digits)),
+        };
+        Request::Query(Box::new(query
+            .expression_attribute_values(":start", value(start))
+            .expression_attribute_values(":end", value(start + self.width))))
+    }
+}
+
+/// --sort-start/--sort-end given relative to now (e.g. `now-1h`), resolved as
+/// each query is drawn so that long and repeated runs keep reading current buckets.
+pub struct RelativeBounds {
+    /// Milliseconds from now
+    start: Option<i64>,
+    end: Option<i64>,
+    format: TimeFormat,
+    key_type: KeyType,
+}
+
+impl RelativeBounds {
+    /// None unless a bound is relative. Expects the bounds to be validated
+    /// with [`timestamp::parse_relative`].
+    pub fn new(cli: &Cli, args: &BenchArgs) -> Option<RelativeBounds> {
+        let offset = |bound: &Option<String>| bound.as_deref()
+            .and_then(|bound| timestamp::parse_relative(bound).expect("relative bounds are validated"));
+        let (start, end) = (offset(&args.sort_start), offset(&args.sort_end));
+        (start.is_some() || end.is_some()).then_some(RelativeBounds { start, end, format: args.time_format, key_type: cli.sort_type })
+    }
+
+    /// Resolves the relative bounds of a query against the current time.
+    pub fn apply(&self, request: Request) -> Request {
+        let Request::Query(query) = request else {
+            return request;
+        };
+        let now = SystemTime::now();
+        let mut query = *query;
+        for (name, offset) in [(":start", self.start), (":end", self.end)] {
+            if let Some(offset) = offset {
+                let value = self.format.format(timestamp::shift(now, offset));
+                query = query.expression_attribute_values(name, self.key_type.value(&value));
+            }
+        }
+        Request::Query(Box::new(query))
+    }
+}
+
+/// The value of a --sort-start/--sort-end bound, a relative one resolved
+/// against the current time, as [`RelativeBounds::apply`] then does per query.
+fn bound_value(cli: &Cli, args: &BenchArgs, bound: &str) -> AttributeValue {
+    match timestamp::parse_relative(bound).expect("relative bounds are validated") {
+        Some(offset) => cli.sort_type.value(&args.time_format.format(timestamp::shift(SystemTime::now(), offset))),
+        None => cli.sort_type.value(bound),
+    }
+}
+
+/// Spreads picks evenly over the run: request `i` is picked whenever the
+/// running pick count `floor(i * ratio)` ticks over.
+pub fn is_picked(i: usize, ratio: f64) -> bool {
+    ((i + 1) as f64 * ratio).floor() > (i as f64 * ratio).floor()
+}
+
+fn make_component(client: &Client, cli: &Cli, args: &BenchArgs, component: Component) -> Vec<Request> {
+    match component {
+        Component::Read => make_get_item(client, cli, args),
+        Component::ReadLatest => {
+            // the latest sort values, or partition values without a sort key
+            let values = if cli.no_sort_key { &args.partition_value } else { &args.sort_value };
+            let latest = values[values.len().saturating_sub(((values.len() as f64 * 0.1).ceil() as usize).max(1))..].to_vec();
+            make_get_item(client, cli, &if cli.no_sort_key {
+                BenchArgs { partition_value: latest, ..args.clone() }
+            } else {
+                BenchArgs { sort_value: latest, ..args.clone() }
+            })
+        }
+        Component::Update => make_update_item(client, cli, args),
+        Component::Scan => make_query(client, cli, args),
+        Component::ReadModifyWrite => make_get_item(client, cli, args).into_iter()
+            .zip(make_put_item(client, cli, args))
+            .map(|(read, write)| Request::ReadModifyWrite(Box::new(read), Box::new(write)))
+            .collect(),
+        Component::Insert => {
+            // Fresh sort values for every insert the run can make (fresh
+            // partition values without a sort key), tagged with the start time
+            // so repeated runs don't overwrite each other.
+            let run = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+            let planned = args.stages().iter().map(|stage| stage.planned_queries()).sum::<usize>();
+            let fresh = |key_type: KeyType, inserts: usize| -> Vec<String> {
+                (0..inserts.max(1)).map(|n| match key_type {
+                    KeyType::S => format!("ycsb-insert:{}:{}", run, n),
+                    KeyType::N => format!("{}{:09}", run, n),
+                    KeyType::B => format!("{:016x}{:016x}", run, n),
+                }).collect()
+            };
+            make_put_item(client, cli, &if cli.no_sort_key {
+                BenchArgs { partition_value: fresh(cli.partition_type, args.warmup_queries + planned), ..args.clone() }
+            } else {
+                let inserts = (args.warmup_queries + planned).div_ceil(args.partition_value.len().max(1));
+                BenchArgs { sort_value: fresh(cli.sort_type, inserts), ..args.clone() }
+            })
+        }
+    }
+}
+
+pub fn make_requests(client: &Client, cli: &Cli, args: &BenchArgs) -> Vec<Request> {
+    match args.operation {
+        Operation::Query => make_query(client, cli, args),
+        Operation::GetItem => make_get_item(client, cli, args),
+        Operation::PutItem => make_put_item(client, cli, args),
+        Operation::UpdateItem => make_update_item(client, cli, args),
+        Operation::BatchGetItem => make_batch_get_item(client, cli, args),
+        Operation::BatchWriteItem => make_batch_write_item(client, cli, args),
+        Operation::TransactWriteItems => make_transact_write_items(client, cli, args),
+        Operation::TransactGetItems => make_transact_get_items(client, cli, args),
+        Operation::ExecuteStatement => make_statements(cli, args).into_iter().map(|statement| {
+            Request::ExecuteStatement(Box::new(client.execute_statement().statement(statement)))
+        }).collect(),
+        Operation::BatchExecuteStatement => {
+            let statements: Vec<_> = make_statements(cli, args).into_iter().map(|statement| {
+                BatchStatementRequest::builder().statement(statement).build().expect("statement is set")
+            }).collect();
+            statements.chunks(args.batch_size).map(|statements| Request::BatchExecuteStatement {
+                client: client.clone(),
+                statements: statements.to_vec(),
+            }).collect()
+        }
+        Operation::Error => vec![make_failing_request(client, cli, args.error_kind)],
+    }
+}
+
+/// A request DynamoDB rejects without doing any work. None of these can write.
+fn make_failing_request(client: &Client, cli: &Cli, kind: FailureKind) -> Request {
+    let missing = missing_key(cli, 0);
+    match kind {
+        FailureKind::MissingTable => Request::GetItem(Box::new(client.get_item()
+            .table_name(format!("{}__dynamodbbench_missing__", cli.table))
+            .set_key(Some(missing)))),
+        FailureKind::ConditionalCheck => Request::PutItem(Box::new(client.put_item()
+            .table_name(&cli.table)
+            .set_item(Some(missing))
+            .condition_expression("attribute_exists(#pk) AND attribute_not_exists(#pk)")
+            .expression_attribute_names("#pk", &cli.partition_key))),
+        FailureKind::Validation => Request::Query(Box::new(client.query()
+            .table_name(&cli.table)
+            .key_condition_expression("#pk = :pk")
+            .expression_attribute_names("#pk", &cli.partition_key))),
+    }
+}
+
+/// The attributes written with the `n`th item besides its key: the
+/// --item-template rendered for that item, or the --item-size payload split
+/// over --attribute-count attributes.
+fn items(args: &BenchArgs) -> impl Fn(usize) -> HashMap<String, AttributeValue> {
+    let template = args.item_template.as_deref().map(|template| template::ItemTemplate::parse(template)
+        .unwrap_or_else(|e| Cli::command().error(ErrorKind::ValueValidation, e).exit()));
+    let payload = match args.attribute_count {
+        1 => HashMap::from([("payload".to_owned(), AttributeValue::S(json_payload(args.item_size)))]),
+        n => (0..n).map(|a| (format!("attr{}", a), AttributeValue::S("x".repeat((args.item_size / n).max(1)))))
+            .collect(),
+    };
+    move |n| match &template {
+        Some(template) => template.render(n),
+        None => payload.clone(),
+    }
+}
+
+/// Attributes written with the first item, besides its key; typical of them all.
+pub fn base_item(args: &BenchArgs) -> HashMap<String, AttributeValue> {
+    items(args)(0)
+}
+
+fn make_update_item(client: &Client, cli: &Cli, args: &BenchArgs) -> Vec<Request> {
+    let values = args.expression_value.iter().map(|(name, json)| {
+        template::parse_value(json)
+            .map(|value| (name.clone(), value))
+            .unwrap_or_else(|e| Cli::command().error(ErrorKind::ValueValidation, e).exit())
+    }).collect();
+    let update = client.update_item()
+        .table_name(&cli.table)
+        .update_expression(&args.update_expression)
+        .set_expression_attribute_names(Some(args.expression_name.iter().cloned().collect()))
+        .set_expression_attribute_values(Some(values));
+
+    key_pairs(cli, args).map(|(pk, sk)| {
+        Request::UpdateItem(Box::new(update.clone().set_key(Some(primary_key(cli, pk, sk)))))
+    }).collect()
+}
+
+fn make_transact_write_items(client: &Client, cli: &Cli, args: &BenchArgs) -> Vec<Request> {
+    let items = items(args);
+    let writes: Vec<_> = key_pairs(cli, args).enumerate().map(|(n, (pk, sk))| {
+        let mut item = items(n);
+        item.extend(primary_key(cli, pk, sk));
+        let put = Put::builder().table_name(&cli.table).set_item(Some(item)).build().expect("table and item are set");
+        TransactWriteItem::builder().put(put).build()
+    }).collect();
+
+    writes.chunks(args.batch_size).map(|items| Request::TransactWriteItems {
+        client: client.clone(),
+        items: items.to_vec(),
+    }).collect()
+}
+
+fn make_transact_get_items(client: &Client, cli: &Cli, args: &BenchArgs) -> Vec<Request> {
+    let gets: Vec<_> = key_pairs(cli, args).map(|(pk, sk)| {
+        let get = Get::builder()
+            .table_name(&cli.table)
+            .set_key(Some(primary_key(cli, pk, sk)))
+            .build()
+            .expect("table and key are set");
+        TransactGetItem::builder().get(get).build()
+    }).collect();
+
+    gets.chunks(args.batch_size).map(|items| Request::TransactGetItems {
+        client: client.clone(),
+        items: items.to_vec(),
+    }).collect()
+}
+
+/// The PartiQL equivalent of the Query (or GetItem, with --sort-value) this run would otherwise issue.
+fn default_statement(args: &BenchArgs) -> String {
+    let mut statement = "SELECT * FROM \"{table}\" WHERE \"{partition_key}\" = {partition_value}".to_owned();
+    statement.push_str(match (args.sort_value.is_empty(), args.sort_op, &args.sort_start, &args.sort_end) {
+        (false, _, _, _) => " AND \"{sort_key}\" = {sort_value}",
+        (true, Some(SortOp::BeginsWith), _, _) => " AND begins_with(\"{sort_key}\", {sort_start})",
+        (true, Some(SortOp::Gt), _, _) => " AND \"{sort_key}\" > {sort_start}",
+        (true, Some(SortOp::Lt), _, _) => " AND \"{sort_key}\" < {sort_end}",
+        (true, Some(SortOp::Eq), _, _) => " AND \"{sort_key}\" = {sort_start}",
+        (true, None, Some(_), Some(_)) => " AND \"{sort_key}\" BETWEEN {sort_start} AND {sort_end}",
+        (true, None, Some(_), None) => " AND \"{sort_key}\" >= {sort_start}",
+        (true, None, None, Some(_)) => " AND \"{sort_key}\" <= {sort_end}",
+        (true, None, None, None) => "",
+    });
+    statement
+}
+
+/// One statement per partition value (and sort value, if any).
+pub fn make_statements(cli: &Cli, args: &BenchArgs) -> Vec<String> {
+    let template = args.statement.clone().unwrap_or_else(|| default_statement(args));
+    // N keys are bare numbers in PartiQL; B keys are rejected up front
+    let literal = |key_type: KeyType, value: &str| match key_type {
+        KeyType::N => value.to_owned(),
+        _ => template::partiql_string(value),
+    };
+    let bound = |value: &Option<String>| value.as_deref().map(|value| literal(cli.sort_type, value)).unwrap_or_default();
+    let (sort_start, sort_end) = (bound(&args.sort_start), bound(&args.sort_end));
+    let sort_values: Vec<Option<&String>> = if args.sort_value.is_empty() {
+        vec![None]
+    } else {
+        args.sort_value.iter().map(Some).collect()
+    };
+
+    args.partition_value.iter().flat_map(|pk| sort_values.iter().map(move |sk| (pk, *sk))).map(|(pk, sk)| {
+        template::fill(&template, &[
+            ("table", &cli.table),
+            ("partition_key", &cli.partition_key),
+            ("sort_key", &cli.sort_key),
+            ("partition_value", &literal(cli.partition_type, pk)),
+            ("sort_value", &sk.map(|sk| literal(cli.sort_type, sk)).unwrap_or_default()),
+            ("sort_start", &sort_start),
+            ("sort_end", &sort_end),
+        ])
+    }).collect()
+}
+
+fn make_batch_write_item(client: &Client, cli: &Cli, args: &BenchArgs) -> Vec<Request> {
+    let items = items(args);
+    let writes: Vec<_> = key_pairs(cli, args).enumerate().map(|(n, (pk, sk))| {
+        let mut item = items(n);
+        item.extend(primary_key(cli, pk, sk));
+        let put = PutRequest::builder().set_item(Some(item)).build().expect("item is set");
+        WriteRequest::builder().put_request(put).build()
+    }).collect();
+
+    writes.chunks(args.batch_size).map(|batch| Request::BatchWriteItem {
+        client: client.clone(),
+        request_items: HashMap::from([(cli.table.clone(), batch.to_vec())]),
+    }).collect()
+}
+
+/// Every partition value with every sort value, or on its own with --no-sort-key.
+fn key_pairs<'a>(cli: &Cli, args: &'a BenchArgs) -> impl Iterator<Item = (&'a str, Option<&'a str>)> + 'a {
+    let sort_values: Vec<Option<&str>> = if cli.no_sort_key {
+        vec![None]
+    } else {
+        args.sort_value.iter().map(|sk| Some(sk.as_str())).collect()
+    };
+    args.partition_value.iter().flat_map(move |pk| sort_values.clone().into_iter().map(move |sk| (pk.as_str(), sk)))
+}
+
+/// The key of the item `(pk, sk)`; `sk` is None with --no-sort-key.
+pub fn primary_key(cli: &Cli, pk: &str, sk: Option<&str>) -> HashMap<String, AttributeValue> {
+    let mut key = HashMap::from([(cli.partition_key.clone(), cli.partition_type.value(pk))]);
+    key.extend(sk.map(|sk| (cli.sort_key.clone(), cli.sort_type.value(sk))));
+    key
+}
+
+/// The `n`th key known to be absent from the table.
+pub fn missing_key(cli: &Cli, n: usize) -> HashMap<String, AttributeValue> {
+    let sort = (!cli.no_sort_key).then(|| cli.sort_type.missing(0));
+    primary_key(cli, &cli.partition_type.missing(n), sort.as_deref())
+}
+
+fn make_batch_get_item(client: &Client, cli: &Cli, args: &BenchArgs) -> Vec<Request> {
+    let keys: Vec<_> = key_pairs(cli, args).map(|(pk, sk)| primary_key(cli, pk, sk)).collect();
+
+    // Missing keys take the place of real ones so the batch size stays fixed.
+    keys.chunks(args.batch_size - args.missing_keys).enumerate().map(|(i, batch)| {
+        let missing = (0..args.missing_keys).map(|j| missing_key(cli, 1 + i * args.missing_keys + j));
+        let keys_and_attributes = KeysAndAttributes::builder()
+            .set_keys(Some(batch.iter().cloned().chain(missing).collect()))
+            .build()
+            .expect("keys are set");
+        Request::BatchGetItem {
+            client: client.clone(),
+            request_items: HashMap::from([(cli.table.clone(), keys_and_attributes)]),
+        }
+    }).collect()
+}
+
+/// A JSON document of exactly `size` bytes (or the smallest valid one if `size` is tiny).
+pub fn json_payload(size: usize) -> String {
+    let mut payload = String::from("{\"data\":\"");
+    let padding = size.saturating_sub(payload.len() + 2);
+    payload.extend(std::iter::repeat_n('x', padding));
+    payload.push_str("\"}");
+    payload
+}
+
+pub fn make_put_item(client: &Client, cli: &Cli, args: &BenchArgs) -> Vec<Request> {
+    let put = client.put_item().table_name(&cli.table);
+    let items = items(args);
+
+    key_pairs(cli, args).enumerate().map(|(n, (pk, sk))| {
+        let mut item = items(n);
+        item.extend(primary_key(cli, pk, sk));
+        Request::PutItem(Box::new(put.clone().set_item(Some(item))))
+    }).collect()
+}
+
+pub fn make_get_item(client: &Client, cli: &Cli, args: &BenchArgs) -> Vec<Request> {
+    let get = client.get_item().table_name(&cli.table);
+
+    key_pairs(cli, args).map(|(pk, sk)| {
+        Request::GetItem(Box::new(get.clone().set_key(Some(primary_key(cli, pk, sk)))))
+    }).collect()
+}
+
+/// Has `query` return only the `projection` attributes, if any are given.
+/// They are referred to by name placeholders, as some may be reserved words.
+pub fn project(mut query: QueryFluentBuilder, projection: &[String]) -> QueryFluentBuilder {
+    if projection.is_empty() {
+        return query;
+    }
+    let names: Vec<_> = (0..projection.len()).map(|n| format!("#proj{}", n)).collect();
+    for (name, attribute) in names.iter().zip(projection) {
+        query = query.expression_attribute_names(name, attribute);
+    }
+    query.projection_expression(names.join(", "))
+}
+
+pub fn make_query(client: &Client, cli: &Cli, args: &BenchArgs) -> Vec<Request> {
+    let mut query_without_pkey = client
+        .query()
+        .table_name(&cli.table)
+        .set_index_name(args.index_name.clone())
+        .set_limit(args.limit)
+        .set_scan_index_forward(args.descending.then_some(false))
+        .expression_attribute_names("#pk", &cli.partition_key);
+    query_without_pkey = project(query_without_pkey, &args.projection);
+
+    let sort_key_condition = match (args.sort_op, args.sort_start.is_some(), args.sort_end.is_some()) {
+        (Some(SortOp::BeginsWith), true, _) => " AND begins_with(#sk, :start)",
+        (Some(SortOp::Gt), true, _) => " AND #sk > :start",
+        (Some(SortOp::Eq), true, _) => " AND #sk = :start",
+        (Some(SortOp::Lt), _, true) => " AND #sk < :end",
+        (_, true, true) =>  " AND #sk BETWEEN :start AND :end",
+        (_, true, false) => " AND #sk >= :start",
+        (_, false, true) => " AND #sk <= :end",
+        (_, false, false) => ""
+    };
+
+    // only the bounds the condition uses, as DynamoDB rejects unused values
+    if let Some(start) = args.sort_start.as_ref().filter(|_| sort_key_condition.contains(":start")) {
+        query_without_pkey = query_without_pkey
+            .expression_attribute_values(":start", bound_value(cli, args, start))
+            .expression_attribute_names("#sk", &cli.sort_key)
+    }
+    if let Some(end) = args.sort_end.as_ref().filter(|_| sort_key_condition.contains(":end")) {
+        query_without_pkey = query_without_pkey
+            .expression_attribute_values(":end", bound_value(cli, args, end))
+            .expression_attribute_names("#sk", &cli.sort_key)
+    }
+
+    query_without_pkey = query_without_pkey.key_condition_expression(format!("#pk = :pk{}", sort_key_condition));
+
+    args.partition_value.iter().map(|val| {
+        Request::Query(Box::new(query_without_pkey.clone().expression_attribute_values(":pk", cli.partition_type.value(val))))
+    }).collect()
+}